@@ -2,55 +2,204 @@ use std::path::PathBuf;
 
 use clap::{ArgGroup, Parser, Subcommand};
 
+use crate::logger::{LinePrefix, LogFormat, LogLevel};
+
 #[derive(Parser, Debug)]
 #[command(name = "arc")]
 #[command(version, about = "A scriptable automation tool.", long_about = None)]
 pub struct Cli {
+    /// Show details such as negotiated SSH algorithms, same as `--log-level debug`
+    #[arg(short, long, global = true, conflicts_with_all = ["quiet", "log_level"])]
+    pub verbose: bool,
+    /// Only show errors, same as `--log-level error`
+    #[arg(short, long, global = true, conflicts_with = "log_level")]
+    pub quiet: bool,
+    /// Also write log output to this file, without colors
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Lowest level of messages to show
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Info)]
+    pub log_level: LogLevel,
+    /// Format of log output
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+    /// Print progress as plain lines without spinners and bars, the default
+    /// if stdout is not a terminal
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+    /// Prefix output lines with the system name, or with the system and task
+    /// names, as when systems run in parallel
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "PREFIX",
+        num_args = 0..=1,
+        default_missing_value = "system"
+    )]
+    pub prefix: Option<LinePrefix>,
     #[command(subcommand)]
     pub command: Command,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
-    /// Initialize project with type definitions
-    Init { project_root: PathBuf },
+    /// Initialize project with type definitions and starter files
+    Init {
+        project_root: PathBuf,
+        /// Starter files to create
+        #[arg(long, value_enum, default_value_t = InitTemplate::Minimal)]
+        template: InitTemplate,
+    },
     /// Execute tasks
-    #[command(group = ArgGroup::new("tags").required(true).args(["tag", "all_tags"]))]
+    #[command(group = ArgGroup::new("tags").required(true).multiple(true).args(["tag", "task", "all_tags"]))]
     #[command(group = ArgGroup::new("targets").required(true).args(["group", "system", "all_systems"]))]
     Run {
-        /// Select tasks by tag
+        /// Select tasks by tag (supports `*` and `?` patterns)
         #[arg(short, long)]
         tag: Vec<String>,
-        /// Run tasks only on specific groups
+        /// Exclude tasks by tag, even if they are selected by another tag (supports `*` and `?` patterns)
+        #[arg(long, value_name = "TAG")]
+        skip_tag: Vec<String>,
+        /// Select tasks by name
+        #[arg(long)]
+        task: Vec<String>,
+        /// Run tasks only on specific groups (supports `*` and `?` patterns)
         #[arg(short, long)]
         group: Vec<String>,
-        /// Run tasks only on specific systems
+        /// Run tasks only on specific systems (supports `*` and `?` patterns)
         #[arg(short, long)]
         system: Vec<String>,
-        /// List selected tasks without executing
+        /// Narrow the selected systems to these (comma separated, supports `*` and `?` patterns)
+        #[arg(long, value_name = "SYSTEMS", value_delimiter = ',')]
+        limit: Vec<String>,
+        /// Show changes to file contents written by tasks
         #[arg(long)]
+        diff: bool,
+        /// List selected tasks without executing
+        #[arg(long, visible_alias = "dry-run")]
         list: bool,
+        /// Validate the definitions and the selection without executing
+        #[arg(long, conflicts_with = "list")]
+        check: bool,
+        /// Output the listed execution plan as JSON
+        #[arg(long, requires = "list")]
+        json: bool,
+        /// Skip planned tasks until the named task and run from there
+        #[arg(long, value_name = "TASK")]
+        start_at: Option<String>,
+        /// Maximum number of task retries across the whole run
+        #[arg(long, value_name = "RETRIES")]
+        retry_budget: Option<u32>,
+        /// Run up to this many systems at the same time
+        #[arg(long, value_name = "SYSTEMS", value_parser = clap::value_parser!(u32).range(1..))]
+        parallel: Option<u32>,
+        /// Write a JSON report of the run with per-task timing to this file
+        #[arg(long, value_name = "PATH")]
+        report: Option<PathBuf>,
+        /// Set a variable available as `arc.vars` and to task parameters
+        #[arg(long, value_name = "KEY=VALUE", value_parser = parse_var)]
+        var: Vec<(String, String)>,
         /// Skip resolution of requires and only run explicitly selected tasks
-        #[arg(long)]
+        #[arg(long, visible_alias = "no-deps")]
         no_reqs: bool,
+        /// Run against groups with `confirm = true` without asking
+        #[arg(short, long)]
+        yes: bool,
         /// Run all tasks
-        #[arg(long)]
+        #[arg(long, conflicts_with_all = ["tag", "task"])]
         all_tags: bool,
         /// Run on all systems
         #[arg(long)]
         all_systems: bool,
+        /// Fail if a task selected by name or tag targets none of the selected systems
+        #[arg(long)]
+        strict: bool,
+        /// Evaluate this script instead of `arc.lua`
+        #[arg(short, long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Validate the definitions without connecting to any system. Without a
+    /// selection, all tasks on all systems are validated
+    Validate {
+        /// Select tasks by tag (supports `*` and `?` patterns)
+        #[arg(short, long)]
+        tag: Vec<String>,
+        /// Exclude tasks by tag (supports `*` and `?` patterns)
+        #[arg(long, value_name = "TAG")]
+        skip_tag: Vec<String>,
+        /// Select tasks by name
+        #[arg(long)]
+        task: Vec<String>,
+        /// Select systems by group (supports `*` and `?` patterns)
+        #[arg(short, long)]
+        group: Vec<String>,
+        /// Select systems by name (supports `*` and `?` patterns)
+        #[arg(short, long)]
+        system: Vec<String>,
+        /// Fail if a task selected by name or tag targets none of the selected systems
+        #[arg(long)]
+        strict: bool,
+        /// Evaluate this script instead of `arc.lua`
+        #[arg(short, long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Manage the secrets stored in the encrypted vault of the project
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommand,
     },
     /// List registered items
     List {
         /// Item type to list
         #[arg(value_enum)]
         item_type: ListItemType,
-        /// Output as JSON
-        #[arg(long)]
+        /// Output as JSON, short for `--format json`
+        #[arg(long, conflicts_with = "format")]
         json: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+        /// List tasks in the order they run, with the requirements that only
+        /// run after them
+        #[arg(long)]
+        order: bool,
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum SecretsCommand {
+    /// Store a secret, reading its value from stdin
+    Set { name: String },
+    /// Remove a secret
+    Remove { name: String },
+    /// List the names of the stored secrets
+    List,
+}
+
+fn parse_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", value)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum InitTemplate {
+    /// Hello world tasks
+    Minimal,
+    /// nginx serving a templated page on a group of web servers
+    Webservice,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+    Yaml,
+}
+
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub enum ListItemType {
     Tasks,
@@ -1,52 +1,82 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::{HashSet, VecDeque},
     panic::{AssertUnwindSafe, catch_unwind},
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use indexmap::IndexMap;
 
+use confirm::{ConfirmationError, confirm_run};
 use delegator::{
-    executor::{ExecutionTargetSetError, Executor},
-    operator::{FileSystemOperator, OperationTargetSetError},
+    executor::{ExecutionTargetSetError, Executor, RecordedCommand},
+    operator::FileSystemOperator,
 };
-use mlua::{Lua, LuaOptions, StdLib};
+use mlua::{HookTriggers, IntoLua, Lua, LuaOptions, StdLib, VmState};
 use modules::{Modules, MountToGlobals};
 use objects::system::System;
+use retry::RetryBudget;
+use rollout::{Rollout, Turn};
 use selection::{
-    GroupSelection, SystemSelection, TagSelection, select_groups, select_groups_for_system,
-    select_systems, select_tasks, select_tasks_for_system, select_tasks_with_requires,
+    ExcludedRequirement, Selection, TagSelection, flatten_groups, inherit_group_vars,
+    select_groups, select_groups_for_system, select_systems, select_tasks, select_tasks_for_system,
+    select_tasks_starting_at, select_tasks_with_requires, select_untargeted_tasks,
+    sort_by_execution_order,
 };
 use state::{
-    State, TasksErrorStateSetError, TasksExecutionStateResetError, TasksResultStateSetError,
-    TasksStateStateSetError,
+    State, TasksDurationStateSetError, TasksErrorStateSetError, TasksExecutionStateResetError,
+    TasksResultStateSetError, TasksStateStateSetError,
+};
+use summary::{RunStatus, RunSummary};
+use task_files::{
+    DEFAULT_TASKS_DIRECTORY, TaskFileLoadError, TaskFilesListingError, find_task_files, module_name,
 };
 use validation::{
-    GroupSystemNameConflictError, MissingSelectedGroupError, MissingSelectedSystemError,
-    MissingSelectedTagError, UndefinedGroupMembersError, UndefinedRequiresError,
-    UndefinedTaskTargetsError, validate_group_members, validate_group_system_names,
-    validate_selected_groups, validate_selected_systems, validate_selected_tags,
-    validate_task_requires, validate_task_targets,
+    EmptyDefinitionError, GroupCycleError, GroupSystemNameConflictError, MissingSelectedGroupError,
+    MissingSelectedSystemError, MissingSelectedTagError, MissingSelectedTaskError, TaskParamsError,
+    UndefinedGroupMembersError, UndefinedRequiresError, UndefinedTaskTargetsError,
+    UnmatchedLimitError, UnplannedStartTaskError, UntargetedTasksError, find_requires_cycle,
+    format_cycle, validate_definitions, validate_group_cycles, validate_group_members,
+    validate_group_system_names, validate_limit, validate_selected_groups,
+    validate_selected_systems, validate_selected_tags, validate_selected_tasks,
+    validate_start_task, validate_task_params, validate_task_requires, validate_task_targets,
 };
 
 use crate::{
     engine::{delegator::error::FfiPanicError, objects::system::SystemKind},
-    error::MutexLockError,
+    error::{ErrorReport, MutexLockError},
+    interrupt,
     logger::{LogLevel, Logger},
     memory::{
+        config::ConfigMemory,
         target_groups::TargetGroupsMemory,
         target_systems::{TargetSystem, TargetSystemKind, TargetSystemsMemory},
         tasks::{OnFailBehavior, Task, TaskState, TasksMemory},
     },
-    progress::{ProgressContext, SystemLogger, SystemLoggerCreationError, TaskLoggerCreationError},
+    progress::{
+        ProgressContext, SystemLogger, SystemLoggerCreationError, TaskLogger,
+        TaskLoggerCreationError, format_duration,
+    },
 };
 
+mod confirm;
 pub mod delegator;
 pub mod modules;
+mod notify;
 pub mod objects;
 mod readonly;
+mod retry;
+mod rollout;
 pub mod selection;
 pub mod state;
+pub mod summary;
+mod task_files;
 pub mod validation;
 
 pub struct Engine {
@@ -54,7 +84,24 @@ pub struct Engine {
     state: State,
     logger: Logger,
     progress: ProgressContext,
+    root_path: PathBuf,
     home_path: PathBuf,
+    vars: IndexMap<String, String>,
+    entrypoint: PathBuf,
+    /// Host commands run by the entrypoint, replayed by parallel workers
+    entrypoint_commands: RefCell<Vec<RecordedCommand>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EngineOptions {
+    /// Print diffs of file contents written by tasks
+    pub show_diff: bool,
+    /// Variables given on the command line via `--var`
+    pub vars: IndexMap<String, String>,
+    /// Preview file writes as diffs instead of making them
+    pub dry_run: bool,
+    /// Script evaluated instead of `arc.lua`, given via `--file`
+    pub entrypoint: Option<PathBuf>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -63,41 +110,261 @@ pub enum EngineBuilderCreationError {
     Lua(#[from] mlua::Error),
 }
 
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Overrides the retry budget configured via `arc.config`
+    pub retry_budget: Option<u32>,
+    /// Number of systems to run at the same time
+    pub parallel: Option<usize>,
+    /// File the run summary is written to as JSON
+    pub report: Option<PathBuf>,
+    /// Skips the confirmation of groups with `confirm = true`
+    pub assume_yes: bool,
+}
+
+/// State shared by the worker threads of a parallel run.
+struct ParallelRun<'a> {
+    logger: Logger,
+    root_path: PathBuf,
+    home_path: PathBuf,
+    options: EngineOptions,
+    selection: &'a Selection,
+    entrypoint_commands: &'a [RecordedCommand],
+    queue: Mutex<VecDeque<String>>,
+    aborted: AtomicBool,
+    failed_systems: AtomicUsize,
+    failed_workers: AtomicUsize,
+    /// Why the last worker failed to start, reported for systems that did not run
+    worker_error: Mutex<Option<String>>,
+    summary: &'a Mutex<RunSummary>,
+    retry_budget: &'a RetryBudget,
+    rollout: &'a Rollout,
+}
+
+impl ParallelRun<'_> {
+    fn work(&self) -> Result<(), EngineExecutionError> {
+        let engine = Engine::new(
+            self.logger.clone(),
+            self.root_path.clone(),
+            self.home_path.clone(),
+            self.options.clone(),
+        )?;
+
+        engine.replay_entrypoint(self.entrypoint_commands.to_vec())?;
+
+        let mut system_tasks = engine.plan_selection(self.selection)?.system_tasks;
+
+        while !self.aborted.load(Ordering::Relaxed) {
+            let Some(system_name) = self.queue.lock().map_err(|_| MutexLockError)?.pop_front()
+            else {
+                break;
+            };
+
+            let Some(index) = system_tasks
+                .keys()
+                .position(|system| system.name == system_name)
+            else {
+                self.record_system_failure(
+                    &system_name,
+                    "System is not part of the plan of the worker".to_string(),
+                )?;
+                self.rollout.finish_system(&system_name)?;
+                continue;
+            };
+            let (system, tasks) = system_tasks
+                .swap_remove_index(index)
+                .expect("index is in bounds");
+
+            if let Err(error) =
+                engine.execute_system(system, tasks, self.summary, self.retry_budget, self.rollout)
+            {
+                self.record_system_error(&system_name, error)?;
+            }
+
+            self.rollout.finish_system(&system_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reports an error right away, as errors cannot be passed between
+    /// threads. Aborts stop workers from starting further systems.
+    fn record_system_error(
+        &self,
+        system_name: &str,
+        error: EngineExecutionError,
+    ) -> Result<(), MutexLockError> {
+        match error {
+            // The aborting task has already been reported by its task logger
+            EngineExecutionError::Aborted(_) => {
+                self.failed_systems.fetch_add(1, Ordering::Relaxed);
+                self.aborted.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            error => {
+                let message = ErrorReport::describe(&error);
+                self.logger
+                    .error(&ErrorReport::boxed_from(error).build_report());
+                self.record_system_failure(system_name, message)
+            }
+        }
+    }
+
+    fn record_system_failure(
+        &self,
+        system_name: &str,
+        error: String,
+    ) -> Result<(), MutexLockError> {
+        self.failed_systems.fetch_add(1, Ordering::Relaxed);
+        self.summary
+            .lock()
+            .map_err(|_| MutexLockError)?
+            .record_failed_system(system_name, error);
+
+        Ok(())
+    }
+
+    /// Reports a worker that could not start. Its systems are taken over by
+    /// the other workers or recorded as not run once all workers finished.
+    fn record_worker_error(&self, error: EngineExecutionError) {
+        self.failed_workers.fetch_add(1, Ordering::Relaxed);
+
+        if let Ok(mut worker_error) = self.worker_error.lock() {
+            *worker_error = Some(ErrorReport::describe(&error));
+        }
+
+        self.logger
+            .error(&ErrorReport::boxed_from(error).build_report());
+    }
+}
+
+/// The tasks planned for each system.
+pub type SystemTasks = IndexMap<TargetSystem, Vec<Task>>;
+
+/// The planned tasks and what the selection left out of the plan.
+struct SelectionPlan {
+    system_tasks: SystemTasks,
+    excluded_requirements: Vec<ExcludedRequirement>,
+    /// Tasks selected by name or tag that target none of the selected systems
+    untargeted_tasks: Vec<String>,
+    /// Planned tasks that require each other
+    requires_cycle: Option<Vec<String>>,
+}
+
 static ENTRY_POINT_SCRIPT: &str = "arc.lua";
+static TIMEOUT_CHECK_INSTRUCTION_INTERVAL: u32 = 1000;
 
 #[derive(thiserror::Error, Debug)]
 #[error("Runtime error")]
 pub enum EngineExecutionError {
     Aborted(TaskExecutionError),
+    EngineCreation(#[from] EngineBuilderCreationError),
+    ParallelRun(#[from] ParallelRunError),
     EntrypointExecution(#[from] EntrypointExecutionError),
     Validation(#[from] ValidationError),
+    Confirmation(#[from] ConfirmationError),
     TaskExecution(#[from] TaskExecutionError),
     ExecutionTargetSet(#[from] ExecutionTargetSetError),
-    OperationTargetSet(#[from] OperationTargetSetError),
     TasksExecutionStateReset(#[from] TasksExecutionStateResetError),
     SystemLoggerCreation(#[from] SystemLoggerCreationError),
+    Lock(#[from] MutexLockError),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "{failed} system(s) failed{}{}",
+    match failed_workers { 0 => String::new(), failed_workers => format!(", {failed_workers} worker(s) failed to start") },
+    if *aborted { ", run aborted" } else { "" }
+)]
+pub struct ParallelRunError {
+    failed: usize,
+    failed_workers: usize,
+    pub aborted: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
 #[error("Failed to execute arc entrypoint")]
 pub enum EntrypointExecutionError {
     Lua(#[from] mlua::Error),
-    Io(#[from] std::io::Error),
+    Read(#[from] EntrypointReadError),
+    TaskFilesListing(#[from] TaskFilesListingError),
+    TaskFileLoad(#[from] TaskFileLoadError),
+    Lock(#[from] MutexLockError),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to read {path:?}")]
+pub struct EntrypointReadError {
+    path: PathBuf,
+    #[source]
+    source: std::io::Error,
 }
 
 #[derive(thiserror::Error, Debug)]
 #[error("Failed to validate and filter selection")]
 pub enum ValidationError {
+    EmptyDefinition(#[from] EmptyDefinitionError),
     MissingSelectedGroup(#[from] MissingSelectedGroupError),
     MissingSelectedSystem(#[from] MissingSelectedSystemError),
     MissingSelectedTag(#[from] MissingSelectedTagError),
+    MissingSelectedTask(#[from] MissingSelectedTaskError),
     GroupSystemNameConflict(#[from] GroupSystemNameConflictError),
     UndefinedGroupMembers(#[from] UndefinedGroupMembersError),
+    GroupCycle(#[from] GroupCycleError),
     UndefinedTaskTargets(#[from] UndefinedTaskTargetsError),
     UndefinedRequires(#[from] UndefinedRequiresError),
+    UnplannedStartTask(#[from] UnplannedStartTaskError),
+    UnmatchedLimit(#[from] UnmatchedLimitError),
+    UntargetedTasks(#[from] UntargetedTasksError),
+    TaskParams(#[from] TaskParamsError),
+    Problems(#[from] ValidationProblemsError),
     Lock(#[from] MutexLockError),
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("Found {} problems:{}", .0.len(), format_problems(.0))]
+pub struct ValidationProblemsError(Vec<ValidationError>);
+
+/// Directory of the entrypoint that `require` and the tasks directory are
+/// relative to. Empty for a script in the working directory.
+fn script_directory(entrypoint: &Path) -> &Path {
+    entrypoint.parent().unwrap_or(Path::new(""))
+}
+
+/// Lets `require` find modules in `directory` before the default locations.
+fn prepend_package_path(lua: &Lua, directory: &Path) -> mlua::Result<()> {
+    let package: mlua::Table = lua.globals().get("package")?;
+    let path: String = package.get("path")?;
+    let directory = directory.to_string_lossy();
+
+    package.set(
+        "path",
+        format!("{0}/?.lua;{0}/?/init.lua;{1}", directory, path),
+    )
+}
+
+fn format_problems(problems: &[ValidationError]) -> String {
+    problems
+        .iter()
+        .map(|problem| {
+            let message = match std::error::Error::source(problem) {
+                Some(source) => source.to_string(),
+                None => problem.to_string(),
+            };
+
+            message
+                .trim_end()
+                .lines()
+                .enumerate()
+                .map(|(index, line)| match index {
+                    0 => format!("\n    - {}", line),
+                    _ => format!("\n      {}", line),
+                })
+                .collect::<String>()
+        })
+        .collect()
+}
+
 #[derive(thiserror::Error, Debug)]
 #[error("Failed to run tasks on system")]
 pub enum TaskExecutionError {
@@ -106,8 +373,30 @@ pub enum TaskExecutionError {
     TasksResultSet(#[from] TasksResultStateSetError),
     TasksStateSet(#[from] TasksStateStateSetError),
     TasksErrorSet(#[from] TasksErrorStateSetError),
+    TasksDurationSet(#[from] TasksDurationStateSetError),
     TaskLoggerCreation(#[from] TaskLoggerCreationError),
     Ffi(#[from] FfiPanicError),
+    Lock(#[from] MutexLockError),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Task timed out after {}", format_duration(*.0))]
+pub struct TaskTimeoutError(Duration);
+
+enum HandlerOutcome {
+    Success(mlua::Value),
+    Failure(mlua::Error),
+    TimedOut(mlua::Error, Duration),
+}
+
+impl HandlerOutcome {
+    fn error_message(&self) -> Option<String> {
+        match self {
+            HandlerOutcome::Success(_) => None,
+            HandlerOutcome::Failure(error) => Some(error.to_string()),
+            HandlerOutcome::TimedOut(_, timeout) => Some(TaskTimeoutError(*timeout).to_string()),
+        }
+    }
 }
 
 impl Engine {
@@ -115,6 +404,7 @@ impl Engine {
         logger: Logger,
         root_path: PathBuf,
         home_path: PathBuf,
+        options: EngineOptions,
     ) -> Result<Self, EngineBuilderCreationError> {
         let mut lua = Lua::new_with(
             StdLib::TABLE | StdLib::STRING | StdLib::PACKAGE | StdLib::BIT | StdLib::MATH,
@@ -125,25 +415,45 @@ impl Engine {
         let target_groups_memory = Arc::new(Mutex::new(TargetGroupsMemory::default()));
         #[allow(clippy::arc_with_non_send_sync)]
         let tasks_memory = Arc::new(Mutex::new(TasksMemory::default()));
+        let config_memory = Arc::new(Mutex::new(ConfigMemory::default()));
 
-        let progress = ProgressContext::new(logger.clone());
+        let progress = ProgressContext::new(logger.clone(), options.show_diff, options.dry_run);
+        let entrypoint = options
+            .entrypoint
+            .unwrap_or_else(|| PathBuf::from(ENTRY_POINT_SCRIPT));
 
         Modules::new(
             target_systems_memory.clone(),
             target_groups_memory.clone(),
             tasks_memory.clone(),
+            config_memory.clone(),
             progress.clone(),
-            root_path,
+            options.vars.clone(),
+            root_path.clone(),
             home_path.clone(),
         )
         .mount_to_globals(&mut lua)?;
 
+        let script_directory = script_directory(&entrypoint);
+        if !script_directory.as_os_str().is_empty() {
+            prepend_package_path(&lua, script_directory)?;
+        }
+
         Ok(Self {
             lua,
-            state: State::new(target_systems_memory, target_groups_memory, tasks_memory),
+            state: State::new(
+                target_systems_memory,
+                target_groups_memory,
+                tasks_memory,
+                config_memory,
+            ),
             logger,
             progress,
+            root_path,
             home_path,
+            vars: options.vars,
+            entrypoint,
+            entrypoint_commands: RefCell::new(Vec::new()),
         })
     }
 
@@ -152,58 +462,248 @@ impl Engine {
     }
 
     pub fn execute_entrypoint(&self) -> Result<(), EntrypointExecutionError> {
-        let entry_point_script_path = PathBuf::from(ENTRY_POINT_SCRIPT);
-        let entry_point_script = std::fs::read_to_string(&entry_point_script_path)?;
+        *self.entrypoint_commands.borrow_mut() = self.evaluate_entrypoint(None)?;
+
+        Ok(())
+    }
+
+    /// Evaluates the entrypoint again without repeating the side effects of
+    /// its first evaluation, which ran `commands` on the host.
+    fn replay_entrypoint(
+        &self,
+        commands: Vec<RecordedCommand>,
+    ) -> Result<(), EntrypointExecutionError> {
+        self.evaluate_entrypoint(Some(commands))?;
+
+        Ok(())
+    }
+
+    fn evaluate_entrypoint(
+        &self,
+        replay: Option<Vec<RecordedCommand>>,
+    ) -> Result<Vec<RecordedCommand>, EntrypointExecutionError> {
+        let entry_point_script_path = &self.entrypoint;
+        let entry_point_script =
+            std::fs::read_to_string(entry_point_script_path).map_err(|source| {
+                EntrypointReadError {
+                    path: entry_point_script_path.clone(),
+                    source,
+                }
+            })?;
 
-        self.lua
+        self.progress.start_entrypoint(replay);
+        let result = self
+            .lua
             .load(entry_point_script)
             .set_name(entry_point_script_path.to_string_lossy())
-            .exec()?;
+            .exec()
+            .map_err(EntrypointExecutionError::from)
+            .and_then(|()| self.load_task_files());
+        let commands = self.progress.finish_entrypoint();
+
+        result?;
+
+        Ok(commands)
+    }
+
+    /// Loads the `.lua` files in the tasks directory that the entrypoint did
+    /// not `require` already, and marks them as loaded for `require`. The
+    /// directory is relative to the directory of the entrypoint.
+    fn load_task_files(&self) -> Result<(), EntrypointExecutionError> {
+        let script_directory = script_directory(&self.entrypoint);
+        let loaded_modules: mlua::Table = self
+            .lua
+            .globals()
+            .get::<mlua::Table>("package")?
+            .get("loaded")?;
+
+        for path in find_task_files(&self.tasks_directory()?)? {
+            // Names and tags do not depend on where the project is run from
+            let project_path = path.strip_prefix(script_directory).unwrap_or(&path);
+            let module_name = module_name(project_path);
+
+            if loaded_modules.contains_key(module_name.as_str())? {
+                continue;
+            }
+
+            let to_error = |source| TaskFileLoadError {
+                path: path.clone(),
+                source,
+            };
+
+            let script = std::fs::read_to_string(&path)
+                .map_err(|error| to_error(mlua::Error::external(error)))?;
+            // The `@` marks the name as a file path, which tags the tasks
+            // with the components of the path
+            let value: mlua::Value = self
+                .lua
+                .load(script)
+                .set_name(format!("@{}", project_path.to_string_lossy()))
+                .call(module_name.as_str())
+                .map_err(to_error)?;
+
+            let value = match value {
+                mlua::Value::Nil => mlua::Value::Boolean(true),
+                value => value,
+            };
+            loaded_modules.set(module_name, value).map_err(to_error)?;
+        }
 
         Ok(())
     }
 
+    /// Directory whose `.lua` files are loaded after the entrypoint.
+    fn tasks_directory(&self) -> Result<PathBuf, MutexLockError> {
+        let directory = self
+            .state
+            .config()?
+            .tasks_directory
+            .unwrap_or_else(|| DEFAULT_TASKS_DIRECTORY.to_string());
+
+        Ok(script_directory(&self.entrypoint).join(directory))
+    }
+
     pub fn validate_and_filter_by_selection(
         &self,
-        tags_selection: &TagSelection,
-        groups_selection: &GroupSelection,
-        systems_selection: &SystemSelection,
-        no_reqs: bool,
-    ) -> Result<IndexMap<TargetSystem, Vec<Task>>, ValidationError> {
+        selection: &Selection,
+    ) -> Result<SystemTasks, ValidationError> {
+        let plan = self.plan_selection(selection)?;
+
+        for excluded in plan.excluded_requirements {
+            self.logger.warn(&format!(
+                "Task {:?} is required by {:?} but excluded by --skip-tag and will not run",
+                excluded.task, excluded.required_by
+            ));
+        }
+
+        if !plan.untargeted_tasks.is_empty() {
+            self.logger.warn(&format!(
+                "Tasks {} target none of the selected systems and will not run",
+                plan.untargeted_tasks
+                    .iter()
+                    .map(|name| format!("{:?}", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        if let Some(cycle) = plan.requires_cycle {
+            self.logger.warn(&format!(
+                "Tasks require each other: {}. Tasks run in definition order, so a task can only rely on the results of required tasks defined before it",
+                format_cycle(&cycle)
+            ));
+        }
+
+        Ok(plan.system_tasks)
+    }
+
+    /// Validates the definitions and selects the tasks of each system.
+    /// Parallel workers plan without repeating the warnings of the main run.
+    fn plan_selection(&self, selection: &Selection) -> Result<SelectionPlan, ValidationError> {
         let all_groups = self.state.all_groups()?;
         let all_systems = self.state.all_systems()?;
         let all_tasks = self.state.all_tasks()?;
 
-        validate_group_system_names(&all_groups, &all_systems)?;
-        validate_group_members(&all_groups, &all_systems)?;
-        validate_task_targets(&all_tasks, &all_groups, &all_systems)?;
-        validate_task_requires(&all_tasks)?;
-        validate_selected_groups(&all_groups, groups_selection)?;
-        validate_selected_systems(&all_systems, systems_selection)?;
-        validate_selected_tags(&all_tasks, tags_selection)?;
-
-        let selected_groups = select_groups(all_groups.clone(), groups_selection);
-        let filtered_systems = select_systems(all_systems, &selected_groups, systems_selection);
-        let filtered_tasks = if no_reqs {
-            select_tasks(all_tasks, groups_selection, tags_selection)
+        // All checks run so that every problem is reported at once
+        let mut problems = [
+            validate_definitions(
+                &all_systems,
+                &all_tasks,
+                &self.entrypoint,
+                &self.tasks_directory()?,
+            )
+            .map_err(ValidationError::from),
+            validate_group_system_names(&all_groups, &all_systems).map_err(ValidationError::from),
+            validate_group_members(&all_groups, &all_systems).map_err(ValidationError::from),
+            validate_group_cycles(&all_groups).map_err(ValidationError::from),
+            validate_task_targets(&all_tasks, &all_groups, &all_systems)
+                .map_err(ValidationError::from),
+            validate_task_requires(&all_tasks).map_err(ValidationError::from),
+            validate_selected_groups(&all_groups, &selection.groups).map_err(ValidationError::from),
+            validate_selected_systems(&all_systems, &selection.systems)
+                .map_err(ValidationError::from),
+            validate_selected_tags(&all_tasks, &selection.tags).map_err(ValidationError::from),
+            validate_selected_tags(&all_tasks, &TagSelection::Set(selection.skip_tags.clone()))
+                .map_err(ValidationError::from),
+            validate_selected_tasks(&all_tasks, &selection.tasks).map_err(ValidationError::from),
+        ]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect::<Vec<_>>();
+
+        match problems.len() {
+            0 => {}
+            1 => return Err(problems.remove(0)),
+            _ => return Err(ValidationProblemsError(problems).into()),
+        }
+
+        let all_groups = flatten_groups(&all_groups);
+        let selected_groups = select_groups(all_groups.clone(), &selection.groups);
+        let mut filtered_systems =
+            select_systems(all_systems, &selected_groups, &selection.systems);
+        validate_limit(&filtered_systems, &selection.limit)?;
+        filtered_systems.retain(|name, _| selection.limit.contains(name));
+        let (filtered_tasks, excluded_requirements) = if selection.no_reqs {
+            let tasks = select_tasks(
+                all_tasks,
+                &selection.tags,
+                &selection.skip_tags,
+                &selection.tasks,
+            );
+
+            (tasks, Vec::new())
         } else {
-            select_tasks_with_requires(all_tasks, groups_selection, tags_selection)
+            select_tasks_with_requires(
+                all_tasks,
+                &selection.tags,
+                &selection.skip_tags,
+                &selection.tasks,
+            )
         };
 
         let mut result = IndexMap::new();
 
-        for (system_name, system_config) in filtered_systems {
+        for (system_name, mut system_config) in filtered_systems {
+            system_config.vars = inherit_group_vars(&all_groups, &system_config);
+
             let system_groups = select_groups_for_system(&all_groups, &system_name);
-            let system_tasks: Vec<Task> =
+            let mut system_tasks: Vec<Task> =
                 select_tasks_for_system(&filtered_tasks, &system_name, &system_groups)
                     .into_iter()
                     .cloned()
                     .collect();
+            sort_by_execution_order(&mut system_tasks);
 
             result.insert(system_config, system_tasks);
         }
 
-        Ok(result)
+        let untargeted_tasks = select_untargeted_tasks(
+            &filtered_tasks,
+            &selection.tags,
+            &selection.tasks,
+            result.values().flatten(),
+        );
+
+        if selection.strict && !untargeted_tasks.is_empty() {
+            return Err(UntargetedTasksError(untargeted_tasks).into());
+        }
+
+        if let Some(start_at) = &selection.start_at {
+            validate_start_task(result.values().flatten(), start_at)?;
+
+            for system_tasks in result.values_mut() {
+                *system_tasks = select_tasks_starting_at(std::mem::take(system_tasks), start_at);
+            }
+        }
+
+        validate_task_params(result.values().flatten(), &self.vars)?;
+
+        Ok(SelectionPlan {
+            system_tasks: result,
+            excluded_requirements,
+            untargeted_tasks,
+            requires_cycle: find_requires_cycle(&filtered_tasks),
+        })
     }
 
     // TODO: do not propagate immediately, summarize instead
@@ -212,159 +712,714 @@ impl Engine {
         system: System,
         tasks: Vec<Task>,
         system_logger: &SystemLogger,
+        retry_budget: &RetryBudget,
+        rollout: &Rollout,
     ) -> Result<(), TaskExecutionError> {
         let mut skip_system = false;
+        let (notified_tasks, tasks): (Vec<Task>, Vec<Task>) =
+            tasks.into_iter().partition(|task| task.on_notify);
+        let notified_task_names: HashSet<String> = notified_tasks
+            .iter()
+            .map(|task| task.name.clone())
+            .collect();
 
         for task_config in tasks {
-            let task_logger = system_logger.task(&task_config.name)?;
+            self.run_task(
+                &system,
+                task_config,
+                system_logger,
+                retry_budget,
+                rollout,
+                &mut skip_system,
+            )?;
+        }
 
-            if skip_system && !task_config.important {
+        // Notified tasks run once after all other tasks, in definition
+        // order, and may notify tasks that come after them
+        for task_config in notified_tasks {
+            if system.is_notified(&task_config.name) {
+                self.run_task(
+                    &system,
+                    task_config,
+                    system_logger,
+                    retry_budget,
+                    rollout,
+                    &mut skip_system,
+                )?;
+            } else {
+                self.state
+                    .set_task_state(&task_config.name, TaskState::Skipped)?;
+                system_logger.task(&task_config.name)?.skip();
+                rollout.finish(&task_config.name, &system.name, false)?;
+            }
+        }
+
+        let mut unplanned: Vec<String> = system
+            .notified
+            .borrow()
+            .difference(&notified_task_names)
+            .cloned()
+            .collect();
+        unplanned.sort();
+
+        for task_name in unplanned {
+            self.logger.warn(&format!(
+                "Task {:?} was notified on system {:?} but is not planned there with `on_notify`",
+                task_name, system.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn run_task(
+        &self,
+        system: &System,
+        task_config: Task,
+        system_logger: &SystemLogger,
+        retry_budget: &RetryBudget,
+        rollout: &Rollout,
+        skip_system: &mut bool,
+    ) -> Result<(), TaskExecutionError> {
+        let task_logger = system_logger.task(&task_config.name)?;
+
+        // Tasks that did not start yet are skipped after Ctrl-C, important
+        // ones as well
+        if (*skip_system && !task_config.important) || interrupt::is_interrupted() {
+            self.state
+                .set_task_state(&task_config.name, TaskState::Skipped)?;
+            task_logger.skip();
+            rollout.finish(&task_config.name, &system.name, false)?;
+            return Ok(());
+        }
+
+        if rollout.wait_for_turn(&task_config.name, &system.name)? == Turn::Halted {
+            task_logger.log(
+                LogLevel::Warn,
+                &format!(
+                    "Rollout of task '{}' halted because it failed in an earlier batch",
+                    task_config.name
+                ),
+            );
+            self.state
+                .set_task_state(&task_config.name, TaskState::Skipped)?;
+            task_logger.skip();
+            rollout.finish(&task_config.name, &system.name, false)?;
+            *skip_system = true;
+            return Ok(());
+        }
+
+        if let Some(when_handler) = &task_config.when {
+            let should_run: bool = when_handler.call(system.clone())?;
+            if !should_run {
                 self.state
                     .set_task_state(&task_config.name, TaskState::Skipped)?;
                 task_logger.skip();
-                continue;
+                rollout.finish(&task_config.name, &system.name, false)?;
+                return Ok(());
             }
+        }
 
-            if let Some(when_handler) = &task_config.when {
-                let should_run: bool = when_handler.call(())?;
-                if !should_run {
-                    self.state
-                        .set_task_state(&task_config.name, TaskState::Skipped)?;
-                    task_logger.skip();
-                    continue;
-                }
+        task_logger.start();
+
+        let started_at = Instant::now();
+        let mut attempt = 0;
+        let outcome = loop {
+            let outcome = self.call_task_handler(system, &task_config, &task_logger)?;
+
+            let Some(error_message) = outcome.error_message() else {
+                break outcome;
+            };
+
+            if attempt >= task_config.retries {
+                break outcome;
             }
 
-            task_logger.start();
+            if !retry_budget.try_consume() {
+                task_logger.log(
+                    LogLevel::Warn,
+                    &format!(
+                        "Retry budget exhausted, not retrying task '{}'",
+                        task_config.name
+                    ),
+                );
+                break outcome;
+            }
 
-            self.progress.activate(task_logger.clone());
+            attempt += 1;
 
-            let panic_result = catch_unwind(AssertUnwindSafe(|| {
-                task_config.handler.call::<mlua::Value>(system.clone())
-            }));
+            task_logger.log(
+                LogLevel::Warn,
+                &format!(
+                    "Task '{}' failed: {}. Retrying ({}/{})",
+                    task_config.name, error_message, attempt, task_config.retries
+                ),
+            );
+        };
 
-            let handler_result = match panic_result {
-                Ok(handler_result) => handler_result,
-                Err(panic_payload) => {
-                    self.progress.deactivate();
-                    return Err(panic_payload
-                        .downcast::<FfiPanicError>()
-                        .map(|error| TaskExecutionError::Ffi(*error))
-                        .unwrap_or(TaskExecutionError::Lua(mlua::Error::RuntimeError(
-                            "Unknown panic in task handler".to_string(),
-                        ))));
-                }
-            };
+        self.state
+            .set_task_duration(&task_config.name, started_at.elapsed())?;
 
-            self.progress.deactivate();
+        let (error, error_message) = match outcome {
+            HandlerOutcome::Success(result) => {
+                self.state.set_task_result(&task_config.name, result)?;
+                self.state
+                    .set_task_state(&task_config.name, TaskState::Success)?;
 
-            match handler_result {
-                Ok(result) => {
-                    self.state.set_task_result(&task_config.name, result)?;
-                    self.state
-                        .set_task_state(&task_config.name, TaskState::Success)?;
+                task_logger.finish(TaskState::Success);
+                rollout.finish(&task_config.name, &system.name, false)?;
+                return Ok(());
+            }
+            HandlerOutcome::Failure(error) => {
+                let error_message = error.to_string();
 
-                    task_logger.finish(TaskState::Success);
-                }
-                Err(error) => {
-                    task_logger.log(
-                        LogLevel::Error,
-                        &format!("Task '{}' failed: {}", task_config.name, error),
-                    );
+                (error, error_message)
+            }
+            HandlerOutcome::TimedOut(error, timeout) => {
+                task_logger.time_out();
 
-                    self.state
-                        .set_task_state(&task_config.name, TaskState::Failed)?;
-                    self.state
-                        .set_task_error(&task_config.name, error.to_string())?;
-
-                    match task_config.on_fail {
-                        OnFailBehavior::Continue => {
-                            task_logger.finish(TaskState::Failed);
-                        }
-                        OnFailBehavior::SkipSystem => {
-                            task_logger.finish(TaskState::Failed);
-                            skip_system = true;
-                        }
-                        OnFailBehavior::Abort => {
-                            task_logger.abort();
-                            return Err(TaskExecutionError::Aborted(error));
-                        }
-                    }
-                }
+                (error, TaskTimeoutError(timeout).to_string())
+            }
+        };
+
+        task_logger.log(
+            LogLevel::Error,
+            &format!("Task '{}' failed: {}", task_config.name, error_message),
+        );
+
+        self.state
+            .set_task_state(&task_config.name, TaskState::Failed)?;
+        self.state
+            .set_task_error(&task_config.name, error_message)?;
+        rollout.finish(
+            &task_config.name,
+            &system.name,
+            task_config.on_fail != OnFailBehavior::Continue,
+        )?;
+
+        match task_config.on_fail {
+            OnFailBehavior::Continue => {
+                task_logger.finish(TaskState::Failed);
+            }
+            OnFailBehavior::SkipSystem => {
+                task_logger.finish(TaskState::Failed);
+                *skip_system = true;
+            }
+            OnFailBehavior::Abort => {
+                task_logger.abort();
+                return Err(TaskExecutionError::Aborted(error));
             }
         }
 
         Ok(())
     }
 
-    pub fn execute(
+    fn call_task_handler(
         &self,
-        tags_selection: TagSelection,
-        groups_selection: GroupSelection,
-        systems_selection: SystemSelection,
-        no_reqs: bool,
-    ) -> Result<(), EngineExecutionError> {
-        self.execute_entrypoint()?;
+        system: &System,
+        task_config: &Task,
+        task_logger: &TaskLogger,
+    ) -> Result<HandlerOutcome, TaskExecutionError> {
+        let deadline = task_config.timeout.map(|timeout| Instant::now() + timeout);
 
-        let system_tasks = self.validate_and_filter_by_selection(
-            &tags_selection,
-            &groups_selection,
-            &systems_selection,
-            no_reqs,
-        )?;
+        self.progress.activate(task_logger.clone(), deadline);
 
-        for (system, tasks) in system_tasks {
-            let system_logger = self.logger.system(&system.name)?;
+        let timed_out = Rc::new(Cell::new(false));
 
-            if tasks.is_empty() {
-                // TODO: use system logger
-                self.logger.info("No tasks to execute.");
-                continue;
-            }
+        if let (Some(timeout), Some(deadline)) = (task_config.timeout, deadline) {
+            let timed_out = timed_out.clone();
 
-            self.state.reset_execution_state()?;
-
-            let system = System {
-                name: system.name.clone(),
-                kind: match &system.kind {
-                    TargetSystemKind::Remote(remote_target_system) => {
-                        SystemKind::Remote(objects::system::RemoteSystem {
-                            address: remote_target_system.address,
-                            port: remote_target_system.port,
-                            user: remote_target_system.user.clone(),
-                            executor: Executor::new_for_system(
-                                &system,
-                                self.progress.clone(),
-                                self.home_path.clone(),
-                            )?,
-                            file_system_operator: FileSystemOperator::new_for_system(
-                                &system,
-                                self.progress.clone(),
-                                self.home_path.clone(),
-                            )?,
-                        })
+            self.lua.set_hook(
+                HookTriggers::new().every_nth_instruction(TIMEOUT_CHECK_INSTRUCTION_INTERVAL),
+                move |_, _| {
+                    if Instant::now() < deadline {
+                        return Ok(VmState::Continue);
                     }
-                    TargetSystemKind::Local => SystemKind::Local(
-                        Executor::new_local(self.progress.clone(), self.home_path.clone()),
-                        FileSystemOperator::new_local(
-                            self.progress.clone(),
-                            self.home_path.clone(),
-                        ),
-                    ),
+
+                    timed_out.set(true);
+
+                    Err(mlua::Error::external(TaskTimeoutError(timeout)))
                 },
+            )?;
+        }
+
+        let params = self.task_params(task_config);
+
+        // Lets modules like `template` access the system of the running task
+        self.lua.set_app_data(system.clone());
+
+        let panic_result = catch_unwind(AssertUnwindSafe(|| {
+            task_config
+                .handler
+                .call::<mlua::Value>((system.clone(), params?))
+        }));
+
+        self.lua.remove_app_data::<System>();
+        self.lua.remove_hook();
+        system.close_shells();
+        self.progress.deactivate();
+
+        let handler_result = panic_result.map_err(|panic_payload| {
+            panic_payload
+                .downcast::<FfiPanicError>()
+                .map(|error| TaskExecutionError::Ffi(*error))
+                .unwrap_or(TaskExecutionError::Lua(mlua::Error::RuntimeError(
+                    "Unknown panic in task handler".to_string(),
+                )))
+        })?;
+
+        // Interrupted commands fail before the hook runs again, and both may
+        // have been caught by `pcall`
+        let deadline_reached =
+            timed_out.get() || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        Ok(match (handler_result, task_config.timeout) {
+            (Ok(_), Some(timeout)) if deadline_reached => {
+                HandlerOutcome::TimedOut(mlua::Error::external(TaskTimeoutError(timeout)), timeout)
+            }
+            (Err(error), Some(timeout)) if deadline_reached => {
+                HandlerOutcome::TimedOut(error, timeout)
+            }
+            (Ok(result), _) => HandlerOutcome::Success(result),
+            (Err(error), _) => HandlerOutcome::Failure(error),
+        })
+    }
+
+    /// Builds the parameter table passed to a task handler from `--var`
+    /// values and parameter defaults.
+    fn task_params(&self, task_config: &Task) -> mlua::Result<mlua::Table> {
+        let params = self.lua.create_table()?;
+
+        for (param_name, param) in &task_config.params {
+            let value = match self.vars.get(param_name) {
+                Some(value) => param
+                    .r#type
+                    .parse(value)
+                    .ok_or_else(|| {
+                        mlua::Error::runtime(format!(
+                            "Invalid value {:?} for parameter {:?}",
+                            value, param_name
+                        ))
+                    })?
+                    .into_lua(&self.lua)?,
+                None => param.default.clone().unwrap_or(mlua::Value::Nil),
             };
 
-            let result = self.run_tasks_on_system(system, tasks, &system_logger);
+            params.set(param_name.as_str(), value)?;
+        }
 
-            system_logger.finish();
+        Ok(params)
+    }
+
+    /// Runs the selected tasks and returns the summary of the run. Tasks
+    /// that failed without aborting the run are only reflected in the summary.
+    pub fn execute(
+        &self,
+        selection: Selection,
+        options: RunOptions,
+    ) -> Result<RunSummary, EngineExecutionError> {
+        let system_tasks = match self
+            .execute_entrypoint()
+            .map_err(EngineExecutionError::from)
+            .and_then(|()| Ok(self.validate_and_filter_by_selection(&selection)?))
+        {
+            Ok(system_tasks) => system_tasks,
+            Err(error) => {
+                // The webhook is configured by the entrypoint, so it is only
+                // known if evaluation got far enough to call `arc.config`
+                let mut summary = RunSummary::start();
+                summary.error = Some(ErrorReport::describe(&error));
+                summary.finish(RunStatus::Error);
+                self.notify(&summary);
+
+                return Err(error);
+            }
+        };
+
+        if !options.assume_yes {
+            confirm_run(&flatten_groups(&self.state.all_groups()?), &system_tasks)?;
+        }
+
+        let retry_budget =
+            RetryBudget::new(options.retry_budget.or(self.state.config()?.retry_budget));
+
+        let summary = Mutex::new(RunSummary::start());
+        let rollout = Rollout::plan(&system_tasks);
+
+        let result = match options.parallel {
+            Some(workers) if workers > 1 && system_tasks.len() > 1 => {
+                let system_names = system_tasks.into_keys().map(|system| system.name).collect();
+
+                self.execute_systems_in_parallel(
+                    system_names,
+                    &selection,
+                    workers,
+                    &summary,
+                    &retry_budget,
+                    &rollout,
+                )
+            }
+            _ => self.execute_system_tasks(system_tasks, &summary, &retry_budget, &rollout),
+        };
+
+        let mut summary = summary.into_inner().map_err(|_| MutexLockError)?;
+
+        let retry_usage = retry_budget.usage();
+        if let Some(budget) = retry_usage.budget {
+            self.logger.info(&format!(
+                "Retry budget: {} of {} retries used",
+                retry_usage.used, budget
+            ));
+        }
+        summary.retries = retry_usage;
+
+        if interrupt::is_interrupted() {
+            self.logger
+                .warn("Interrupted, tasks that had not started yet were skipped");
+        }
+
+        summary.finish(match &result {
+            _ if interrupt::is_interrupted() => RunStatus::Interrupted,
+            Ok(()) => RunStatus::Success,
+            Err(EngineExecutionError::Aborted(_)) => RunStatus::Aborted,
+            Err(EngineExecutionError::ParallelRun(error)) if error.aborted => RunStatus::Aborted,
+            Err(_) => RunStatus::Error,
+        });
+
+        self.notify(&summary);
+
+        if !self.logger.is_json() && !summary.tasks.is_empty() {
+            self.logger.print(&summary.report_table());
+        }
+
+        if let Some(path) = &options.report
+            && let Err(error) = summary::write_report(path, &summary)
+        {
+            self.logger
+                .warn(&ErrorReport::boxed_from(error).build_report());
+        }
+
+        result.map(|()| summary)
+    }
+
+    fn execute_system_tasks(
+        &self,
+        system_tasks: IndexMap<TargetSystem, Vec<Task>>,
+        summary: &Mutex<RunSummary>,
+        retry_budget: &RetryBudget,
+        rollout: &Rollout,
+    ) -> Result<(), EngineExecutionError> {
+        for (system, tasks) in system_tasks {
+            let system_name = system.name.clone();
 
-            if let Err(error @ TaskExecutionError::Aborted(_)) = result {
-                return Err(EngineExecutionError::Aborted(error));
+            self.execute_system(system, tasks, summary, retry_budget, rollout)?;
+            rollout.finish_system(&system_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs systems on up to `workers` threads. Lua state cannot be shared
+    /// between threads, so every worker creates its own engine and replays
+    /// the entrypoint. Failures of one system do not stop the others, only an
+    /// aborting task prevents systems that did not start yet from running.
+    /// Systems left over because workers failed to start count as failed.
+    fn execute_systems_in_parallel(
+        &self,
+        system_names: VecDeque<String>,
+        selection: &Selection,
+        workers: usize,
+        summary: &Mutex<RunSummary>,
+        retry_budget: &RetryBudget,
+        rollout: &Rollout,
+    ) -> Result<(), EngineExecutionError> {
+        let worker_count = workers.min(system_names.len());
+        let entrypoint_commands = self.entrypoint_commands.borrow().clone();
+        let parallel_run = ParallelRun {
+            logger: self.logger.for_parallel_systems(),
+            root_path: self.root_path.clone(),
+            home_path: self.home_path.clone(),
+            options: EngineOptions {
+                show_diff: self.progress.shows_diff(),
+                vars: self.vars.clone(),
+                dry_run: self.progress.is_dry_run(),
+                entrypoint: Some(self.entrypoint.clone()),
+            },
+            selection,
+            entrypoint_commands: &entrypoint_commands,
+            queue: Mutex::new(system_names),
+            aborted: AtomicBool::new(false),
+            failed_systems: AtomicUsize::new(0),
+            failed_workers: AtomicUsize::new(0),
+            worker_error: Mutex::new(None),
+            summary,
+            retry_budget,
+            rollout,
+        };
+
+        std::thread::scope(|scope| {
+            for _ in 0..worker_count {
+                scope.spawn(|| {
+                    if let Err(error) = parallel_run.work() {
+                        parallel_run.record_worker_error(error);
+                    }
+                });
             }
+        });
+
+        let aborted = parallel_run.aborted.into_inner();
+
+        if !aborted {
+            let worker_error = parallel_run
+                .worker_error
+                .into_inner()
+                .map_err(|_| MutexLockError)?
+                .unwrap_or_default();
+            let mut summary = summary.lock().map_err(|_| MutexLockError)?;
+
+            for system_name in parallel_run
+                .queue
+                .into_inner()
+                .map_err(|_| MutexLockError)?
+            {
+                parallel_run.failed_systems.fetch_add(1, Ordering::Relaxed);
+                summary.record_failed_system(
+                    &system_name,
+                    format!("System did not run because no worker could start: {worker_error}"),
+                );
+            }
+        }
+
+        let failed = parallel_run.failed_systems.into_inner();
+        let failed_workers = parallel_run.failed_workers.into_inner();
+
+        match (failed, failed_workers) {
+            (0, 0) => Ok(()),
+            (failed, failed_workers) => Err(ParallelRunError {
+                failed,
+                failed_workers,
+                aborted,
+            }
+            .into()),
+        }
+    }
+
+    fn execute_system(
+        &self,
+        mut system: TargetSystem,
+        tasks: Vec<Task>,
+        summary: &Mutex<RunSummary>,
+        retry_budget: &RetryBudget,
+        rollout: &Rollout,
+    ) -> Result<(), EngineExecutionError> {
+        if let TargetSystemKind::Remote(remote_target_system) = &mut system.kind {
+            remote_target_system.apply_default_host_key_check(self.state.config()?.host_key_check);
+        }
+
+        let system_logger = self.logger.system(&system.name)?;
+
+        if tasks.is_empty() {
+            // TODO: use system logger
+            self.logger.info("No tasks to execute.");
+            return Ok(());
+        }
+
+        self.state.reset_execution_state()?;
+
+        let system_name = system.name.clone();
+        let task_names: Vec<String> = tasks.iter().map(|task| task.name.clone()).collect();
+
+        if interrupt::is_interrupted() {
+            let result =
+                self.skip_interrupted_system(&system_name, &task_names, &system_logger, rollout);
+
+            system_logger.finish();
+            summary.lock().map_err(|_| MutexLockError)?.record_system(
+                &system_name,
+                &task_names,
+                &self.state.all_tasks()?,
+            );
+
+            return Ok(result?);
+        }
+
+        let all_groups = flatten_groups(&self.state.all_groups()?);
+        let mut groups: Vec<String> = select_groups_for_system(&all_groups, &system_name)
+            .into_iter()
+            .cloned()
+            .collect();
+        groups.sort();
+
+        let system = System {
+            name: system.name.clone(),
+            vars: system.vars.clone(),
+            groups,
+            notified: Default::default(),
+            kind: match &system.kind {
+                TargetSystemKind::Remote(remote_target_system) => {
+                    let executor = Executor::new_for_system(
+                        &system,
+                        self.progress.clone(),
+                        self.home_path.clone(),
+                    )?;
+                    let file_system_operator = FileSystemOperator::new_for_system(
+                        &executor,
+                        self.progress.clone(),
+                        self.home_path.clone(),
+                    );
+
+                    SystemKind::Remote(objects::system::RemoteSystem {
+                        address: remote_target_system.address,
+                        port: remote_target_system.port,
+                        user: remote_target_system.user.clone(),
+                        executor,
+                        file_system_operator,
+                    })
+                }
+                TargetSystemKind::Local => SystemKind::Local(
+                    Executor::new_local(self.progress.clone(), self.home_path.clone()),
+                    FileSystemOperator::new_local(self.progress.clone(), self.home_path.clone()),
+                ),
+            },
+        };
+
+        let result = self.run_tasks_on_system(system, tasks, &system_logger, retry_budget, rollout);
+
+        system_logger.finish();
+
+        summary.lock().map_err(|_| MutexLockError)?.record_system(
+            &system_name,
+            &task_names,
+            &self.state.all_tasks()?,
+        );
 
-            result?;
+        if let Err(error @ TaskExecutionError::Aborted(_)) = result {
+            return Err(EngineExecutionError::Aborted(error));
+        }
+
+        Ok(result?)
+    }
+
+    /// Skips the tasks of a system that is not connected to because the run
+    /// was interrupted.
+    fn skip_interrupted_system(
+        &self,
+        system_name: &str,
+        task_names: &[String],
+        system_logger: &SystemLogger,
+        rollout: &Rollout,
+    ) -> Result<(), TaskExecutionError> {
+        for task_name in task_names {
+            self.state.set_task_state(task_name, TaskState::Skipped)?;
+            system_logger.task(task_name)?.skip();
+            rollout.finish(task_name, system_name, false)?;
         }
 
         Ok(())
     }
+
+    fn notify(&self, summary: &RunSummary) {
+        let config = match self.state.config() {
+            Ok(config) => config,
+            Err(error) => {
+                self.logger
+                    .warn(&ErrorReport::boxed_from(error).build_report());
+                return;
+            }
+        };
+
+        if let Some(url) = config.notify_webhook
+            && let Err(error) = notify::send_webhook(&url, summary)
+        {
+            self.logger
+                .warn(&ErrorReport::boxed_from(error).build_report());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use super::{Engine, EngineOptions};
+    use crate::{
+        engine::selection::{
+            GroupSelection, Selection, SystemSelection, TagSelection, TaskSelection,
+        },
+        logger::Logger,
+    };
+
+    fn all_tasks_on_all_systems() -> Selection {
+        Selection {
+            tags: TagSelection::All,
+            skip_tags: HashSet::new(),
+            tasks: TaskSelection::None,
+            groups: GroupSelection::None,
+            systems: SystemSelection::All,
+            limit: SystemSelection::All,
+            no_reqs: false,
+            start_at: None,
+            strict: false,
+        }
+    }
+
+    fn evaluate(entrypoint: PathBuf) -> Engine {
+        let engine = Engine::new(
+            Logger::new(),
+            std::env::temp_dir(),
+            std::env::temp_dir(),
+            EngineOptions {
+                entrypoint: Some(entrypoint),
+                ..EngineOptions::default()
+            },
+        )
+        .unwrap();
+
+        engine.execute_entrypoint().unwrap();
+
+        engine
+    }
+
+    #[test]
+    fn tasks_requiring_each_other_pass_validation() {
+        let engine = evaluate(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("examples/blue-green-switch/project/env/arc.lua"),
+        );
+
+        let plan = engine.plan_selection(&all_tasks_on_all_systems()).unwrap();
+
+        assert!(plan.requires_cycle.is_some());
+    }
+
+    #[test]
+    fn requiring_the_tag_of_the_own_file_is_not_a_cycle() {
+        let root = std::env::temp_dir().join(format!("arc-requires-{}", std::process::id()));
+        let files = [
+            (
+                "arc.lua",
+                r#"targets.systems["localhost"] = { type = "local" }"#,
+            ),
+            (
+                "tasks/web/nginx.lua",
+                r#"tasks["nginx"] = { handler = function() end }"#,
+            ),
+            (
+                "tasks/web/site.lua",
+                r#"tasks["site"] = { requires = { "web" }, handler = function() end }"#,
+            ),
+        ];
+
+        for (file, content) in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let engine = evaluate(root.join("arc.lua"));
+        let plan = engine.plan_selection(&all_tasks_on_all_systems());
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(plan.unwrap().requires_cycle.is_none());
+    }
 }
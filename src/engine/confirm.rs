@@ -0,0 +1,82 @@
+use std::io::{IsTerminal, Write};
+
+use crate::memory::target_groups::TargetGroups;
+
+use super::SystemTasks;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to confirm the run")]
+pub enum ConfirmationError {
+    Declined(#[from] ConfirmationDeclinedError),
+    NotInteractive(#[from] ConfirmationUnavailableError),
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The run against {0} was not confirmed")]
+pub struct ConfirmationDeclinedError(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Running against {0} needs confirmation, pass `--yes` to run without a terminal")]
+pub struct ConfirmationUnavailableError(String);
+
+/// Asks on the terminal whether to continue if tasks are planned on systems of
+/// groups with `confirm = true`. `groups` must be flattened.
+pub fn confirm_run(
+    groups: &TargetGroups,
+    system_tasks: &SystemTasks,
+) -> Result<(), ConfirmationError> {
+    let mut confirm_groups: Vec<&String> = groups
+        .values()
+        .filter(|group| group.confirm)
+        .map(|group| &group.name)
+        .collect();
+    confirm_groups.sort();
+
+    let systems = system_tasks
+        .iter()
+        .filter(|(system, tasks)| {
+            !tasks.is_empty()
+                && confirm_groups
+                    .iter()
+                    .any(|group| groups[*group].members.contains(&system.name))
+        })
+        .count();
+
+    if systems == 0 {
+        return Ok(());
+    }
+
+    let affected_groups: Vec<&str> = confirm_groups
+        .iter()
+        .filter(|group| {
+            system_tasks.iter().any(|(system, tasks)| {
+                !tasks.is_empty() && groups[**group].members.contains(&system.name)
+            })
+        })
+        .map(|group| group.as_str())
+        .collect();
+    let description = format!(
+        "{} {} of {}",
+        systems,
+        if systems == 1 { "system" } else { "systems" },
+        affected_groups.join(", ")
+    );
+
+    if !std::io::stdin().is_terminal() {
+        return Err(ConfirmationUnavailableError(description).into());
+    }
+
+    let _prompt = crate::interrupt::exit_while_prompting();
+
+    eprint!("About to run against {}, continue? [y/N] ", description);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(ConfirmationDeclinedError(description).into()),
+    }
+}
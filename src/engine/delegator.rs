@@ -1,8 +1,12 @@
 pub const TRANSFER_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+/// Exit code of `getent` when the key is not in the database
+pub const GETENT_KEY_NOT_FOUND: i32 = 2;
 
 pub mod error;
 pub mod executor;
 pub mod host;
 pub mod local;
 pub mod operator;
+pub mod shell;
 mod ssh;
@@ -18,6 +18,17 @@ pub trait FfiError: std::error::Error + Send + Sync + Sized + 'static {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Command interrupted because the task timed out")]
+pub struct TaskDeadlineError;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown {kind} {name:?}")]
+pub struct UnknownOwnerError {
+    pub kind: &'static str,
+    pub name: String,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum OperationError {
     #[error(transparent)]
@@ -26,6 +37,8 @@ pub enum OperationError {
     Local(ExecutionError<host::UserError, host::InfrastructureError>),
     #[error(transparent)]
     Progress(TransferProgressCreationError),
+    #[error(transparent)]
+    UnknownOwner(UnknownOwnerError),
 }
 
 impl FfiError for OperationError {
@@ -34,6 +47,7 @@ impl FfiError for OperationError {
             Self::Remote(error) => error.is_user_error(),
             Self::Local(error) => error.is_user_error(),
             Self::Progress(_) => false,
+            Self::UnknownOwner(_) => true,
         }
     }
 }
@@ -1,29 +1,36 @@
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use mlua::IntoLua;
+use mlua::{FromLua, IntoLua};
 use serde::Serialize;
 
 use super::{
+    error::FfiError,
     host::{CommandError, HostClient},
-    local::with_local_dir,
-    ssh::{ConnectionError, SshClient, SshError},
+    operator::{FileSystemOperator, MetadataError, MetadataType},
+    shell::{ShellRegistry, ShellSession, ShellSessionKind, quote},
+    ssh::{ConnectionError, RebootError, SshConnection, SshError},
 };
 use crate::{
     engine::readonly::set_readonly,
     error::ErrorReport,
+    logger::LogLevel,
     memory::target_systems::{TargetSystem, TargetSystemKind},
-    progress::ProgressContext,
+    progress::{CommandProgress, ProgressContext, format_duration},
 };
 
 #[derive(Clone)]
 pub struct Executor {
     kind: ExecutorKind,
     progress: ProgressContext,
+    shells: ShellRegistry,
 }
 
 #[derive(Clone)]
 enum ExecutorKind {
-    Ssh(SshClient),
+    Ssh(SshConnection),
     Host(HostClient),
     Local(HostClient, PathBuf),
 }
@@ -35,18 +42,41 @@ impl Executor {
         home_path: PathBuf,
     ) -> Result<Self, ExecutionTargetSetError> {
         Ok(match &config.kind {
-            TargetSystemKind::Remote(remote_target_system) => Self {
-                kind: ExecutorKind::Ssh(SshClient::connect(remote_target_system)?),
-                progress,
-            },
+            TargetSystemKind::Remote(remote_target_system) => {
+                let ssh_connection =
+                    SshConnection::connect(remote_target_system, &home_path, progress.clone())?;
+
+                if !remote_target_system.algorithms.is_empty() {
+                    progress.log_verbose(&format!(
+                        "Negotiated SSH algorithms with {:?}: {}",
+                        config.name,
+                        ssh_connection.client().negotiated_algorithms().join(", ")
+                    ));
+                }
+
+                Self {
+                    kind: ExecutorKind::Ssh(ssh_connection),
+                    progress,
+                    shells: ShellRegistry::default(),
+                }
+            }
             TargetSystemKind::Local => Self::new_local(progress, home_path),
         })
     }
 
+    /// Connection of a remote system, shared with its file system operator
+    pub(super) fn ssh_connection(&self) -> Option<&SshConnection> {
+        match &self.kind {
+            ExecutorKind::Ssh(ssh_connection) => Some(ssh_connection),
+            ExecutorKind::Host(_) | ExecutorKind::Local(..) => None,
+        }
+    }
+
     pub fn new_local(progress: ProgressContext, home_path: PathBuf) -> Self {
         Self {
             kind: ExecutorKind::Local(HostClient, home_path),
             progress,
+            shells: ShellRegistry::default(),
         }
     }
 
@@ -54,24 +84,54 @@ impl Executor {
         Self {
             kind: ExecutorKind::Host(HostClient),
             progress,
+            shells: ShellRegistry::default(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Default)]
 pub struct CommandResult {
-    pub stdout: String,
-    pub stderr: String,
+    /// Output as received, which is not necessarily valid UTF-8
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
     pub exit_code: i32,
+    pub elapsed: Duration,
+    /// Whether the command was not run because a guard of its
+    /// `CommandOptions` was met or because of a dry run
+    pub skipped: bool,
 }
 
-impl IntoLua for CommandResult {
-    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+/// A command run while evaluating the entrypoint.
+#[derive(Debug, Clone)]
+pub struct RecordedCommand {
+    pub command: String,
+    pub result: CommandResult,
+}
+
+impl CommandResult {
+    /// `stdout` with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn stdout_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// `stderr` with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn stderr_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    fn into_lua_table(self, lua: &mlua::Lua, raw_output: bool) -> mlua::Result<mlua::Value> {
         let result_table = lua.create_table()?;
 
-        result_table.set("stdout", self.stdout)?;
-        result_table.set("stderr", self.stderr)?;
+        if raw_output {
+            result_table.set("stdout", lua.create_string(&self.stdout)?)?;
+            result_table.set("stderr", lua.create_string(&self.stderr)?)?;
+        } else {
+            result_table.set("stdout", self.stdout_text())?;
+            result_table.set("stderr", self.stderr_text())?;
+        }
         result_table.set("exit_code", self.exit_code)?;
+        result_table.set("elapsed", self.elapsed.as_secs_f64())?;
+        result_table.set("skipped", self.skipped)?;
 
         let result_table = set_readonly(lua, result_table).map_err(|error| {
             mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
@@ -81,6 +141,228 @@ impl IntoLua for CommandResult {
     }
 }
 
+impl IntoLua for CommandResult {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.into_lua_table(lua, false)
+    }
+}
+
+/// A [`CommandResult`] whose output is passed to Lua byte for byte, as
+/// returned by `run_command_bytes`.
+pub struct RawCommandResult(pub CommandResult);
+
+impl IntoLua for RawCommandResult {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.0.into_lua_table(lua, true)
+    }
+}
+
+/// Options of `run_command`: the directory to run in and guards that skip a
+/// command whose effect is already in place.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOptions {
+    /// Directory the command and the `unless` check run in
+    pub cwd: Option<PathBuf>,
+    /// Variables set for the command and the `unless` check
+    pub env: BTreeMap<String, String>,
+    /// Path the command creates, it is skipped if the path exists
+    pub creates: Option<PathBuf>,
+    /// Command that checks the effect, the command is skipped if it succeeds
+    pub unless: Option<String>,
+    /// Whether a non-zero exit code is an error
+    pub check: bool,
+}
+
+impl FromLua for CommandOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(table) => Ok(Self {
+                cwd: table
+                    .get("cwd")
+                    .or(Err(mlua::Error::runtime("\"cwd\" is invalid")))?,
+                env: parse_env(table.get("env"))?,
+                creates: table
+                    .get("creates")
+                    .or(Err(mlua::Error::runtime("\"creates\" is invalid")))?,
+                unless: table
+                    .get("unless")
+                    .or(Err(mlua::Error::runtime("\"unless\" is invalid")))?,
+                check: table
+                    .get::<Option<bool>>("check")
+                    .or(Err(mlua::Error::runtime("\"check\" is invalid")))?
+                    .unwrap_or(false),
+            }),
+            value => Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: String::from("CommandOptions"),
+                message: None,
+            }),
+        }
+    }
+}
+
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_WAIT_INTERVAL: Duration = Duration::from_secs(1);
+static DEFAULT_WAIT_HOST: &str = "127.0.0.1";
+
+/// What `wait_for` polls until it holds.
+#[derive(Debug, Clone)]
+pub enum WaitCondition {
+    /// The port accepts TCP connections, checked from the system itself
+    Port { host: String, port: u16 },
+    /// The path exists
+    Path(PathBuf),
+    /// The command exits with `0`
+    Command(String),
+}
+
+impl std::fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Port { host, port } => {
+                write!(f, "port {} on {} to accept connections", port, host)
+            }
+            Self::Path(path) => write!(f, "{:?} to exist", path),
+            Self::Command(command) => write!(f, "`{}` to succeed", command),
+        }
+    }
+}
+
+/// Options of `wait_for`: the condition and how long and how often it is
+/// checked.
+#[derive(Debug, Clone)]
+pub struct WaitOptions {
+    pub condition: WaitCondition,
+    pub timeout: Duration,
+    /// Time between two checks
+    pub interval: Duration,
+}
+
+fn parse_seconds(table: &mlua::Table, name: &str) -> mlua::Result<Option<Duration>> {
+    table
+        .get::<Option<f64>>(name)
+        .or(Err(mlua::Error::runtime(format!(
+            "\"{}\" is invalid",
+            name
+        ))))?
+        .map(|seconds| match Duration::try_from_secs_f64(seconds) {
+            Ok(duration) if !duration.is_zero() => Ok(duration),
+            _ => Err(mlua::Error::runtime(format!(
+                "Invalid {} value: {}. Expected a positive number of seconds",
+                name, seconds
+            ))),
+        })
+        .transpose()
+}
+
+impl FromLua for WaitOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        let table = match value {
+            mlua::Value::Table(table) => table,
+            value => {
+                return Err(mlua::Error::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: String::from("WaitOptions"),
+                    message: None,
+                });
+            }
+        };
+
+        let port: Option<u16> = table
+            .get("port")
+            .or(Err(mlua::Error::runtime("\"port\" is invalid")))?;
+        let host: Option<String> = table
+            .get("host")
+            .or(Err(mlua::Error::runtime("\"host\" is invalid")))?;
+        let path: Option<PathBuf> = table
+            .get("path")
+            .or(Err(mlua::Error::runtime("\"path\" is invalid")))?;
+        let command: Option<String> = table
+            .get("command")
+            .or(Err(mlua::Error::runtime("\"command\" is invalid")))?;
+
+        if host.is_some() && port.is_none() {
+            return Err(mlua::Error::runtime("\"host\" requires \"port\""));
+        }
+
+        let condition = match (port, path, command) {
+            (Some(port), None, None) => WaitCondition::Port {
+                host: host.unwrap_or_else(|| DEFAULT_WAIT_HOST.to_string()),
+                port,
+            },
+            (None, Some(path), None) => WaitCondition::Path(path),
+            (None, None, Some(command)) => WaitCondition::Command(command),
+            _ => {
+                return Err(mlua::Error::runtime(
+                    "Expected exactly one of \"port\", \"path\" or \"command\"",
+                ));
+            }
+        };
+
+        Ok(Self {
+            condition,
+            timeout: parse_seconds(&table, "timeout")?.unwrap_or(DEFAULT_WAIT_TIMEOUT),
+            interval: parse_seconds(&table, "interval")?.unwrap_or(DEFAULT_WAIT_INTERVAL),
+        })
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+fn parse_env(
+    value: mlua::Result<Option<BTreeMap<String, String>>>,
+) -> mlua::Result<BTreeMap<String, String>> {
+    let env = value
+        .or(Err(mlua::Error::runtime("\"env\" is invalid")))?
+        .unwrap_or_default();
+
+    match env.keys().find(|name| !is_variable_name(name)) {
+        Some(name) => Err(mlua::Error::runtime(format!(
+            "\"env\" contains the invalid variable name {:?}",
+            name
+        ))),
+        None => Ok(env),
+    }
+}
+
+/// Command that succeeds if `port` on `host` accepts TCP connections. It
+/// uses `nc` and falls back to bash's `/dev/tcp` where `nc` is missing.
+fn port_check_command(host: &str, port: u16) -> String {
+    format!(
+        "nc -z -w 1 {} {} 2>/dev/null || bash -c {} 2>/dev/null",
+        quote(host),
+        port,
+        quote(&format!(
+            "exec 3<>{}",
+            quote(&format!("/dev/tcp/{}/{}", host, port))
+        ))
+    )
+}
+
+/// Prefixes `cmd` with the shell statements that set `env` and change to
+/// `cwd`, for systems where the process environment cannot be set directly.
+fn with_shell_environment(cmd: &str, cwd: Option<&Path>, env: &BTreeMap<String, String>) -> String {
+    let mut prefix = String::new();
+
+    for (name, value) in env {
+        prefix.push_str(&format!("export {}={}\n", name, quote(value)));
+    }
+
+    if let Some(cwd) = cwd {
+        prefix.push_str(&format!("cd {} || exit\n", quote(&cwd.to_string_lossy())));
+    }
+
+    prefix + cmd
+}
+
 #[derive(thiserror::Error, Debug)]
 #[error("Failed to set execution target")]
 pub enum ExecutionTargetSetError {
@@ -100,20 +382,472 @@ pub enum TaskError {
     UninitializedSshClientError(#[from] UninitializedSshClientError),
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to execute command")]
+pub enum GuardedCommandError {
+    Task(#[from] TaskError),
+    Metadata(#[from] MetadataError),
+    WorkingDirectory(#[from] WorkingDirectoryError),
+    Check(#[from] CommandCheckError),
+}
+
+impl FfiError for GuardedCommandError {
+    fn is_user_error(&self) -> bool {
+        match self {
+            Self::Task(error) => error.is_user_error(),
+            Self::Metadata(error) => error.is_user_error(),
+            Self::WorkingDirectory(_) | Self::Check(_) => true,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to wait for the condition")]
+pub enum WaitError {
+    Task(#[from] TaskError),
+    Metadata(#[from] MetadataError),
+    Timeout(#[from] WaitTimeoutError),
+}
+
+impl FfiError for WaitError {
+    fn is_user_error(&self) -> bool {
+        match self {
+            Self::Task(error) => error.is_user_error(),
+            Self::Metadata(error) => error.is_user_error(),
+            Self::Timeout(_) => true,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Timed out after {} waiting for {condition}, the last check failed: {last_failure}",
+    format_duration(*timeout)
+)]
+pub struct WaitTimeoutError {
+    condition: WaitCondition,
+    timeout: Duration,
+    last_failure: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to reboot the system")]
+pub enum SystemRebootError {
+    Unsupported(#[from] RebootUnsupportedError),
+    Ssh(#[from] RebootError),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Only remote systems can be rebooted")]
+pub struct RebootUnsupportedError;
+
+impl FfiError for SystemRebootError {
+    fn is_user_error(&self) -> bool {
+        match self {
+            Self::Unsupported(_) => true,
+            Self::Ssh(error) => error.is_user_error(),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Working directory {0:?} does not exist or is not a directory")]
+pub struct WorkingDirectoryError(PathBuf);
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Command `{command}` exited with code {exit_code}{}{}",
+    format_output("stdout", stdout),
+    format_output("stderr", stderr)
+)]
+pub struct CommandCheckError {
+    command: String,
+    exit_code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+fn format_output(name: &str, output: &str) -> String {
+    match output.trim_end() {
+        "" => String::new(),
+        output => format!("\n{}:\n{}", name, output),
+    }
+}
+
+impl FfiError for TaskError {
+    fn is_user_error(&self) -> bool {
+        match self {
+            Self::Ssh(error) => error.is_task_deadline(),
+            Self::Host(error) => error.is_task_deadline(),
+            _ => false,
+        }
+    }
+}
+
 impl Executor {
-    pub fn run_command(&self, cmd: String) -> Result<CommandResult, TaskError> {
+    /// Runs `cmd` with the variables `env` in `cwd`, or in the default
+    /// directory of the system if `cwd` is `None`.
+    pub fn run_command(
+        &self,
+        cmd: String,
+        cwd: Option<&Path>,
+        env: &BTreeMap<String, String>,
+    ) -> Result<CommandResult, TaskError> {
+        if let Some(result) = self.progress.replayed_command(&cmd) {
+            return Ok(result);
+        }
+
+        // Whether a command changes the system is unknown, so a dry run
+        // treats every command as a change
+        if self
+            .progress
+            .skips_change(|| format!("run command `{}`", cmd))
+        {
+            return Ok(CommandResult {
+                skipped: true,
+                ..CommandResult::default()
+            });
+        }
+
         let progress = self.progress.command(&cmd)?;
+        let result = self.execute(&cmd, cwd, env, &progress)?;
+
+        progress.finish();
+        self.progress.record_command(&cmd, &result);
+
+        Ok(result)
+    }
 
+    fn execute(
+        &self,
+        cmd: &str,
+        cwd: Option<&Path>,
+        env: &BTreeMap<String, String>,
+        progress: &CommandProgress,
+    ) -> Result<CommandResult, TaskError> {
         let result = match &self.kind {
-            ExecutorKind::Ssh(ssh_client) => ssh_client.execute_command(&cmd, &progress)?,
-            ExecutorKind::Host(local_client) => local_client.execute_command(&cmd, &progress)?,
+            ExecutorKind::Ssh(ssh_connection) => {
+                ssh_connection.execute_command(&with_shell_environment(cmd, cwd, env), progress)?
+            }
+            ExecutorKind::Host(local_client) => {
+                local_client.execute_command(cmd, cwd, env, progress)?
+            }
             ExecutorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.execute_command(&cmd, &progress))?
+                let cwd = match cwd {
+                    Some(cwd) => home_path.join(cwd),
+                    None => home_path.clone(),
+                };
+
+                local_client.execute_command(cmd, Some(&cwd), env, progress)?
             }
         };
 
-        progress.finish();
+        self.progress.log_verbose(&format!(
+            "Running `{}` exited with code {} after {}",
+            cmd,
+            result.exit_code,
+            format_duration(result.elapsed)
+        ));
 
         Ok(result)
     }
 }
+
+impl Executor {
+    /// Runs `cmd` unless a guard of `options` shows that its effect is
+    /// already in place. `file_system_operator` must belong to the same system.
+    pub fn run_guarded_command(
+        &self,
+        cmd: String,
+        options: &CommandOptions,
+        file_system_operator: &FileSystemOperator,
+    ) -> Result<CommandResult, GuardedCommandError> {
+        let cwd = options.cwd.as_deref();
+
+        if let Some(cwd) = cwd
+            && !file_system_operator
+                .metadata(cwd)?
+                .is_some_and(|metadata| metadata.r#type == MetadataType::Directory)
+        {
+            return Err(WorkingDirectoryError(cwd.to_path_buf()).into());
+        }
+
+        if let Some(path) = &options.creates
+            && file_system_operator.metadata(path)?.is_some()
+        {
+            return Ok(self.skip_command(&cmd, &format!("{:?} exists", path)));
+        }
+
+        if let Some(unless) = &options.unless
+            && self
+                .run_command(unless.clone(), cwd, &options.env)?
+                .exit_code
+                == 0
+        {
+            return Ok(self.skip_command(&cmd, &format!("`{}` succeeded", unless)));
+        }
+
+        let result = self.run_command(cmd.clone(), cwd, &options.env)?;
+
+        if options.check && result.exit_code != 0 {
+            return Err(CommandCheckError {
+                command: cmd,
+                exit_code: result.exit_code,
+                stdout: result.stdout_text().into_owned(),
+                stderr: result.stderr_text().into_owned(),
+            }
+            .into());
+        }
+
+        Ok(result)
+    }
+
+    fn skip_command(&self, cmd: &str, reason: &str) -> CommandResult {
+        self.progress.log(
+            LogLevel::Info,
+            &format!("Skipped command `{}` because {}", cmd, reason),
+        );
+
+        CommandResult {
+            skipped: true,
+            ..CommandResult::default()
+        }
+    }
+
+    pub fn open_shell(&self) -> Result<ShellSession, TaskError> {
+        let kind = match &self.kind {
+            ExecutorKind::Ssh(ssh_connection) => {
+                ShellSessionKind::Ssh(ssh_connection.open_shell()?)
+            }
+            ExecutorKind::Host(host_client) => {
+                ShellSessionKind::Host(host_client.open_shell(None)?)
+            }
+            ExecutorKind::Local(host_client, home_path) => {
+                ShellSessionKind::Host(host_client.open_shell(Some(home_path))?)
+            }
+        };
+
+        let session = ShellSession::new(kind, self.progress.clone());
+        self.shells.register(session.clone());
+
+        Ok(session)
+    }
+
+    pub fn close_shells(&self) {
+        self.shells.close_all();
+    }
+
+    /// Checks the condition of `options` every interval until it holds and
+    /// returns how long that took, or `None` if it was not checked in a dry
+    /// run. Checks are not shown as commands. `file_system_operator` must
+    /// belong to the same system.
+    pub fn wait_for(
+        &self,
+        options: &WaitOptions,
+        file_system_operator: &FileSystemOperator,
+    ) -> Result<Option<Duration>, WaitError> {
+        let condition = &options.condition;
+
+        // Commands do not run in a dry run, so the condition cannot be checked
+        if self.progress.is_dry_run() {
+            self.progress
+                .log(LogLevel::Info, &format!("Would wait for {}", condition));
+
+            return Ok(None);
+        }
+
+        let started = Instant::now();
+        let mut checks = 0;
+
+        loop {
+            let Some(failure) = self.check_condition(condition, file_system_operator)? else {
+                return Ok(Some(started.elapsed()));
+            };
+
+            if started.elapsed() + options.interval > options.timeout {
+                return Err(WaitTimeoutError {
+                    condition: condition.clone(),
+                    timeout: options.timeout,
+                    last_failure: failure,
+                }
+                .into());
+            }
+
+            if checks == 0 {
+                self.progress.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Waiting up to {} for {}",
+                        format_duration(options.timeout),
+                        condition
+                    ),
+                );
+            }
+
+            checks += 1;
+
+            std::thread::sleep(options.interval);
+        }
+    }
+
+    /// Checks `condition` once. Returns why it does not hold, or `None` if it
+    /// does.
+    fn check_condition(
+        &self,
+        condition: &WaitCondition,
+        file_system_operator: &FileSystemOperator,
+    ) -> Result<Option<String>, WaitError> {
+        let command = match condition {
+            WaitCondition::Path(path) => {
+                return Ok(match file_system_operator.metadata(path)? {
+                    Some(_) => None,
+                    None => Some(format!("{:?} does not exist", path)),
+                });
+            }
+            WaitCondition::Port { host, port } => port_check_command(host, *port),
+            WaitCondition::Command(command) => command.clone(),
+        };
+
+        let progress = self.progress.quiet_command();
+        let result = self.execute(&command, None, &BTreeMap::new(), &progress)?;
+
+        Ok(match (condition, result.exit_code) {
+            (_, 0) => None,
+            (WaitCondition::Port { host, port }, _) => {
+                Some(format!("{}:{} refused the connection", host, port))
+            }
+            (_, exit_code) => Some(format!(
+                "`{}` exited with code {}{}",
+                command,
+                exit_code,
+                format_output("stderr", &result.stderr_text())
+            )),
+        })
+    }
+
+    /// Reboots a remote system and waits for it to come back, for at most
+    /// `timeout`. Returns how long the system was unreachable, or `None` if
+    /// the reboot was skipped in a dry run.
+    pub fn reboot(&self, timeout: Duration) -> Result<Option<Duration>, SystemRebootError> {
+        let ExecutorKind::Ssh(ssh_connection) = &self.kind else {
+            return Err(RebootUnsupportedError.into());
+        };
+
+        if self
+            .progress
+            .skips_change(|| "reboot the system".to_string())
+        {
+            return Ok(None);
+        }
+
+        // Shells do not survive the reboot
+        self.close_shells();
+        self.progress.log(
+            LogLevel::Info,
+            &format!(
+                "Rebooting, waiting up to {} for the system to come back",
+                format_duration(timeout)
+            ),
+        );
+
+        let downtime = ssh_connection.reboot(timeout)?;
+
+        self.progress.log(
+            LogLevel::Info,
+            &format!("System is back after {}", format_duration(downtime)),
+        );
+
+        Ok(Some(downtime))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use super::{
+        Executor, HostClient, WaitCondition, WaitError, WaitOptions, port_check_command,
+        with_shell_environment,
+    };
+    use crate::{
+        engine::delegator::operator::FileSystemOperator,
+        logger::Logger,
+        progress::{CommandProgress, ProgressContext},
+    };
+
+    #[test]
+    fn shell_environment_keeps_values_intact() {
+        let value = "it's a \"quoted\" $HOME `id` value";
+        let env = BTreeMap::from([("ARC_TEST_VALUE".to_string(), value.to_string())]);
+        let command = with_shell_environment(
+            "printf '%s' \"$ARC_TEST_VALUE\"; pwd",
+            Some(std::path::Path::new("/")),
+            &env,
+        );
+
+        let result = HostClient
+            .execute_command(&command, None, &BTreeMap::new(), &CommandProgress::noop())
+            .unwrap();
+
+        assert_eq!(result.stdout_text(), format!("{}/\n", value));
+    }
+
+    #[test]
+    fn port_check_succeeds_only_while_the_port_is_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let check = || {
+            HostClient
+                .execute_command(
+                    &port_check_command("127.0.0.1", port),
+                    None,
+                    &BTreeMap::new(),
+                    &CommandProgress::noop(),
+                )
+                .unwrap()
+                .exit_code
+        };
+
+        assert_eq!(check(), 0);
+        drop(listener);
+        assert_ne!(check(), 0);
+    }
+
+    #[test]
+    fn waiting_ends_once_the_path_exists_or_times_out() {
+        let progress = ProgressContext::new(Logger::new(), false, false);
+        let directory = std::env::temp_dir();
+        let executor = Executor::new_local(progress.clone(), directory.clone());
+        let operator = FileSystemOperator::new_local(progress, directory.clone());
+        let path = directory.join(format!("arc-wait-for-{}", std::process::id()));
+        let wait = |timeout| {
+            executor.wait_for(
+                &WaitOptions {
+                    condition: WaitCondition::Path(path.clone()),
+                    timeout,
+                    interval: Duration::from_millis(50),
+                },
+                &operator,
+            )
+        };
+
+        let created = std::thread::spawn({
+            let path = path.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(200));
+                std::fs::write(path, "").unwrap();
+            }
+        });
+        let waited = wait(Duration::from_secs(5)).unwrap().unwrap();
+        created.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(waited >= Duration::from_millis(200));
+        assert!(matches!(
+            wait(Duration::from_millis(200)),
+            Err(WaitError::Timeout(_))
+        ));
+    }
+}
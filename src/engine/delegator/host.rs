@@ -1,8 +1,10 @@
-use std::io::Read;
-use std::os::unix::fs::PermissionsExt;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
 use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 use crate::engine::delegator::host::error::classify_io_error;
 use crate::progress::{CommandProgress, ProgressWriter, TransferProgress};
@@ -13,8 +15,10 @@ use error::ExecutionError;
 pub use error::{InfrastructureError, UserError};
 
 use super::{
+    error::TaskDeadlineError,
     executor::CommandResult,
-    operator::{FileWriteResult, MetadataResult, MetadataType},
+    operator::{FileWriteResult, MetadataResult, MetadataType, OpenMode, WriteOptions},
+    shell::{Sentinel, ShellOutput},
 };
 
 #[derive(Clone)]
@@ -34,20 +38,192 @@ pub enum CommandError {
     Io(#[from] std::io::Error),
     OutputReaderPanic(#[from] OutputReaderPanicError),
     OutputReader(#[from] OutputReaderError),
+    TaskDeadline(#[from] TaskDeadlineError),
+}
+
+impl CommandError {
+    pub fn is_task_deadline(&self) -> bool {
+        matches!(self, Self::TaskDeadline(_))
+    }
+}
+
+static CHILD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Receives the next message, giving up once the deadline of the running
+/// task has passed. Returns `Ok(None)` once all senders are gone.
+fn receive_until_deadline<T>(
+    receiver: &mpsc::Receiver<T>,
+    progress: &CommandProgress,
+) -> Result<Option<T>, TaskDeadlineError> {
+    // Queued messages are still returned after the deadline, so a command
+    // that keeps writing has to be stopped here
+    if progress.deadline_reached() {
+        return Err(TaskDeadlineError);
+    }
+
+    match progress.remaining() {
+        Some(remaining) => match receiver.recv_timeout(remaining) {
+            Ok(message) => Ok(Some(message)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(TaskDeadlineError),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Ok(None),
+        },
+        None => Ok(receiver.recv().ok()),
+    }
+}
+
+/// Waits for the child to exit, killing it once the deadline of the running
+/// task has passed.
+fn wait_until_deadline(
+    child: &mut Child,
+    progress: &CommandProgress,
+) -> Result<ExitStatus, CommandError> {
+    if progress.remaining().is_none() {
+        return Ok(child.wait()?);
+    }
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if progress.deadline_reached() {
+            kill(child);
+
+            return Err(TaskDeadlineError.into());
+        }
+
+        std::thread::sleep(CHILD_POLL_INTERVAL);
+    }
+}
+
+fn kill(child: &mut Child) {
+    _ = child.kill();
+    _ = child.wait();
+}
+
+enum ShellChunk {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+pub struct HostShell {
+    child: Child,
+    stdin: ChildStdin,
+    output: mpsc::Receiver<ShellChunk>,
+}
+
+fn forward_output(
+    mut pipe: impl Read + Send + 'static,
+    tx: mpsc::Sender<ShellChunk>,
+    wrap: fn(Vec<u8>) -> ShellChunk,
+) {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+
+        while let Ok(n) = pipe.read(&mut buf) {
+            if n == 0 || tx.send(wrap(buf[..n].to_vec())).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+impl HostShell {
+    pub fn run(
+        &mut self,
+        command: &str,
+        progress: &CommandProgress,
+    ) -> Result<Option<CommandResult>, CommandError> {
+        let sentinel = Sentinel::new();
+
+        self.stdin.write_all(sentinel.wrap(command).as_bytes())?;
+        self.stdin.flush()?;
+
+        let mut output = ShellOutput::new();
+
+        loop {
+            let chunk = match receive_until_deadline(&self.output, progress) {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(error) => {
+                    kill(&mut self.child);
+
+                    return Err(error.into());
+                }
+            };
+
+            match chunk {
+                ShellChunk::Stdout(data) => output.push_stdout(&data, &sentinel, progress),
+                ShellChunk::Stderr(data) => output.push_stderr(&data, &sentinel, progress),
+            }
+
+            if let Some(result) = output.complete(&sentinel) {
+                return Ok(Some(result));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn close(mut self) {
+        _ = self.stdin.write_all(b"exit\n");
+        drop(self.stdin);
+
+        _ = self.child.wait();
+    }
 }
 
 impl HostClient {
+    pub fn open_shell(&self, working_directory: Option<&Path>) -> Result<HostShell, CommandError> {
+        let mut command = Command::new("sh");
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(working_directory) = working_directory {
+            command.current_dir(working_directory);
+        }
+
+        let mut child = command.spawn()?;
+
+        let stdin = child.stdin.take().expect("shell stdin has been taken");
+        let stdout_pipe = child.stdout.take().expect("shell stdout has been taken");
+        let stderr_pipe = child.stderr.take().expect("shell stderr has been taken");
+
+        let (tx, rx) = mpsc::channel();
+        forward_output(stdout_pipe, tx.clone(), ShellChunk::Stdout);
+        forward_output(stderr_pipe, tx, ShellChunk::Stderr);
+
+        Ok(HostShell {
+            child,
+            stdin,
+            output: rx,
+        })
+    }
+
     pub fn execute_command(
         &self,
         command: &str,
+        cwd: Option<&Path>,
+        env: &BTreeMap<String, String>,
         progress: &CommandProgress,
     ) -> Result<CommandResult, CommandError> {
-        let mut child = Command::new("sh")
+        let started = Instant::now();
+
+        let mut process = Command::new("sh");
+        process
             .arg("-c")
             .arg(command)
+            .envs(env)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            .stderr(Stdio::piped());
+
+        if let Some(cwd) = cwd {
+            process.current_dir(cwd);
+        }
+
+        let mut child = process.spawn()?;
 
         let mut stdout_pipe = child.stdout.take().expect("command stdout has been taken");
         let mut stderr_pipe = child.stderr.take().expect("command stderr has been taken");
@@ -55,8 +231,8 @@ impl HostClient {
         let (tx, rx) = mpsc::channel::<String>();
         let tx_stderr = tx.clone();
 
-        let stdout_thread = std::thread::spawn(move || -> std::io::Result<String> {
-            let mut data = String::new();
+        let stdout_thread = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+            let mut data = Vec::new();
             let mut buf = [0u8; 4096];
 
             loop {
@@ -66,17 +242,18 @@ impl HostClient {
                     break;
                 }
 
-                let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
-                data.push_str(&chunk);
+                // Decoded as a whole at the end, so that characters split
+                // between chunks stay intact in the result
+                data.extend_from_slice(&buf[..n]);
 
-                _ = tx.send(chunk);
+                _ = tx.send(String::from_utf8_lossy(&buf[..n]).into_owned());
             }
 
             Ok(data)
         });
 
-        let stderr_thread = std::thread::spawn(move || -> std::io::Result<String> {
-            let mut data = String::new();
+        let stderr_thread = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+            let mut data = Vec::new();
             let mut buf = [0u8; 4096];
 
             loop {
@@ -86,16 +263,27 @@ impl HostClient {
                     break;
                 }
 
-                let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
-                data.push_str(&chunk);
+                // Decoded as a whole at the end, so that characters split
+                // between chunks stay intact in the result
+                data.extend_from_slice(&buf[..n]);
 
-                _ = tx_stderr.send(chunk);
+                _ = tx_stderr.send(String::from_utf8_lossy(&buf[..n]).into_owned());
             }
             Ok(data)
         });
 
         let mut combined = String::new();
-        for chunk in rx {
+        loop {
+            let chunk = match receive_until_deadline(&rx, progress) {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(error) => {
+                    kill(&mut child);
+
+                    return Err(error.into());
+                }
+            };
+
             combined.push_str(&chunk);
             progress.update_output(&combined);
         }
@@ -109,12 +297,14 @@ impl HostClient {
             .map_err(|_| OutputReaderPanicError)?
             .map_err(OutputReaderError)?;
 
-        let status = child.wait()?;
+        let status = wait_until_deadline(&mut child, progress)?;
 
         Ok(CommandResult {
             stdout: stdout_data,
             stderr: stderr_data,
             exit_code: status.code().unwrap_or(-1),
+            elapsed: started.elapsed(),
+            skipped: false,
         })
     }
 
@@ -126,12 +316,28 @@ impl HostClient {
         &self,
         path: &PathBuf,
         content: &[u8],
+        options: &WriteOptions,
     ) -> Result<FileWriteResult, ExecutionError> {
-        std::fs::write(path, content).map_err(|error| classify_io_error(error, path))?;
+        let mut open_options = std::fs::OpenOptions::new();
+        match options.open_mode {
+            OpenMode::Truncate => open_options.write(true).create(true).truncate(true),
+            OpenMode::Append => open_options.append(true).create(true),
+            OpenMode::Exclusive => open_options.write(true).create_new(true),
+        };
+
+        if let Some(mode) = options.mode {
+            open_options.mode(mode);
+        }
+
+        open_options
+            .open(path)
+            .and_then(|mut file| file.write_all(content))
+            .map_err(|error| classify_io_error(error, path))?;
 
         Ok(FileWriteResult {
             path: path.clone(),
             bytes_written: content.len(),
+            backup_path: None,
         })
     }
 
@@ -139,6 +345,33 @@ impl HostClient {
         std::fs::rename(from, to).map_err(|error| classify_io_error(error, from))
     }
 
+    pub fn set_ownership(
+        &self,
+        path: &Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> Result<(), ExecutionError> {
+        std::os::unix::fs::chown(path, uid, gid).map_err(|error| classify_io_error(error, path))
+    }
+
+    /// Looks up `key` in a name service database such as `passwd` or
+    /// `group`. Returns `None` if the key does not exist.
+    pub fn getent(&self, database: &str, key: &str) -> Result<Option<String>, ExecutionError> {
+        let output = Command::new("getent")
+            .arg(database)
+            .arg(key)
+            .output()
+            .map_err(|error| classify_io_error(error, Path::new("getent")))?;
+
+        match output.status.code() {
+            Some(0) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+            Some(super::GETENT_KEY_NOT_FOUND) => Ok(None),
+            _ => Err(ExecutionError::User(UserError::CommandFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))),
+        }
+    }
+
     pub fn remove_file(&self, path: &PathBuf) -> Result<(), ExecutionError> {
         std::fs::remove_file(path).map_err(|error| classify_io_error(error, path))
     }
@@ -147,7 +380,9 @@ impl HostClient {
         std::fs::remove_dir_all(path).map_err(|error| classify_io_error(error, path))
     }
 
-    pub fn create_directory(&self, path: &Path) -> Result<(), ExecutionError> {
+    /// Creates `path` and its missing ancestors. `path` itself gets `mode` if
+    /// given, ancestors the default permissions.
+    pub fn create_directory(&self, path: &Path, mode: Option<u32>) -> Result<(), ExecutionError> {
         let ancestors = path
             .ancestors()
             .filter(|p| !p.as_os_str().is_empty())
@@ -167,6 +402,13 @@ impl HostClient {
                 Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
                     std::fs::create_dir(ancestor_path)
                         .map_err(|error| classify_io_error(error, ancestor_path))?;
+
+                    // `create_dir` applies the umask, so the mode is set afterwards
+                    if let Some(mode) = mode
+                        && ancestor_path == path
+                    {
+                        self.set_permissions(ancestor_path, mode)?;
+                    }
                 }
                 Err(error) => {
                     return Err(classify_io_error(error, ancestor_path));
@@ -207,10 +449,10 @@ impl HostClient {
             result.push(MetadataResult {
                 path: entry_path,
                 size: Some(metadata.len()),
-                permissions: Some(metadata.permissions().mode() & 0o777),
+                permissions: Some(metadata.permissions().mode() & 0o7777),
                 r#type,
-                uid: None,
-                gid: None,
+                uid: Some(metadata.uid()),
+                gid: Some(metadata.gid()),
                 accessed: metadata
                     .accessed()
                     .ok()
@@ -240,10 +482,10 @@ impl HostClient {
                 Ok(Some(MetadataResult {
                     path: path.to_path_buf(),
                     size: Some(metadata.len()),
-                    permissions: Some(metadata.permissions().mode() & 0o777),
+                    permissions: Some(metadata.permissions().mode() & 0o7777),
                     r#type,
-                    uid: None,
-                    gid: None,
+                    uid: Some(metadata.uid()),
+                    gid: Some(metadata.gid()),
                     accessed: metadata.accessed().ok().map(|time| {
                         time.duration_since(std::time::UNIX_EPOCH)
                             .unwrap()
@@ -334,10 +576,95 @@ impl HostClient {
 
         let bytes_written =
             std::io::copy(reader, &mut writer).map_err(|error| classify_io_error(error, path))?;
+        // Dropping the buffered writer would ignore a failure of the last write
+        writer
+            .flush()
+            .map_err(|error| classify_io_error(error, path))?;
 
         Ok(FileWriteResult {
             path: path.to_path_buf(),
             bytes_written: bytes_written as usize,
+            backup_path: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("arc-host-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn exclusive_write_fails_on_existing_file() {
+        let path = temporary_path("exclusive");
+        std::fs::write(&path, "original").unwrap();
+
+        let options = WriteOptions {
+            open_mode: OpenMode::Exclusive,
+            ..WriteOptions::default()
+        };
+        let result = HostClient.write_file(&path, b"replacement", &options);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(ExecutionError::User(UserError::AlreadyExists(_)))
+        ));
+        assert_eq!(content, "original");
+    }
+
+    #[test]
+    fn exclusive_write_creates_missing_file() {
+        let path = temporary_path("exclusive-new");
+
+        let options = WriteOptions {
+            open_mode: OpenMode::Exclusive,
+            ..WriteOptions::default()
+        };
+        let result = HostClient.write_file(&path, b"content", &options);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap().bytes_written, 7);
+        assert_eq!(content, "content");
+    }
+
+    #[test]
+    fn command_writing_continuously_stops_at_deadline() {
+        let progress = CommandProgress::noop()
+            .with_deadline(Some(Instant::now() + Duration::from_millis(200)));
+        let started = Instant::now();
+
+        let result = HostClient.execute_command("yes", None, &BTreeMap::new(), &progress);
+
+        assert!(matches!(result, Err(CommandError::TaskDeadline(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn characters_split_between_chunks_are_kept() {
+        let result = HostClient
+            .execute_command(
+                "printf '%4095s\\303\\251' ''",
+                None,
+                &BTreeMap::new(),
+                &CommandProgress::noop(),
+            )
+            .unwrap();
+
+        assert_eq!(result.stdout_text(), format!("{}é", " ".repeat(4095)));
+    }
+
+    #[test]
+    fn queued_output_is_not_received_after_deadline() {
+        let (sender, receiver) = mpsc::channel();
+        sender.send("queued").unwrap();
+        let progress = CommandProgress::noop().with_deadline(Some(Instant::now()));
+
+        assert!(receive_until_deadline(&receiver, &progress).is_err());
+    }
+}
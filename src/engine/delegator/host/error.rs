@@ -32,6 +32,8 @@ pub enum UserError {
     ExecutableFileBusy(std::io::Error),
     #[error("Too many links")]
     TooManyLinks(std::io::Error),
+    #[error("Command failed: {0}")]
+    CommandFailed(String),
 }
 
 #[derive(thiserror::Error, Debug)]
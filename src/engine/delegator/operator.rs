@@ -2,24 +2,32 @@ use std::{
     fmt::Display,
     io::Read,
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
-use mlua::IntoLua;
+use md5::Md5;
+use mlua::{FromLua, IntoLua};
+use regex::Regex;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use strum::EnumString;
 
 use super::{
-    error::{FfiError, OperationError},
-    host::HostClient,
+    error::{FfiError, OperationError, UnknownOwnerError},
+    executor::Executor,
+    host::{self, HostClient},
     local::with_local_dir,
-    ssh::{ConnectionError, SshClient},
+    ssh::{self, SshClient, SshConnection},
 };
 use crate::{
     engine::{
         objects::{directory::Directory, file::File},
         readonly::set_readonly,
+        selection::matches_pattern,
     },
     error::ErrorReport,
-    memory::target_systems::{TargetSystem, TargetSystemKind},
+    logger::LogLevel,
     progress::{ProgressContext, TransferDirection, TransferProgress},
 };
 
@@ -48,7 +56,7 @@ pub struct FileSystemOperator {
 
 #[derive(Clone)]
 enum FileSystemOperatorKind {
-    Ssh(SshClient),
+    Ssh(SshConnection),
     Local(HostClient, PathBuf),
     Host(HostClient),
 }
@@ -69,18 +77,21 @@ impl Display for Locality {
 }
 
 impl FileSystemOperator {
+    /// Returns the operator of the system `executor` runs commands on. A
+    /// remote system's connection is shared, so that all operations of its
+    /// tasks use a single SSH session.
     pub fn new_for_system(
-        config: &TargetSystem,
+        executor: &Executor,
         progress: ProgressContext,
         home_path: PathBuf,
-    ) -> Result<Self, OperationTargetSetError> {
-        Ok(match &config.kind {
-            TargetSystemKind::Remote(remote_target_system) => Self {
-                kind: FileSystemOperatorKind::Ssh(SshClient::connect(remote_target_system)?),
+    ) -> Self {
+        match executor.ssh_connection() {
+            Some(ssh_connection) => Self {
+                kind: FileSystemOperatorKind::Ssh(ssh_connection.clone()),
                 progress,
             },
-            TargetSystemKind::Local => Self::new_local(progress, home_path),
-        })
+            None => Self::new_local(progress, home_path),
+        }
     }
 
     pub fn new_local(progress: ProgressContext, home_path: PathBuf) -> Self {
@@ -107,10 +118,134 @@ impl FileSystemOperator {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum OpenMode {
+    #[default]
+    Truncate,
+    Append,
+    Exclusive,
+}
+
+/// A user or group given by numeric id or by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnerSpec {
+    Id(u32),
+    Name(String),
+}
+
+impl FromLua for OwnerSpec {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Integer(id) => u32::try_from(id)
+                .map(OwnerSpec::Id)
+                .or(Err(mlua::Error::runtime(format!("Invalid id: {}", id)))),
+            mlua::Value::String(name) => {
+                let name = name.to_str()?.to_string();
+
+                Ok(match name.parse() {
+                    Ok(id) => OwnerSpec::Id(id),
+                    Err(_) => OwnerSpec::Name(name),
+                })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "OwnerSpec".to_string(),
+                message: Some("expected id or name".to_string()),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    pub open_mode: OpenMode,
+    pub sensitive: bool,
+    pub mode: Option<u32>,
+    pub owner: Option<OwnerSpec>,
+    pub group: Option<OwnerSpec>,
+    pub atomic: bool,
+    /// Copies an existing file next to it before it is written
+    pub backup: bool,
+}
+
+impl FromLua for WriteOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(table) => {
+                let open_str: Option<String> = table
+                    .get("open")
+                    .or(Err(mlua::Error::runtime("\"open\" is invalid")))?;
+                let open_mode = match open_str {
+                    Some(string) => OpenMode::from_str(&string).or(Err(mlua::Error::runtime(
+                        format!(
+                            "Invalid open value: \"{}\". Expected \"truncate\", \"append\", or \"exclusive\"",
+                            string
+                        ),
+                    )))?,
+                    None => OpenMode::default(),
+                };
+                let sensitive = table
+                    .get::<Option<bool>>("sensitive")
+                    .or(Err(mlua::Error::runtime("\"sensitive\" is invalid")))?
+                    .unwrap_or(false);
+                let mode = table
+                    .get::<Option<u32>>("mode")
+                    .or(Err(mlua::Error::runtime("\"mode\" is invalid")))?;
+                let owner = table
+                    .get::<Option<OwnerSpec>>("owner")
+                    .or(Err(mlua::Error::runtime("\"owner\" is invalid")))?;
+                let group = table
+                    .get::<Option<OwnerSpec>>("group")
+                    .or(Err(mlua::Error::runtime("\"group\" is invalid")))?;
+                let atomic = table
+                    .get::<Option<bool>>("atomic")
+                    .or(Err(mlua::Error::runtime("\"atomic\" is invalid")))?
+                    .unwrap_or(false);
+
+                let backup = table
+                    .get::<Option<bool>>("backup")
+                    .or(Err(mlua::Error::runtime("\"backup\" is invalid")))?
+                    .unwrap_or(false);
+
+                if atomic && open_mode != OpenMode::Truncate {
+                    return Err(mlua::Error::runtime(
+                        "\"atomic\" can only be used with open = \"truncate\"",
+                    ));
+                }
+
+                if backup && open_mode == OpenMode::Exclusive {
+                    return Err(mlua::Error::runtime(
+                        "\"backup\" cannot be used with open = \"exclusive\"",
+                    ));
+                }
+
+                Ok(Self {
+                    open_mode,
+                    sensitive,
+                    mode,
+                    owner,
+                    group,
+                    atomic,
+                    backup,
+                })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "WriteOptions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Default)]
 pub struct FileWriteResult {
     pub path: PathBuf,
     pub bytes_written: usize,
+    /// Copy of the previous content made because of [`WriteOptions::backup`]
+    pub backup_path: Option<PathBuf>,
 }
 
 impl IntoLua for FileWriteResult {
@@ -119,6 +254,320 @@ impl IntoLua for FileWriteResult {
 
         result_table.set("path", self.path)?;
         result_table.set("bytes_written", self.bytes_written)?;
+        result_table.set("backup_path", self.backup_path)?;
+
+        let result_table = set_readonly(lua, result_table).map_err(|error| {
+            mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+        })?;
+
+        Ok(mlua::Value::Table(result_table))
+    }
+}
+
+static TEMPORARY_FILE_MODE: u32 = 0o600;
+
+static TEMPORARY_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Path next to `path` that its previous content is copied to, e.g.
+/// `app.conf.20261016T192455Z.bak`.
+fn backup_sibling_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(
+        "{}.{}.bak",
+        file_name,
+        jiff::Timestamp::now().strftime("%Y%m%dT%H%M%SZ")
+    ))
+}
+
+fn temporary_sibling_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(
+        ".{}.arc-{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMPORARY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+#[derive(Debug, Default)]
+pub struct DirectoryCopyResult {
+    pub files_transferred: usize,
+    pub files_unchanged: usize,
+    /// Entries other than files and directories, such as symbolic links
+    pub files_skipped: usize,
+    pub bytes_transferred: u64,
+}
+
+impl IntoLua for DirectoryCopyResult {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        let result_table = lua.create_table()?;
+
+        result_table.set("files_transferred", self.files_transferred)?;
+        result_table.set("files_unchanged", self.files_unchanged)?;
+        result_table.set("files_skipped", self.files_skipped)?;
+        result_table.set("bytes_transferred", self.bytes_transferred)?;
+
+        let result_table = set_readonly(lua, result_table).map_err(|error| {
+            mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+        })?;
+
+        Ok(mlua::Value::Table(result_table))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum OwnerKind {
+    User,
+    Group,
+}
+
+impl OwnerKind {
+    fn name(&self) -> &'static str {
+        match self {
+            OwnerKind::User => "user",
+            OwnerKind::Group => "group",
+        }
+    }
+
+    fn database(&self) -> &'static str {
+        match self {
+            OwnerKind::User => "passwd",
+            OwnerKind::Group => "group",
+        }
+    }
+}
+
+static DEFAULT_GREP_MAX_FILE_SIZE: u64 = 1024 * 1024;
+static BINARY_DETECTION_LENGTH: usize = 8192;
+
+#[derive(Debug, Clone, Copy, strum::Display)]
+pub enum ChecksumAlgorithm {
+    #[strum(serialize = "SHA-256")]
+    Sha256,
+    #[strum(serialize = "MD5")]
+    Md5,
+}
+
+impl ChecksumAlgorithm {
+    /// Hashes everything `reader` yields and returns the digest as lowercase hex.
+    fn digest(self, reader: &mut dyn Read) -> std::io::Result<String> {
+        let digest = match self {
+            ChecksumAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                std::io::copy(reader, &mut hasher)?;
+                hasher.finalize().to_vec()
+            }
+            ChecksumAlgorithm::Md5 => {
+                let mut hasher = Md5::new();
+                std::io::copy(reader, &mut hasher)?;
+                hasher.finalize().to_vec()
+            }
+        };
+
+        Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrepOptions {
+    pub max_file_size: u64,
+    pub ignore: Vec<String>,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_GREP_MAX_FILE_SIZE,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+impl FromLua for GrepOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(table) => {
+                let max_file_size = table
+                    .get::<Option<u64>>("max_file_size")
+                    .or(Err(mlua::Error::runtime("\"max_file_size\" is invalid")))?
+                    .unwrap_or(DEFAULT_GREP_MAX_FILE_SIZE);
+                let ignore = table
+                    .get::<Option<Vec<String>>>("ignore")
+                    .or(Err(mlua::Error::runtime("\"ignore\" is invalid")))?
+                    .unwrap_or_default();
+
+                Ok(Self {
+                    max_file_size,
+                    ignore,
+                })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "GrepOptions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CreateDirectoryOptions {
+    pub mode: Option<u32>,
+}
+
+impl FromLua for CreateDirectoryOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(table) => {
+                let mode = table
+                    .get::<Option<u32>>("mode")
+                    .or(Err(mlua::Error::runtime("\"mode\" is invalid")))?;
+
+                Ok(Self { mode })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "CreateDirectoryOptions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
+static DEFAULT_SEARCH_MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Levels of subdirectories searched, `1` only searches the directory
+    /// itself
+    pub max_depth: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_SEARCH_MAX_DEPTH,
+        }
+    }
+}
+
+impl FromLua for SearchOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(table) => {
+                let max_depth = table
+                    .get::<Option<usize>>("max_depth")
+                    .or(Err(mlua::Error::runtime("\"max_depth\" is invalid")))?
+                    .unwrap_or(DEFAULT_SEARCH_MAX_DEPTH);
+
+                Ok(Self { max_depth })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "SearchOptions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
+/// Whether the components of a relative path match the components of a glob
+/// pattern. `**` matches any number of components, including none, other
+/// components are matched with `*` and `?` wildcards.
+fn glob_matches(pattern: &[&str], path: &[String]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (None, None) => true,
+        (Some((&"**", pattern_rest)), _) => {
+            glob_matches(pattern_rest, path)
+                || path
+                    .split_first()
+                    .is_some_and(|(_, path_rest)| glob_matches(pattern, path_rest))
+        }
+        (Some((component_pattern, pattern_rest)), Some((component, path_rest))) => {
+            matches_pattern(component_pattern, component) && glob_matches(pattern_rest, path_rest)
+        }
+        _ => false,
+    }
+}
+
+/// Modes applied by [`FileSystemOperator::set_permissions_recursive`].
+#[derive(Debug, Clone, Copy)]
+pub struct RecursivePermissions {
+    pub directories: Option<u32>,
+    pub files: Option<u32>,
+}
+
+impl FromLua for RecursivePermissions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Table(table) => {
+                let directories = table
+                    .get::<Option<u32>>("dirs")
+                    .or(Err(mlua::Error::runtime("\"dirs\" is invalid")))?;
+                let files = table
+                    .get::<Option<u32>>("files")
+                    .or(Err(mlua::Error::runtime("\"files\" is invalid")))?;
+
+                if directories.is_none() && files.is_none() {
+                    return Err(mlua::Error::runtime(
+                        "Expected at least one of \"dirs\" and \"files\"",
+                    ));
+                }
+
+                Ok(Self { directories, files })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "RecursivePermissions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GrepLineMatch {
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub struct GrepResult {
+    pub path: PathBuf,
+    pub matches: Vec<GrepLineMatch>,
+}
+
+impl IntoLua for GrepResult {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        let result_table = lua.create_table()?;
+
+        let matches = self
+            .matches
+            .into_iter()
+            .map(|line_match| {
+                let match_table = lua.create_table()?;
+                match_table.set("line", line_match.line)?;
+                match_table.set("text", line_match.text)?;
+
+                set_readonly(lua, match_table).map_err(|error| {
+                    mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+                })
+            })
+            .collect::<mlua::Result<Vec<_>>>()?;
+
+        result_table.set("path", self.path)?;
+        result_table.set("matches", matches)?;
 
         let result_table = set_readonly(lua, result_table).map_err(|error| {
             mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
@@ -179,12 +628,6 @@ impl IntoLua for MetadataResult {
     }
 }
 
-#[derive(thiserror::Error, Debug)]
-#[error("Failed to set execution target")]
-pub enum OperationTargetSetError {
-    Connection(#[from] ConnectionError),
-}
-
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to read {locality} file {path:?}")]
 pub struct FileReadError {
@@ -240,6 +683,15 @@ pub struct CreateDirectoryError {
     source: OperationError,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to set ownership on {locality} path {path:?}")]
+pub struct SetOwnershipError {
+    path: PathBuf,
+    locality: Locality,
+    #[source]
+    source: OperationError,
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to set permissions on {locality} path {path:?}")]
 pub struct SetPermissionsError {
@@ -267,6 +719,34 @@ pub struct DirectoryEntriesError {
     source: OperationError,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to walk {locality} directory {path:?}")]
+pub struct DirectoryWalkError {
+    path: PathBuf,
+    locality: Locality,
+    #[source]
+    source: OperationError,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to search {locality} directory {path:?}")]
+pub struct GrepError {
+    path: PathBuf,
+    locality: Locality,
+    #[source]
+    source: OperationError,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to compute {algorithm} checksum of {locality} file {path:?}")]
+pub struct FileChecksumError {
+    path: PathBuf,
+    locality: Locality,
+    algorithm: ChecksumAlgorithm,
+    #[source]
+    source: OperationError,
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Invalid {locality} file path {path:?}")]
 pub struct FileValidityError {
@@ -298,6 +778,43 @@ pub struct FileStreamError {
     source: OperationError,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub enum DirectoryCopyFailure {
+    Entries(#[from] DirectoryEntriesError),
+    CreateDirectory(#[from] CreateDirectoryError),
+    Metadata(#[from] MetadataError),
+    Checksum(#[from] FileChecksumError),
+    Stream(#[from] FileStreamError),
+    SetPermissions(#[from] SetPermissionsError),
+}
+
+impl FfiError for DirectoryCopyFailure {
+    fn is_user_error(&self) -> bool {
+        match self {
+            Self::Entries(error) => error.is_user_error(),
+            Self::CreateDirectory(error) => error.is_user_error(),
+            Self::Metadata(error) => error.is_user_error(),
+            Self::Checksum(error) => error.is_user_error(),
+            Self::Stream(error) => error.is_user_error(),
+            Self::SetPermissions(error) => error.is_user_error(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Failed to copy {source_locality} directory {source_path:?} to {target_locality} directory {target_path:?}"
+)]
+pub struct DirectoryCopyError {
+    source_path: PathBuf,
+    source_locality: Locality,
+    target_path: PathBuf,
+    target_locality: Locality,
+    #[source]
+    source: Box<DirectoryCopyFailure>,
+}
+
 macro_rules! delegate_ffi_error {
     ($($name:ident),* $(,)?) => {
         $(
@@ -314,211 +831,735 @@ delegate_ffi_error!(
     FileReadError,
     FileWriteError,
     FileStreamError,
+    FileChecksumError,
+    DirectoryCopyError,
     RenameError,
     RemoveFileError,
     RemoveDirectoryError,
     CreateDirectoryError,
     SetPermissionsError,
+    SetOwnershipError,
     MetadataError,
     DirectoryEntriesError,
+    GrepError,
+    DirectoryWalkError,
     FileValidityError,
     DirectoryValidityError,
 );
 
 impl FileSystemOperator {
     pub fn read_file(&self, path: &PathBuf) -> Result<Vec<u8>, FileReadError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => self
-                .progress
-                .transfer(
-                    TransferDirection::Download {
-                        source_file_path: path.to_string_lossy().into_owned(),
-                        target_file_path: None,
-                    },
-                    ssh_client.file_size(path).unwrap_or(0),
-                )
-                .map_err(OperationError::Progress)
-                .and_then(|progress| {
-                    ssh_client
-                        .read_file(path, &progress)
-                        .map_err(OperationError::Remote)
-                }),
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.read_file(path))
-                    .map_err(OperationError::Local)
-            }
-            FileSystemOperatorKind::Host(host_client) => {
-                host_client.read_file(path).map_err(OperationError::Local)
-            }
-        }
-        .map_err(|source| FileReadError {
-            path: path.clone(),
-            locality: self.locality(),
-            source,
-        })
+        self.read_file_operation(path)
+            .map_err(|source| FileReadError {
+                path: path.clone(),
+                locality: self.locality(),
+                source,
+            })
+    }
+
+    fn read_file_operation(&self, path: &PathBuf) -> Result<Vec<u8>, OperationError> {
+        self.progress.traced(
+            || format!("Reading {} {:?}", self.locality(), path),
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => self
+                    .progress
+                    .transfer(
+                        TransferDirection::Download {
+                            source_file_path: path.to_string_lossy().into_owned(),
+                            target_file_path: None,
+                        },
+                        ssh_connection.client().file_size(path).unwrap_or(0),
+                    )
+                    .map_err(OperationError::Progress)
+                    .and_then(|progress| {
+                        ssh_connection
+                            .retrying(|ssh_client| ssh_client.read_file(path, &progress))
+                            .map_err(OperationError::Remote)
+                    }),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.read_file(path))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => {
+                    host_client.read_file(path).map_err(OperationError::Local)
+                }
+            },
+        )
+    }
+
+    /// Appends `content` to the end of the file, creating it if it does not exist.
+    pub fn append_file(
+        &self,
+        path: &PathBuf,
+        content: &[u8],
+    ) -> Result<FileWriteResult, FileWriteError> {
+        let options = WriteOptions {
+            open_mode: OpenMode::Append,
+            ..WriteOptions::default()
+        };
+
+        self.write_file(path, content, &options)
+    }
+
+    /// Computes a checksum over the file's content without loading it into memory.
+    pub fn checksum(
+        &self,
+        path: &Path,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<String, FileChecksumError> {
+        self.progress
+            .traced(
+                || format!("Computing the checksum of {} {:?}", self.locality(), path),
+                || match &self.kind {
+                    FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                        .retrying(|ssh_client| ssh_client.open_reader(path))
+                        .and_then(|mut reader| {
+                            algorithm
+                                .digest(&mut reader)
+                                .map_err(ssh::error::classify_io_error)
+                        })
+                        .map_err(OperationError::Remote),
+                    FileSystemOperatorKind::Local(host_client, home_path) => {
+                        with_local_dir(home_path, || {
+                            host_client.open_reader(path).and_then(|mut reader| {
+                                algorithm
+                                    .digest(&mut reader)
+                                    .map_err(|error| host::error::classify_io_error(error, path))
+                            })
+                        })
+                        .map_err(OperationError::Local)
+                    }
+                    FileSystemOperatorKind::Host(host_client) => host_client
+                        .open_reader(path)
+                        .and_then(|mut reader| {
+                            algorithm
+                                .digest(&mut reader)
+                                .map_err(|error| host::error::classify_io_error(error, path))
+                        })
+                        .map_err(OperationError::Local),
+                },
+            )
+            .map_err(|source| FileChecksumError {
+                path: path.to_path_buf(),
+                locality: self.locality(),
+                algorithm,
+                source,
+            })
     }
 
     pub fn write_file(
         &self,
         path: &PathBuf,
         content: &[u8],
+        options: &WriteOptions,
     ) -> Result<FileWriteResult, FileWriteError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => self
-                .progress
-                .transfer(
-                    TransferDirection::Upload {
-                        source_file_path: None,
-                        target_file_path: path.to_string_lossy().into_owned(),
-                    },
-                    content.len() as u64,
-                )
-                .map_err(OperationError::Progress)
-                .and_then(|progress| {
-                    ssh_client
-                        .write_file(path, content, &progress)
-                        .map_err(OperationError::Remote)
-                }),
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.write_file(path, content))
-                    .map_err(OperationError::Local)
+        // The first evaluation of the entrypoint already made all changes
+        if self.progress.is_replaying_entrypoint() {
+            return Ok(FileWriteResult {
+                path: path.clone(),
+                bytes_written: content.len(),
+                backup_path: None,
+            });
+        }
+
+        let previous_content = match options.open_mode {
+            OpenMode::Truncate | OpenMode::Append if self.progress.shows_diff() => {
+                self.current_content(path)
             }
-            FileSystemOperatorKind::Host(host_client) => host_client
-                .write_file(path, content)
-                .map_err(OperationError::Local),
+            _ => None,
+        };
+
+        let result = if self.progress.is_dry_run() {
+            // A dry run only previews the change
+            Ok(FileWriteResult {
+                path: path.clone(),
+                bytes_written: content.len(),
+                backup_path: None,
+            })
+        } else {
+            self.write_file_with_backup(path, content, options)
         }
         .map_err(|source| FileWriteError {
             path: path.clone(),
             locality: self.locality(),
             source,
-        })
+        })?;
+
+        let current_content = match (options.open_mode, &previous_content) {
+            (OpenMode::Append, Some(previous_content)) => [previous_content, content].concat(),
+            _ => content.to_vec(),
+        };
+        self.progress.diff(
+            path,
+            previous_content.as_deref(),
+            &current_content,
+            options.sensitive,
+        );
+
+        Ok(result)
     }
 
-    pub fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), RenameError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .rename_file(from, to)
-                .map_err(OperationError::Remote),
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.rename_file(from, to))
-                    .map_err(OperationError::Local)
-            }
-            FileSystemOperatorKind::Host(host_client) => host_client
-                .rename_file(from, to)
-                .map_err(OperationError::Local),
+    fn write_file_in_place(
+        &self,
+        path: &PathBuf,
+        content: &[u8],
+        options: &WriteOptions,
+    ) -> Result<FileWriteResult, OperationError> {
+        let ownership = self.resolve_ownership(options.owner.as_ref(), options.group.as_ref())?;
+        let result = self.write_content(path, content, options)?;
+
+        // Changing the owner clears setuid and setgid bits, so the mode is
+        // applied afterwards
+        self.apply_ownership(path, ownership)?;
+        if let Some(mode) = options.mode {
+            self.apply_permissions(path, mode)?;
         }
-        .map_err(|source| RenameError {
-            from: from.clone(),
-            to: to.clone(),
-            locality: self.locality(),
-            source,
-        })
+
+        Ok(result)
     }
 
-    pub fn remove_file(&self, path: &PathBuf) -> Result<(), RemoveFileError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => {
-                ssh_client.remove_file(path).map_err(OperationError::Remote)
-            }
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.remove_file(path))
-                    .map_err(OperationError::Local)
-            }
-            FileSystemOperatorKind::Host(host_client) => {
-                host_client.remove_file(path).map_err(OperationError::Local)
-            }
+    fn write_file_with_backup(
+        &self,
+        path: &PathBuf,
+        content: &[u8],
+        options: &WriteOptions,
+    ) -> Result<FileWriteResult, OperationError> {
+        let backup_path = if options.backup {
+            self.back_up_file(path)?
+        } else {
+            None
+        };
+
+        let result = if options.atomic {
+            self.write_file_atomically(path, content, options)?
+        } else {
+            self.write_file_in_place(path, content, options)?
+        };
+
+        Ok(FileWriteResult {
+            backup_path,
+            ..result
+        })
+    }
+
+    /// Copies the file to a [`backup_sibling_path`] with the same mode.
+    /// Returns `None` if there is no file to back up.
+    fn back_up_file(&self, path: &PathBuf) -> Result<Option<PathBuf>, OperationError> {
+        let Some(metadata) = self
+            .metadata_operation(path)?
+            .filter(|metadata| metadata.r#type == MetadataType::File)
+        else {
+            return Ok(None);
+        };
+
+        let content = self.read_file_operation(path)?;
+        let backup_path = backup_sibling_path(path);
+
+        self.write_content(&backup_path, &content, &WriteOptions::default())?;
+        if let Some(permissions) = metadata.permissions {
+            self.apply_permissions(&backup_path, permissions & 0o7777)?;
         }
-        .map_err(|source| RemoveFileError {
-            path: path.clone(),
-            locality: self.locality(),
-            source,
+
+        Ok(Some(backup_path))
+    }
+
+    /// Writes to a temporary sibling file that is only readable by the
+    /// connected user, applies ownership and mode and then renames it over
+    /// the target, so the target never has partial content or wrong
+    /// permissions.
+    fn write_file_atomically(
+        &self,
+        path: &Path,
+        content: &[u8],
+        options: &WriteOptions,
+    ) -> Result<FileWriteResult, OperationError> {
+        let ownership = self.resolve_ownership(options.owner.as_ref(), options.group.as_ref())?;
+        let mode = match options.mode {
+            Some(mode) => mode,
+            None => self
+                .metadata_operation(path)?
+                .and_then(|metadata| metadata.permissions)
+                .map(|permissions| permissions & 0o7777)
+                .unwrap_or(super::DEFAULT_FILE_MODE),
+        };
+
+        let temporary_path = temporary_sibling_path(path);
+        let temporary_options = WriteOptions {
+            open_mode: OpenMode::Exclusive,
+            mode: Some(TEMPORARY_FILE_MODE),
+            ..WriteOptions::default()
+        };
+
+        let result = self
+            .write_content(&temporary_path, content, &temporary_options)
+            .and_then(|_| self.apply_ownership(&temporary_path, ownership))
+            .and_then(|_| self.apply_permissions(&temporary_path, mode))
+            .and_then(|_| self.replace_file(&temporary_path, path));
+
+        if result.is_err() {
+            _ = self.remove_file_operation(&temporary_path);
+        }
+
+        result.map(|_| FileWriteResult {
+            path: path.to_path_buf(),
+            bytes_written: content.len(),
+            backup_path: None,
         })
     }
 
-    pub fn remove_directory(&self, path: &PathBuf) -> Result<(), RemoveDirectoryError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .remove_directory(path)
-                .map_err(OperationError::Remote),
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.remove_directory(path))
+    fn write_content(
+        &self,
+        path: &PathBuf,
+        content: &[u8],
+        options: &WriteOptions,
+    ) -> Result<FileWriteResult, OperationError> {
+        self.progress.traced(
+            || {
+                format!(
+                    "Writing {} bytes to {} {:?}",
+                    content.len(),
+                    self.locality(),
+                    path
+                )
+            },
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => self
+                    .progress
+                    .transfer(
+                        TransferDirection::Upload {
+                            source_file_path: None,
+                            target_file_path: path.to_string_lossy().into_owned(),
+                        },
+                        content.len() as u64,
+                    )
+                    .map_err(OperationError::Progress)
+                    .and_then(|progress| {
+                        let write = |ssh_client: &SshClient| {
+                            ssh_client.write_file(path, content, options, &progress)
+                        };
+
+                        // Appending or creating exclusively again after a partial
+                        // write would not leave the same file
+                        match options.open_mode {
+                            OpenMode::Truncate => ssh_connection.retrying(write),
+                            OpenMode::Append | OpenMode::Exclusive => {
+                                write(&ssh_connection.client())
+                            }
+                        }
+                        .map_err(OperationError::Remote)
+                    }),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || {
+                        local_client.write_file(path, content, options)
+                    })
                     .map_err(OperationError::Local)
-            }
-            FileSystemOperatorKind::Host(host_client) => host_client
-                .remove_directory(path)
-                .map_err(OperationError::Local),
+                }
+                FileSystemOperatorKind::Host(host_client) => host_client
+                    .write_file(path, content, options)
+                    .map_err(OperationError::Local),
+            },
+        )
+    }
+
+    fn apply_permissions(&self, path: &Path, mode: u32) -> Result<(), OperationError> {
+        self.progress.traced(
+            || {
+                format!(
+                    "Setting the permissions of {} {:?} to {:o}",
+                    self.locality(),
+                    path,
+                    mode
+                )
+            },
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.set_permissions(path, mode))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.set_permissions(path, mode))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => host_client
+                    .set_permissions(path, mode)
+                    .map_err(OperationError::Local),
+            },
+        )
+    }
+
+    /// Resolves the requested owner and group to ids before anything is
+    /// written, so unknown names fail without touching the file.
+    fn resolve_ownership(
+        &self,
+        owner: Option<&OwnerSpec>,
+        group: Option<&OwnerSpec>,
+    ) -> Result<(Option<u32>, Option<u32>), OperationError> {
+        let uid = owner
+            .map(|owner| self.resolve_owner_id(owner, OwnerKind::User))
+            .transpose()?;
+        let gid = group
+            .map(|group| self.resolve_owner_id(group, OwnerKind::Group))
+            .transpose()?;
+
+        Ok((uid, gid))
+    }
+
+    fn apply_ownership(
+        &self,
+        path: &Path,
+        (uid, gid): (Option<u32>, Option<u32>),
+    ) -> Result<(), OperationError> {
+        if uid.is_none() && gid.is_none() {
+            return Ok(());
         }
-        .map_err(|source| RemoveDirectoryError {
-            path: path.clone(),
-            locality: self.locality(),
-            source,
-        })
+
+        self.progress.traced(
+            || {
+                format!(
+                    "Setting the ownership of {} {:?} to {:?}:{:?}",
+                    self.locality(),
+                    path,
+                    uid,
+                    gid
+                )
+            },
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.set_ownership(path, uid, gid))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.set_ownership(path, uid, gid))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => host_client
+                    .set_ownership(path, uid, gid)
+                    .map_err(OperationError::Local),
+            },
+        )
     }
 
-    pub fn create_directory(&self, path: &Path) -> Result<(), CreateDirectoryError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .create_directory(path)
+    /// Resolves a user or group name to its id with `getent` on the target,
+    /// so names from any configured name service are found.
+    fn resolve_owner_id(&self, spec: &OwnerSpec, kind: OwnerKind) -> Result<u32, OperationError> {
+        let name = match spec {
+            OwnerSpec::Id(id) => return Ok(*id),
+            OwnerSpec::Name(name) => name,
+        };
+
+        let entry = match &self.kind {
+            FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                .retrying(|ssh_client| ssh_client.getent(kind.database(), name))
                 .map_err(OperationError::Remote),
             FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.create_directory(path))
+                with_local_dir(home_path, || local_client.getent(kind.database(), name))
                     .map_err(OperationError::Local)
             }
             FileSystemOperatorKind::Host(host_client) => host_client
-                .create_directory(path)
+                .getent(kind.database(), name)
                 .map_err(OperationError::Local),
+        }?;
+
+        entry
+            .as_deref()
+            .and_then(|entry| entry.lines().next())
+            .and_then(|line| line.split(':').nth(2))
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| {
+                OperationError::UnknownOwner(UnknownOwnerError {
+                    kind: kind.name(),
+                    name: name.clone(),
+                })
+            })
+    }
+
+    fn replace_file(&self, from: &Path, to: &Path) -> Result<(), OperationError> {
+        self.progress.traced(
+            || format!("Moving {} {:?} to {:?}", self.locality(), from, to),
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.replace_file(from, to))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || {
+                        local_client.rename_file(&from.to_path_buf(), &to.to_path_buf())
+                    })
+                    .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => host_client
+                    .rename_file(&from.to_path_buf(), &to.to_path_buf())
+                    .map_err(OperationError::Local),
+            },
+        )
+    }
+
+    fn remove_file_operation(&self, path: &PathBuf) -> Result<(), OperationError> {
+        self.progress.traced(
+            || format!("Removing {} {:?}", self.locality(), path),
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.remove_file(path))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.remove_file(path))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => {
+                    host_client.remove_file(path).map_err(OperationError::Local)
+                }
+            },
+        )
+    }
+
+    fn metadata_operation(&self, path: &Path) -> Result<Option<MetadataResult>, OperationError> {
+        self.progress.traced(
+            || format!("Reading the metadata of {} {:?}", self.locality(), path),
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.metadata(path))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.metadata(path))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => {
+                    host_client.metadata(path).map_err(OperationError::Local)
+                }
+            },
+        )
+    }
+
+    /// Reads the content of an existing file for diffing, ignoring failures.
+    fn current_content(&self, path: &PathBuf) -> Option<Vec<u8>> {
+        self.metadata(path)
+            .ok()
+            .flatten()
+            .filter(|metadata| metadata.r#type == MetadataType::File)
+            .and_then(|_| self.read_file(path).ok())
+    }
+
+    pub fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), RenameError> {
+        if self
+            .progress
+            .skips_change(|| format!("rename {:?} to {:?}", from, to))
+        {
+            return Ok(());
         }
-        .map_err(|source| CreateDirectoryError {
-            path: path.to_path_buf(),
-            locality: self.locality(),
-            source,
-        })
+
+        self.progress
+            .traced(
+                || format!("Renaming {} {:?} to {:?}", self.locality(), from, to),
+                || match &self.kind {
+                    FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                        .retrying(|ssh_client| ssh_client.rename_file(from, to))
+                        .map_err(OperationError::Remote),
+                    FileSystemOperatorKind::Local(local_client, home_path) => {
+                        with_local_dir(home_path, || local_client.rename_file(from, to))
+                            .map_err(OperationError::Local)
+                    }
+                    FileSystemOperatorKind::Host(host_client) => host_client
+                        .rename_file(from, to)
+                        .map_err(OperationError::Local),
+                },
+            )
+            .map_err(|source| RenameError {
+                from: from.clone(),
+                to: to.clone(),
+                locality: self.locality(),
+                source,
+            })
+    }
+
+    pub fn remove_file(&self, path: &PathBuf) -> Result<(), RemoveFileError> {
+        if self
+            .progress
+            .skips_change(|| format!("remove file {:?}", path))
+        {
+            return Ok(());
+        }
+
+        self.remove_file_operation(path)
+            .map_err(|source| RemoveFileError {
+                path: path.clone(),
+                locality: self.locality(),
+                source,
+            })
+    }
+
+    pub fn remove_directory(&self, path: &PathBuf) -> Result<(), RemoveDirectoryError> {
+        if self
+            .progress
+            .skips_change(|| format!("remove directory {:?}", path))
+        {
+            return Ok(());
+        }
+
+        self.progress
+            .traced(
+                || format!("Removing {} directory {:?}", self.locality(), path),
+                || match &self.kind {
+                    FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                        .retrying(|ssh_client| ssh_client.remove_directory(path))
+                        .map_err(OperationError::Remote),
+                    FileSystemOperatorKind::Local(local_client, home_path) => {
+                        with_local_dir(home_path, || local_client.remove_directory(path))
+                            .map_err(OperationError::Local)
+                    }
+                    FileSystemOperatorKind::Host(host_client) => host_client
+                        .remove_directory(path)
+                        .map_err(OperationError::Local),
+                },
+            )
+            .map_err(|source| RemoveDirectoryError {
+                path: path.clone(),
+                locality: self.locality(),
+                source,
+            })
+    }
+
+    pub fn create_directory(&self, path: &Path) -> Result<(), CreateDirectoryError> {
+        self.create_directory_inner(path, None)
+    }
+
+    /// Creates the directory like [`Self::create_directory`] and sets `mode`
+    /// on it if it did not exist. Ancestors are created with the default mode.
+    pub fn create_directory_with_mode(
+        &self,
+        path: &Path,
+        mode: u32,
+    ) -> Result<(), CreateDirectoryError> {
+        self.create_directory_inner(path, Some(mode))
+    }
+
+    fn create_directory_inner(
+        &self,
+        path: &Path,
+        mode: Option<u32>,
+    ) -> Result<(), CreateDirectoryError> {
+        if self
+            .progress
+            .skips_change(|| format!("create directory {:?}", path))
+        {
+            return Ok(());
+        }
+
+        self.progress
+            .traced(
+                || format!("Creating {} directory {:?}", self.locality(), path),
+                || match &self.kind {
+                    FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                        .retrying(|ssh_client| ssh_client.create_directory(path, mode))
+                        .map_err(OperationError::Remote),
+                    FileSystemOperatorKind::Local(local_client, home_path) => {
+                        with_local_dir(home_path, || local_client.create_directory(path, mode))
+                            .map_err(OperationError::Local)
+                    }
+                    FileSystemOperatorKind::Host(host_client) => host_client
+                        .create_directory(path, mode)
+                        .map_err(OperationError::Local),
+                },
+            )
+            .map_err(|source| CreateDirectoryError {
+                path: path.to_path_buf(),
+                locality: self.locality(),
+                source,
+            })
     }
 
     pub fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), SetPermissionsError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .set_permissions(path, mode)
-                .map_err(OperationError::Remote),
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.set_permissions(path, mode))
-                    .map_err(OperationError::Local)
-            }
-            FileSystemOperatorKind::Host(host_client) => host_client
-                .set_permissions(path, mode)
-                .map_err(OperationError::Local),
+        if self
+            .progress
+            .skips_change(|| format!("set permissions of {:?} to {:o}", path, mode))
+        {
+            return Ok(());
         }
-        .map_err(|source| SetPermissionsError {
-            path: path.to_path_buf(),
-            locality: self.locality(),
-            source,
-        })
+
+        self.apply_permissions(path, mode)
+            .map_err(|source| SetPermissionsError {
+                path: path.to_path_buf(),
+                locality: self.locality(),
+                source,
+            })
     }
 
-    pub fn metadata(&self, path: &Path) -> Result<Option<MetadataResult>, MetadataError> {
-        match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => {
-                ssh_client.metadata(path).map_err(OperationError::Remote)
-            }
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.metadata(path))
-                    .map_err(OperationError::Local)
+    /// Applies the directory mode to `path` and all directories below it and
+    /// the file mode to all files below it. Symbolic links are neither
+    /// followed nor changed, and the tree is walked iteratively, so deep trees
+    /// and link loops are no problem.
+    pub fn set_permissions_recursive(
+        &self,
+        path: &Path,
+        modes: RecursivePermissions,
+    ) -> Result<(), SetPermissionsError> {
+        if self
+            .progress
+            .skips_change(|| format!("set permissions below {:?}", path))
+        {
+            return Ok(());
+        }
+
+        let mut pending_directories = vec![path.to_path_buf()];
+
+        while let Some(directory_path) = pending_directories.pop() {
+            if let Some(mode) = modes.directories {
+                self.set_permissions(&directory_path, mode)?;
             }
-            FileSystemOperatorKind::Host(host_client) => {
-                host_client.metadata(path).map_err(OperationError::Local)
+
+            let entries =
+                self.directory_entries(&directory_path)
+                    .map_err(|source| SetPermissionsError {
+                        path: directory_path.clone(),
+                        locality: self.locality(),
+                        source,
+                    })?;
+
+            for entry in entries {
+                match (entry.r#type, modes.files) {
+                    (MetadataType::Directory, _) => pending_directories.push(entry.path),
+                    (MetadataType::File, Some(mode)) => self.set_permissions(&entry.path, mode)?,
+                    (MetadataType::File, None) | (MetadataType::Unknown, _) => {}
+                }
             }
         }
-        .map_err(|source| MetadataError {
-            path: path.to_path_buf(),
-            locality: self.locality(),
-            source,
-        })
+
+        Ok(())
+    }
+
+    /// Changes the owner and/or group of a path. Names are resolved using the
+    /// target's user and group databases; ids are used as is.
+    pub fn set_ownership(
+        &self,
+        path: &Path,
+        owner: Option<&OwnerSpec>,
+        group: Option<&OwnerSpec>,
+    ) -> Result<(), SetOwnershipError> {
+        if self
+            .progress
+            .skips_change(|| format!("change ownership of {:?}", path))
+        {
+            return Ok(());
+        }
+
+        self.resolve_ownership(owner, group)
+            .and_then(|ownership| self.apply_ownership(path, ownership))
+            .map_err(|source| SetOwnershipError {
+                path: path.to_path_buf(),
+                locality: self.locality(),
+                source,
+            })
+    }
+
+    pub fn metadata(&self, path: &Path) -> Result<Option<MetadataResult>, MetadataError> {
+        self.metadata_operation(path)
+            .map_err(|source| MetadataError {
+                path: path.to_path_buf(),
+                locality: self.locality(),
+                source,
+            })
     }
 
     pub fn file(&self, path: &Path) -> Result<File, FileValidityError> {
         match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .check_file_validity(path)
+            FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                .retrying(|ssh_client| ssh_client.check_file_validity(path))
                 .map_err(OperationError::Remote),
             FileSystemOperatorKind::Local(local_client, home_path) => {
                 with_local_dir(home_path, || local_client.check_file_validity(path))
@@ -540,50 +1581,273 @@ impl FileSystemOperator {
         })
     }
 
+    fn directory_entries(&self, path: &Path) -> Result<Vec<MetadataResult>, OperationError> {
+        self.progress.traced(
+            || format!("Listing {} directory {:?}", self.locality(), path),
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.list_directory(path))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.list_directory(path))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => host_client
+                    .list_directory(path)
+                    .map_err(OperationError::Local),
+            },
+        )
+    }
+
     pub fn list_directory(
         &self,
         path: &Path,
     ) -> Result<Vec<FileSystemEntry>, DirectoryEntriesError> {
-        let directory_entries = match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .list_directory(path)
-                .map_err(OperationError::Remote),
-            FileSystemOperatorKind::Local(local_client, home_path) => {
-                with_local_dir(home_path, || local_client.list_directory(path))
-                    .map_err(OperationError::Local)
+        let directory_entries =
+            self.directory_entries(path)
+                .map_err(|source| DirectoryEntriesError {
+                    path: path.to_path_buf(),
+                    locality: self.locality(),
+                    source,
+                })?;
+
+        let result = directory_entries
+            .iter()
+            .filter_map(|entry| self.entry(entry))
+            .collect();
+
+        Ok(result)
+    }
+
+    /// The file or directory `metadata` describes, `None` for other types.
+    pub fn entry(&self, metadata: &MetadataResult) -> Option<FileSystemEntry> {
+        match metadata.r#type {
+            MetadataType::File => Some(FileSystemEntry::File(File {
+                path: metadata.path.clone(),
+                file_system_operator: self.clone(),
+            })),
+            MetadataType::Directory => Some(FileSystemEntry::Directory(Directory {
+                path: metadata.path.clone(),
+                file_system_operator: self.clone(),
+            })),
+            MetadataType::Unknown => None,
+        }
+    }
+
+    /// Metadata of the files and directories below `path`, sorted by path.
+    /// Subdirectories are entered up to `max_depth` levels deep, `1` only
+    /// lists `path` itself. Symbolic links are neither listed nor followed,
+    /// so links cannot cause cycles. Subdirectories that cannot be read are
+    /// skipped with a warning.
+    pub fn walk_directory(
+        &self,
+        path: &Path,
+        max_depth: usize,
+    ) -> Result<Vec<MetadataResult>, DirectoryWalkError> {
+        let mut results = Vec::new();
+        let mut pending_directories = vec![(path.to_path_buf(), 1)];
+
+        while let Some((directory_path, depth)) = pending_directories.pop() {
+            let entries = match self.directory_entries(&directory_path) {
+                Ok(entries) => entries,
+                Err(source) if directory_path != path && source.is_user_error() => {
+                    self.warn_search_skipped(&directory_path, &source);
+                    continue;
+                }
+                Err(source) => {
+                    return Err(DirectoryWalkError {
+                        path: directory_path,
+                        locality: self.locality(),
+                        source,
+                    });
+                }
+            };
+
+            for entry in entries {
+                match entry.r#type {
+                    MetadataType::Directory => {
+                        if depth < max_depth {
+                            pending_directories.push((entry.path.clone(), depth + 1));
+                        }
+                        results.push(entry);
+                    }
+                    MetadataType::File => results.push(entry),
+                    MetadataType::Unknown => {}
+                }
             }
-            FileSystemOperatorKind::Host(host_client) => host_client
-                .list_directory(path)
-                .map_err(OperationError::Local),
         }
-        .map_err(|source| DirectoryEntriesError {
-            path: path.to_path_buf(),
-            locality: self.locality(),
-            source,
-        })?;
 
-        let result = directory_entries
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(results)
+    }
+
+    /// The files and directories below `path` whose path relative to `path`
+    /// matches `pattern`, see [`glob_matches`]. Patterns without `**` only
+    /// walk as deep as they have components.
+    pub fn glob(
+        &self,
+        path: &Path,
+        pattern: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<FileSystemEntry>, DirectoryWalkError> {
+        let pattern: Vec<&str> = pattern
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect();
+        let max_depth = match pattern.contains(&"**") {
+            true => options.max_depth,
+            false => pattern.len().min(options.max_depth),
+        };
+
+        let entries = self
+            .walk_directory(path, max_depth)?
             .into_iter()
-            .filter_map(|entry| match entry.r#type {
-                MetadataType::File => Some(FileSystemEntry::File(File {
-                    path: entry.path,
-                    file_system_operator: self.clone(),
-                })),
-                MetadataType::Directory => Some(FileSystemEntry::Directory(Directory {
-                    path: entry.path,
-                    file_system_operator: self.clone(),
-                })),
-                MetadataType::Unknown => None,
+            .filter(|entry| {
+                let relative_path: Vec<String> = entry
+                    .path
+                    .strip_prefix(path)
+                    .unwrap_or(&entry.path)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+
+                glob_matches(&pattern, &relative_path)
             })
+            .filter_map(|entry| self.entry(&entry))
             .collect();
 
-        Ok(result)
+        Ok(entries)
+    }
+
+    /// Searches the files below `path` for lines matching `pattern`.
+    ///
+    /// Entries whose name is in the ignore list, files larger than the size
+    /// limit and binary files are skipped. Symbolic links are not followed.
+    /// Files and subdirectories that cannot be read, e.g. due to missing
+    /// permissions, are skipped with a warning.
+    pub fn grep(
+        &self,
+        path: &Path,
+        pattern: &Regex,
+        options: &GrepOptions,
+    ) -> Result<Vec<GrepResult>, GrepError> {
+        let mut results = Vec::new();
+        let mut pending_directories = vec![path.to_path_buf()];
+
+        while let Some(directory_path) = pending_directories.pop() {
+            let mut entries = match self.directory_entries(&directory_path) {
+                Ok(entries) => entries,
+                Err(source) if directory_path != path && source.is_user_error() => {
+                    self.warn_search_skipped(&directory_path, &source);
+                    continue;
+                }
+                Err(source) => {
+                    return Err(GrepError {
+                        path: directory_path,
+                        locality: self.locality(),
+                        source,
+                    });
+                }
+            };
+            entries.sort_by(|a, b| b.path.cmp(&a.path));
+
+            for entry in entries {
+                let is_ignored = entry.path.file_name().is_some_and(|file_name| {
+                    options
+                        .ignore
+                        .iter()
+                        .any(|ignored| file_name.to_string_lossy() == ignored.as_str())
+                });
+
+                if is_ignored {
+                    continue;
+                }
+
+                match entry.r#type {
+                    MetadataType::Directory => pending_directories.push(entry.path),
+                    MetadataType::File if entry.size.unwrap_or(0) <= options.max_file_size => {
+                        let content = match self.read_without_progress(&entry.path) {
+                            Ok(content) => content,
+                            Err(source) if source.is_user_error() => {
+                                self.warn_search_skipped(&entry.path, &source);
+                                continue;
+                            }
+                            Err(source) => {
+                                return Err(GrepError {
+                                    path: entry.path,
+                                    locality: self.locality(),
+                                    source,
+                                });
+                            }
+                        };
+
+                        let detection_length = content.len().min(BINARY_DETECTION_LENGTH);
+                        if content[..detection_length].contains(&0) {
+                            continue;
+                        }
+
+                        let matches: Vec<GrepLineMatch> = String::from_utf8_lossy(&content)
+                            .lines()
+                            .enumerate()
+                            .filter(|(_, line)| pattern.is_match(line))
+                            .map(|(index, line)| GrepLineMatch {
+                                line: index + 1,
+                                text: line.to_string(),
+                            })
+                            .collect();
+
+                        if !matches.is_empty() {
+                            results.push(GrepResult {
+                                path: entry.path,
+                                matches,
+                            });
+                        }
+                    }
+                    MetadataType::File | MetadataType::Unknown => {}
+                }
+            }
+        }
+
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(results)
+    }
+
+    fn warn_search_skipped(&self, path: &Path, error: &OperationError) {
+        self.progress.log(
+            LogLevel::Warn,
+            &format!(
+                "Skipped unreadable {} path {:?} while searching: {}",
+                self.locality(),
+                path,
+                error
+            ),
+        );
+    }
+
+    fn read_without_progress(&self, path: &PathBuf) -> Result<Vec<u8>, OperationError> {
+        self.progress.traced(
+            || format!("Reading {} {:?}", self.locality(), path),
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.read_file(path, &TransferProgress::noop()))
+                    .map_err(OperationError::Remote),
+                FileSystemOperatorKind::Local(local_client, home_path) => {
+                    with_local_dir(home_path, || local_client.read_file(path))
+                        .map_err(OperationError::Local)
+                }
+                FileSystemOperatorKind::Host(host_client) => {
+                    host_client.read_file(path).map_err(OperationError::Local)
+                }
+            },
+        )
     }
 
     pub fn directory(&self, path: &Path) -> Result<Directory, DirectoryValidityError> {
         match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .check_directory_validity(path)
+            FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                .retrying(|ssh_client| ssh_client.check_directory_validity(path))
                 .map_err(OperationError::Remote),
             FileSystemOperatorKind::Local(local_client, home_path) => {
                 with_local_dir(home_path, || local_client.check_directory_validity(path))
@@ -614,8 +1878,8 @@ impl FileSystemOperator {
         };
 
         match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .check_directory_validity(parent_path)
+            FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                .retrying(|ssh_client| ssh_client.check_directory_validity(parent_path))
                 .map_err(OperationError::Remote),
             FileSystemOperatorKind::Local(local_client, home_path) => {
                 with_local_dir(home_path, || {
@@ -653,7 +1917,9 @@ impl FileSystemOperator {
 
     fn get_file_size(&self, path: &PathBuf) -> u64 {
         match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client.file_size(path).unwrap_or(0),
+            FileSystemOperatorKind::Ssh(ssh_connection) => {
+                ssh_connection.client().file_size(path).unwrap_or(0)
+            }
             FileSystemOperatorKind::Local(_, home_path) => with_local_dir(home_path, || {
                 Ok::<u64, std::convert::Infallible>(
                     std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
@@ -666,12 +1932,138 @@ impl FileSystemOperator {
         }
     }
 
+    /// Copies the directory tree at `source_path` into `target_path` on
+    /// `target`, creating directories as needed and keeping permissions.
+    ///
+    /// Files whose size and SHA-256 checksum already match are left as they are.
+    pub fn copy_directory_to(
+        &self,
+        source_path: &Path,
+        target: &FileSystemOperator,
+        target_path: &Path,
+    ) -> Result<DirectoryCopyResult, DirectoryCopyError> {
+        if self
+            .progress
+            .skips_change(|| format!("copy directory {:?} to {:?}", source_path, target_path))
+        {
+            return Ok(DirectoryCopyResult::default());
+        }
+
+        self.copy_tree(source_path, target, target_path)
+            .map_err(|source| DirectoryCopyError {
+                source_path: source_path.to_path_buf(),
+                source_locality: self.locality(),
+                target_path: target_path.to_path_buf(),
+                target_locality: target.locality(),
+                source: Box::new(source),
+            })
+    }
+
+    fn copy_tree(
+        &self,
+        source_path: &Path,
+        target: &FileSystemOperator,
+        target_path: &Path,
+    ) -> Result<DirectoryCopyResult, DirectoryCopyFailure> {
+        let mut result = DirectoryCopyResult::default();
+
+        target.create_directory(target_path)?;
+
+        let mut pending_directories = vec![(source_path.to_path_buf(), target_path.to_path_buf())];
+
+        while let Some((source_directory, target_directory)) = pending_directories.pop() {
+            let mut entries = self
+                .directory_entries(&source_directory)
+                .map_err(|source| DirectoryEntriesError {
+                    path: source_directory.clone(),
+                    locality: self.locality(),
+                    source,
+                })?;
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+            for entry in entries {
+                let Some(file_name) = entry.path.file_name() else {
+                    continue;
+                };
+                let target_entry_path = target_directory.join(file_name);
+
+                match entry.r#type {
+                    MetadataType::Directory => {
+                        target.create_directory(&target_entry_path)?;
+                        pending_directories.push((entry.path.clone(), target_entry_path.clone()));
+                    }
+                    MetadataType::File => {
+                        if self.is_unchanged(&entry, target, &target_entry_path)? {
+                            result.files_unchanged += 1;
+                        } else {
+                            let write_result =
+                                self.stream_to_other(&entry.path, target, &target_entry_path)?;
+
+                            result.files_transferred += 1;
+                            result.bytes_transferred += write_result.bytes_written as u64;
+                        }
+                    }
+                    MetadataType::Unknown => {
+                        result.files_skipped += 1;
+                        continue;
+                    }
+                }
+
+                // SFTP reports the file type along with the mode
+                if let Some(permissions) = entry.permissions {
+                    target.set_permissions(&target_entry_path, permissions & 0o7777)?;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Checks whether the target file has the same content as the source entry.
+    fn is_unchanged(
+        &self,
+        source_entry: &MetadataResult,
+        target: &FileSystemOperator,
+        target_path: &Path,
+    ) -> Result<bool, DirectoryCopyFailure> {
+        let Some(target_metadata) = target.metadata(target_path)? else {
+            return Ok(false);
+        };
+
+        if target_metadata.r#type != MetadataType::File || target_metadata.size != source_entry.size
+        {
+            return Ok(false);
+        }
+
+        let source_checksum = self.checksum(&source_entry.path, ChecksumAlgorithm::Sha256)?;
+        let target_checksum = target.checksum(target_path, ChecksumAlgorithm::Sha256)?;
+
+        Ok(source_checksum == target_checksum)
+    }
+
+    /// Returns an operator for the machine arc runs on, sharing this
+    /// operator's progress output.
+    pub fn host(&self) -> FileSystemOperator {
+        Self::new_host(self.progress.clone())
+    }
+
     pub fn stream_to_other(
         &self,
         source_path: &PathBuf,
         target: &FileSystemOperator,
         target_path: &Path,
     ) -> Result<FileWriteResult, FileStreamError> {
+        if self
+            .progress
+            .skips_change(|| format!("copy file {:?} to {:?}", source_path, target_path))
+        {
+            return Ok(FileWriteResult {
+                path: target_path.to_path_buf(),
+                bytes_written: self.get_file_size(source_path) as usize,
+                backup_path: None,
+            });
+        }
+
         let source_size = self.get_file_size(source_path);
 
         let source_file_path = source_path.to_string_lossy().into_owned();
@@ -718,27 +2110,38 @@ impl FileSystemOperator {
             source: OperationError::Progress(source),
         })?;
 
-        let result = match &self.kind {
-            FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
-                .open_reader(source_path)
-                .map_err(OperationError::Remote)
-                .and_then(|mut reader| {
-                    write_reader_to_writer(&mut reader, target, target_path, &progress)
-                }),
-            FileSystemOperatorKind::Local(host_client, home_path) => {
-                with_local_dir(home_path, || host_client.open_reader(source_path))
+        let result = self.progress.traced(
+            || {
+                format!(
+                    "Copying {} {:?} to {} {:?}",
+                    self.locality(),
+                    source_path,
+                    target.locality(),
+                    target_path
+                )
+            },
+            || match &self.kind {
+                FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+                    .retrying(|ssh_client| ssh_client.open_reader(source_path))
+                    .map_err(OperationError::Remote)
+                    .and_then(|mut reader| {
+                        write_reader_to_writer(&mut reader, target, target_path, &progress)
+                    }),
+                FileSystemOperatorKind::Local(host_client, home_path) => {
+                    with_local_dir(home_path, || host_client.open_reader(source_path))
+                        .map_err(OperationError::Local)
+                        .and_then(|mut reader| {
+                            write_reader_to_writer(&mut reader, target, target_path, &progress)
+                        })
+                }
+                FileSystemOperatorKind::Host(host_client) => host_client
+                    .open_reader(source_path)
                     .map_err(OperationError::Local)
                     .and_then(|mut reader| {
                         write_reader_to_writer(&mut reader, target, target_path, &progress)
-                    })
-            }
-            FileSystemOperatorKind::Host(host_client) => host_client
-                .open_reader(source_path)
-                .map_err(OperationError::Local)
-                .and_then(|mut reader| {
-                    write_reader_to_writer(&mut reader, target, target_path, &progress)
-                }),
-        };
+                    }),
+            },
+        );
 
         progress.finish();
 
@@ -759,7 +2162,9 @@ fn write_reader_to_writer(
     progress: &TransferProgress,
 ) -> Result<FileWriteResult, OperationError> {
     match &target.kind {
-        FileSystemOperatorKind::Ssh(ssh_client) => ssh_client
+        // Not repeated, as the reader is already partly consumed
+        FileSystemOperatorKind::Ssh(ssh_connection) => ssh_connection
+            .client()
             .write_from_reader(target_path, reader, progress)
             .map_err(OperationError::Remote),
         FileSystemOperatorKind::Local(host_client, home_path) => with_local_dir(home_path, || {
@@ -771,3 +2176,178 @@ fn write_reader_to_writer(
             .map_err(OperationError::Local),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::fs::MetadataExt,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+    };
+
+    use super::*;
+    use crate::logger::Logger;
+
+    #[test]
+    fn atomic_write_never_exposes_wrong_mode_or_content() {
+        let directory =
+            std::env::temp_dir().join(format!("arc-operator-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("target");
+
+        let operator =
+            FileSystemOperator::new_host(ProgressContext::new(Logger::new(), false, false));
+        let uid = std::fs::metadata(&directory).unwrap().uid();
+        let options = WriteOptions {
+            mode: Some(0o4755),
+            owner: Some(OwnerSpec::Id(uid)),
+            atomic: true,
+            ..WriteOptions::default()
+        };
+        let contents = [vec![b'a'; 256 * 1024], vec![b'b'; 128 * 1024]];
+
+        operator.write_file(&path, &contents[0], &options).unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let observer = {
+            let path = path.clone();
+            let contents = contents.clone();
+            let done = done.clone();
+
+            std::thread::spawn(move || {
+                let mut observations = 0;
+
+                while !done.load(Ordering::Relaxed) {
+                    // Mode and content are taken from the same open file, which
+                    // is the one the path pointed to at the time of opening
+                    let mut file = std::fs::File::open(&path).unwrap();
+                    let mode = file.metadata().unwrap().mode() & 0o7777;
+                    let mut content = Vec::new();
+                    file.read_to_end(&mut content).unwrap();
+
+                    assert_eq!(mode, 0o4755);
+                    assert!(contents.contains(&content));
+                    observations += 1;
+                }
+
+                observations
+            })
+        };
+
+        for index in 0..200 {
+            operator
+                .write_file(&path, &contents[index % 2], &options)
+                .unwrap();
+        }
+
+        done.store(true, Ordering::Relaxed);
+        let observations = observer.join();
+        let final_mode = std::fs::metadata(&path).unwrap().mode() & 0o7777;
+        let leftovers = std::fs::read_dir(&directory).unwrap().count();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(observations.unwrap() > 0);
+        assert_eq!(final_mode, 0o4755);
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn multi_megabyte_files_are_streamed_completely() {
+        let directory =
+            std::env::temp_dir().join(format!("arc-operator-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let source = directory.join("source");
+        let target = directory.join("target");
+
+        let content: Vec<u8> = (0..5 * 1024 * 1024)
+            .map(|index| (index % 251) as u8)
+            .collect();
+        std::fs::write(&source, &content).unwrap();
+
+        let operator =
+            FileSystemOperator::new_host(ProgressContext::new(Logger::new(), false, false));
+        let result = operator
+            .stream_to_other(&source, &operator.host(), &target)
+            .unwrap();
+        let streamed = std::fs::read(&target).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(result.bytes_written, content.len());
+        assert!(streamed == content);
+    }
+
+    #[test]
+    fn glob_matches_nested_paths_without_following_links() {
+        let directory =
+            std::env::temp_dir().join(format!("arc-operator-glob-{}", std::process::id()));
+        for file in ["a.conf", "b.txt", "site/c.conf", "site/deep/d.conf"] {
+            let path = directory.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        std::os::unix::fs::symlink(&directory, directory.join("site/loop")).unwrap();
+
+        let operator =
+            FileSystemOperator::new_host(ProgressContext::new(Logger::new(), false, false));
+        let glob = |pattern: &str, max_depth: usize| -> Vec<PathBuf> {
+            operator
+                .glob(&directory, pattern, &SearchOptions { max_depth })
+                .unwrap()
+                .into_iter()
+                .map(|entry| match entry {
+                    FileSystemEntry::File(file) => file.path,
+                    FileSystemEntry::Directory(directory) => directory.path,
+                })
+                .collect()
+        };
+        let paths = |files: &[&str]| -> Vec<PathBuf> {
+            files.iter().map(|file| directory.join(file)).collect()
+        };
+
+        let top_level = glob("*.conf", 32);
+        let nested = glob("**/*.conf", 32);
+        let limited = glob("**/*.conf", 2);
+        let single_character = glob("site/?.conf", 32);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(top_level, paths(&["a.conf"]));
+        assert_eq!(
+            nested,
+            paths(&["a.conf", "site/c.conf", "site/deep/d.conf"])
+        );
+        assert_eq!(limited, paths(&["a.conf", "site/c.conf"]));
+        assert_eq!(single_character, paths(&["site/c.conf"]));
+    }
+
+    #[test]
+    fn dry_run_reads_but_does_not_change_files() {
+        let directory =
+            std::env::temp_dir().join(format!("arc-operator-dry-run-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("file");
+        std::fs::write(&path, "content").unwrap();
+
+        let operator =
+            FileSystemOperator::new_host(ProgressContext::new(Logger::new(), false, true));
+        operator
+            .write_file(&path, b"changed", &WriteOptions::default())
+            .unwrap();
+        operator.set_permissions(&path, 0o600).unwrap();
+        operator.create_directory(&directory.join("new")).unwrap();
+        operator.rename(&path, &directory.join("renamed")).unwrap();
+        let read = operator.read_file(&path).unwrap();
+        operator.remove_file(&path).unwrap();
+
+        let content = std::fs::read(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().mode() & 0o7777;
+        let entries = std::fs::read_dir(&directory).unwrap().count();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(read, b"content");
+        assert_eq!(content, b"content");
+        assert_ne!(mode, 0o600);
+        assert_eq!(entries, 1);
+    }
+}
@@ -0,0 +1,304 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use super::{
+    error::FfiError,
+    executor::CommandResult,
+    host::{CommandError, HostShell},
+    ssh::{SshError, SshShell},
+};
+use crate::{
+    logger::LogLevel,
+    progress::{CommandProgress, CommandProgressCreationError, ProgressContext, format_duration},
+};
+
+static SENTINEL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Quotes a value for use as a single word in a POSIX shell command.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Marks the end of a command's output in a shell session.
+///
+/// Each command is followed by a unique marker on stdout (carrying the exit
+/// code) and on stderr so that output boundaries can be detected on a
+/// channel that stays open between commands.
+pub struct Sentinel {
+    marker: String,
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        let counter = SENTINEL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+
+        Self {
+            marker: format!(
+                "__ARC_SHELL_{}_{}_{}__",
+                std::process::id(),
+                timestamp,
+                counter
+            ),
+        }
+    }
+
+    /// Wraps the command so that it runs in the current shell (preserving
+    /// working directory and environment) and emits the markers afterwards.
+    pub fn wrap(&self, command: &str) -> String {
+        format!(
+            "{{ {command}\n}} </dev/null\n__arc_status=$?\nprintf '%s\\n' '{marker}' >&2\nprintf '%s %s\\n' '{marker}' \"$__arc_status\"\n",
+            marker = self.marker,
+        )
+    }
+
+    /// Removes the marker and everything after it from an output line.
+    fn strip<'a>(&self, line: &'a str) -> &'a str {
+        match line.find(&self.marker) {
+            Some(position) => &line[..position],
+            None => line,
+        }
+    }
+
+    /// Finds the marker in `buffer`, skipping the part before `*searched`
+    /// that an earlier call has already ruled out.
+    fn find(&self, buffer: &[u8], searched: &mut usize) -> Option<usize> {
+        let marker = self.marker.as_bytes();
+
+        match buffer[*searched..]
+            .windows(marker.len())
+            .position(|window| window == marker)
+        {
+            Some(position) => Some(*searched + position),
+            None => {
+                // A marker may be cut off at the end of the buffer
+                *searched = buffer.len().saturating_sub(marker.len() - 1);
+                None
+            }
+        }
+    }
+}
+
+pub struct ShellOutput {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// Output of both streams with the markers removed, up to the last
+    /// complete line.
+    visible: String,
+    /// The incomplete last line of the combined output.
+    pending_line: String,
+    stdout_searched: usize,
+    stderr_searched: usize,
+    started: Instant,
+}
+
+impl ShellOutput {
+    pub fn new() -> Self {
+        Self {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            visible: String::new(),
+            pending_line: String::new(),
+            stdout_searched: 0,
+            stderr_searched: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn push_stdout(&mut self, data: &[u8], sentinel: &Sentinel, progress: &CommandProgress) {
+        self.stdout.extend_from_slice(data);
+        self.push_combined(data, sentinel, progress);
+    }
+
+    pub fn push_stderr(&mut self, data: &[u8], sentinel: &Sentinel, progress: &CommandProgress) {
+        self.stderr.extend_from_slice(data);
+        self.push_combined(data, sentinel, progress);
+    }
+
+    fn push_combined(&mut self, data: &[u8], sentinel: &Sentinel, progress: &CommandProgress) {
+        self.pending_line.push_str(&String::from_utf8_lossy(data));
+
+        if let Some(line_end) = self.pending_line.rfind('\n') {
+            let complete_lines = self.pending_line[..=line_end].to_string();
+            self.pending_line.drain(..=line_end);
+
+            for line in complete_lines.split_inclusive('\n') {
+                self.visible
+                    .push_str(sentinel.strip(line.trim_end_matches('\n')));
+                self.visible.push('\n');
+            }
+        }
+
+        // The incomplete line is shown too, without copying the whole output
+        let visible_length = self.visible.len();
+        self.visible.push_str(sentinel.strip(&self.pending_line));
+        progress.update_output(&self.visible);
+        self.visible.truncate(visible_length);
+    }
+
+    /// Returns the command result once both markers have been received.
+    pub fn complete(&mut self, sentinel: &Sentinel) -> Option<CommandResult> {
+        let stdout_end = sentinel.find(&self.stdout, &mut self.stdout_searched)?;
+        let stderr_end = sentinel.find(&self.stderr, &mut self.stderr_searched)?;
+
+        let status_start = stdout_end + sentinel.marker.len();
+        let status_line = &self.stdout[status_start..];
+        let status_end = status_line.iter().position(|byte| *byte == b'\n')?;
+
+        let exit_code = String::from_utf8_lossy(&status_line[..status_end])
+            .trim()
+            .parse()
+            .unwrap_or(-1);
+
+        Some(CommandResult {
+            stdout: self.stdout[..stdout_end].to_vec(),
+            stderr: self.stderr[..stderr_end].to_vec(),
+            exit_code,
+            elapsed: self.started.elapsed(),
+            skipped: false,
+        })
+    }
+}
+
+pub enum ShellSessionKind {
+    Ssh(SshShell),
+    Host(HostShell),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Shell session has been closed")]
+pub struct ShellClosedError;
+
+#[derive(thiserror::Error, Debug)]
+#[error("Shell session ended unexpectedly")]
+pub struct ShellEndedError;
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to run command in shell session")]
+pub enum ShellError {
+    Closed(#[from] ShellClosedError),
+    Ended(#[from] ShellEndedError),
+    Ssh(#[from] SshError),
+    Host(#[from] CommandError),
+    Progress(#[from] CommandProgressCreationError),
+}
+
+impl ShellError {
+    fn is_task_deadline(&self) -> bool {
+        match self {
+            Self::Ssh(error) => error.is_task_deadline(),
+            Self::Host(error) => error.is_task_deadline(),
+            _ => false,
+        }
+    }
+}
+
+impl FfiError for ShellError {
+    fn is_user_error(&self) -> bool {
+        matches!(self, Self::Closed(_) | Self::Ended(_)) || self.is_task_deadline()
+    }
+}
+
+/// A handle to a shell session shared between Lua and the executor that
+/// opened it, so that the session can be closed at the end of a task.
+#[derive(Clone)]
+pub struct ShellSession {
+    kind: Rc<RefCell<Option<ShellSessionKind>>>,
+    progress: ProgressContext,
+}
+
+impl ShellSession {
+    pub fn new(kind: ShellSessionKind, progress: ProgressContext) -> Self {
+        Self {
+            kind: Rc::new(RefCell::new(Some(kind))),
+            progress,
+        }
+    }
+
+    pub fn run(&self, command: &str) -> Result<CommandResult, ShellError> {
+        // Shell commands are not replayed, so only a dry run skips them
+        if self.progress.is_dry_run() {
+            self.progress.log(
+                LogLevel::Info,
+                &format!("Would run command `{}` in shell", command),
+            );
+
+            return Ok(CommandResult {
+                skipped: true,
+                ..CommandResult::default()
+            });
+        }
+
+        let progress = self.progress.command(command)?;
+
+        let mut kind = self.kind.borrow_mut();
+        let result = match kind.as_mut().ok_or(ShellClosedError)? {
+            ShellSessionKind::Ssh(ssh_shell) => {
+                ssh_shell.run(command, &progress).map_err(ShellError::from)
+            }
+            ShellSessionKind::Host(host_shell) => {
+                host_shell.run(command, &progress).map_err(ShellError::from)
+            }
+        };
+
+        // The interrupted command may still be running, so the session cannot be reused
+        let result = match result {
+            Err(error) if error.is_task_deadline() => {
+                drop(kind);
+                self.close();
+
+                return Err(error);
+            }
+            result => result?,
+        };
+
+        progress.finish();
+
+        match result {
+            Some(result) => {
+                self.progress.log_verbose(&format!(
+                    "Running `{}` in shell exited with code {} after {}",
+                    command,
+                    result.exit_code,
+                    format_duration(result.elapsed)
+                ));
+
+                Ok(result)
+            }
+            None => {
+                kind.take();
+                Err(ShellEndedError.into())
+            }
+        }
+    }
+
+    pub fn close(&self) {
+        match self.kind.borrow_mut().take() {
+            Some(ShellSessionKind::Ssh(ssh_shell)) => ssh_shell.close(),
+            Some(ShellSessionKind::Host(host_shell)) => host_shell.close(),
+            None => {}
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ShellRegistry {
+    sessions: Rc<RefCell<Vec<ShellSession>>>,
+}
+
+impl ShellRegistry {
+    pub fn register(&self, session: ShellSession) {
+        self.sessions.borrow_mut().push(session);
+    }
+
+    pub fn close_all(&self) {
+        for session in self.sessions.borrow_mut().drain(..) {
+            session.close();
+        }
+    }
+}
@@ -1,25 +1,58 @@
-use ssh2::{Session, Sftp};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+};
+use ssh2::{
+    Channel, CheckResult, HashType, HostKeyType, KnownHostFileKind, MethodType, OpenFlags,
+    OpenType, Session, Sftp,
+};
 use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream};
+use std::os::fd::OwnedFd;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{cell::RefCell, rc::Rc};
 
 use super::{
+    error::TaskDeadlineError,
     executor::CommandResult,
-    operator::{FileWriteResult, MetadataResult, MetadataType},
+    operator::{FileWriteResult, MetadataResult, MetadataType, OpenMode, WriteOptions},
+    shell::{Sentinel, ShellOutput, quote},
+};
+use crate::engine::delegator::ssh::error::{
+    classify_exclusive_create_error, classify_io_error, classify_remove_directory_error,
+    classify_ssh_error, is_session_failure, is_timeout,
 };
-use crate::engine::delegator::ssh::error::{classify_io_error, classify_ssh_error};
-use crate::memory::target_systems::RemoteTargetSystem;
-use crate::progress::{CommandProgress, ProgressWriter, TransferProgress};
+use crate::error::ErrorReport;
+use crate::logger::{LogLevel, Logger};
+use crate::memory::target_systems::{
+    HostKeyCheck, RemoteTargetSystem, SshAlgorithms, SshPassword, SshTimeouts,
+};
+use crate::progress::format_duration;
+use crate::progress::{CommandProgress, ProgressContext, ProgressWriter, TransferProgress};
 
-mod error;
+pub mod error;
+mod tunnel;
 use error::ExecutionError;
-pub use error::{InfrastructureError, UserError};
+pub use error::{InfrastructureError, OperationTimeoutError, UserError};
 
 #[derive(Clone)]
 pub struct SshClient {
     session: Session,
     sftp: Arc<Sftp>,
+    timeouts: SshTimeouts,
+}
+
+/// Connection of a remote system, shared by its executor and file system
+/// operator. A session that breaks is reopened once, so that a dropped
+/// connection does not fail the operation that noticed it.
+#[derive(Clone)]
+pub struct SshConnection {
+    client: Rc<RefCell<SshClient>>,
+    system: Rc<RemoteTargetSystem>,
+    home_path: PathBuf,
+    progress: ProgressContext,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -27,6 +60,404 @@ pub struct SshClient {
 pub enum ConnectionError {
     TcpConnection(#[source] std::io::Error),
     Ssh(#[from] ssh2::Error),
+    UnsupportedAlgorithm(#[from] UnsupportedAlgorithmError),
+    Authentication(#[from] AuthenticationError),
+    PrivateKey(#[from] PrivateKeyError),
+    HostKeyMismatch(#[from] HostKeyMismatchError),
+    UnknownHost(#[from] UnknownHostError),
+    KnownHosts(#[from] KnownHostsError),
+    MissingHostKey(#[from] MissingHostKeyError),
+    TimedOut(#[from] ConnectTimeoutError),
+    JumpHost(#[from] JumpHostError),
+    Tunnel(#[from] TunnelError),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to connect to jump host {address}")]
+pub struct JumpHostError {
+    address: SocketAddr,
+    #[source]
+    source: Box<ConnectionError>,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Jump host {jump_host} failed to open a tunnel to {target}")]
+pub struct TunnelError {
+    jump_host: SocketAddr,
+    target: SocketAddr,
+    #[source]
+    source: SshError,
+}
+
+fn connect_error(system: &RemoteTargetSystem, error: ssh2::Error) -> ConnectionError {
+    if is_timeout(&error) {
+        ConnectTimeoutError {
+            address: system.socket_address().to_string(),
+            timeout: system.timeouts.connect,
+        }
+        .into()
+    } else {
+        error.into()
+    }
+}
+
+/// Connects and authenticates to `system`, tunneling through its jump hosts
+/// first if there are any. Returns the session along with its socket.
+fn open_session(
+    system: &RemoteTargetSystem,
+    home_path: &Path,
+    logger: &Logger,
+) -> Result<(Session, OwnedFd), ConnectionError> {
+    let mut session = Session::new()?;
+    apply_algorithm_preferences(&session, &system.algorithms)?;
+
+    let timeouts = system.timeouts;
+
+    let socket = match &system.jump_host {
+        Some(jump_host) => {
+            let (jump_session, jump_socket) =
+                open_session(jump_host, home_path, logger).map_err(|source| JumpHostError {
+                    address: jump_host.socket_address(),
+                    source: Box::new(source),
+                })?;
+            jump_session.set_timeout(timeouts.connect.as_millis() as u32);
+
+            let jump = tunnel::JumpConnection {
+                session: jump_session,
+                socket: jump_socket,
+                address: jump_host.socket_address(),
+            };
+            let stream =
+                tunnel::open(jump, system.socket_address(), logger.clone()).map_err(|source| {
+                    TunnelError {
+                        jump_host: jump_host.socket_address(),
+                        target: system.socket_address(),
+                        source,
+                    }
+                })?;
+
+            let socket = stream
+                .try_clone()
+                .map_err(ConnectionError::TcpConnection)?
+                .into();
+            session.set_tcp_stream(stream);
+            socket
+        }
+        None => {
+            let tcp_stream = TcpStream::connect_timeout(&system.socket_address(), timeouts.connect)
+                .map_err(|error| match error.kind() {
+                    std::io::ErrorKind::TimedOut => ConnectTimeoutError {
+                        address: system.socket_address().to_string(),
+                        timeout: timeouts.connect,
+                    }
+                    .into(),
+                    _ => ConnectionError::TcpConnection(error),
+                })?;
+
+            let socket = tcp_stream
+                .try_clone()
+                .map_err(ConnectionError::TcpConnection)?
+                .into();
+            session.set_tcp_stream(tcp_stream);
+            socket
+        }
+    };
+
+    session.set_timeout(timeouts.connect.as_millis() as u32);
+    session
+        .handshake()
+        .map_err(|error| connect_error(system, error))?;
+    verify_host_key(&session, system, home_path)?;
+
+    // Try to authenticate without agent (e.g. without credentials)
+    let offered = session
+        .auth_methods(&system.user)?
+        .split(',')
+        .map(str::to_string)
+        .collect();
+
+    if !session.authenticated() {
+        authenticate(&session, system, offered)?;
+    }
+
+    Ok((session, socket))
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Connection to {address} timed out after {}", format_duration(*timeout))]
+pub struct ConnectTimeoutError {
+    address: String,
+    timeout: Duration,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Host key of {host} does not match known_hosts (offered {fingerprint}). \
+     The host may have been reinstalled or the connection intercepted"
+)]
+pub struct HostKeyMismatchError {
+    host: String,
+    fingerprint: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Host {host} is not in known_hosts (offered {fingerprint}). \
+     Add it or use host_key_check = \"accept_new\""
+)]
+pub struct UnknownHostError {
+    host: String,
+    fingerprint: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to use known_hosts file {path:?}")]
+pub struct KnownHostsError {
+    path: PathBuf,
+    #[source]
+    source: KnownHostsFailure,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum KnownHostsFailure {
+    #[error(transparent)]
+    Ssh(#[from] ssh2::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Host key check failed")]
+    Check,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Server did not offer a host key")]
+pub struct MissingHostKeyError;
+
+/// Verifies the host key offered during the handshake against the user's
+/// `~/.ssh/known_hosts` according to the system's [`HostKeyCheck`].
+fn verify_host_key(
+    session: &Session,
+    system: &RemoteTargetSystem,
+    home_path: &Path,
+) -> Result<(), ConnectionError> {
+    let policy = system.host_key_check.unwrap_or_default();
+
+    if policy == HostKeyCheck::Off {
+        return Ok(());
+    }
+
+    let known_hosts_path = home_path.join(".ssh").join("known_hosts");
+    let known_hosts_error = |source: KnownHostsFailure| KnownHostsError {
+        path: known_hosts_path.clone(),
+        source,
+    };
+
+    let mut known_hosts = session.known_hosts()?;
+
+    if known_hosts_path.exists() {
+        known_hosts
+            .read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)
+            .map_err(|error| known_hosts_error(error.into()))?;
+    }
+
+    let (key, key_type) = session.host_key().ok_or(MissingHostKeyError)?;
+
+    let host = system.address.to_string();
+    let host_entry = match system.port {
+        22 => host.clone(),
+        port => format!("[{}]:{}", host, port),
+    };
+    let fingerprint = session
+        .host_key_hash(HashType::Sha256)
+        .map(|hash| format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+        .unwrap_or_default();
+
+    match known_hosts.check_port(&host, system.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(HostKeyMismatchError {
+            host: host_entry,
+            fingerprint,
+        }
+        .into()),
+        CheckResult::NotFound if policy == HostKeyCheck::Strict => Err(UnknownHostError {
+            host: host_entry,
+            fingerprint,
+        }
+        .into()),
+        CheckResult::NotFound => {
+            let key_type_name =
+                host_key_type_name(key_type).ok_or(known_hosts_error(KnownHostsFailure::Check))?;
+
+            // Appending keeps entries libssh2 cannot parse, which rewriting
+            // the whole file would drop.
+            append_known_host(
+                &known_hosts_path,
+                &format!(
+                    "{} {} {}\n",
+                    host_entry,
+                    key_type_name,
+                    STANDARD.encode(key)
+                ),
+            )
+            .map_err(|error| known_hosts_error(error.into()))?;
+
+            Ok(())
+        }
+        CheckResult::Failure => Err(known_hosts_error(KnownHostsFailure::Check).into()),
+    }
+}
+
+fn host_key_type_name(key_type: HostKeyType) -> Option<&'static str> {
+    match key_type {
+        HostKeyType::Rsa => Some("ssh-rsa"),
+        HostKeyType::Dss => Some("ssh-dss"),
+        HostKeyType::Ecdsa256 => Some("ecdsa-sha2-nistp256"),
+        HostKeyType::Ecdsa384 => Some("ecdsa-sha2-nistp384"),
+        HostKeyType::Ecdsa521 => Some("ecdsa-sha2-nistp521"),
+        HostKeyType::Ed25519 => Some("ssh-ed25519"),
+        HostKeyType::Unknown => None,
+    }
+}
+
+fn append_known_host(known_hosts_path: &Path, line: &str) -> Result<(), std::io::Error> {
+    if let Some(ssh_directory) = known_hosts_path.parent() {
+        std::fs::create_dir_all(ssh_directory)?;
+    }
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(known_hosts_path)?
+        .write_all(line.as_bytes())
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Cannot read private key file {path:?}")]
+pub struct PrivateKeyError {
+    path: PathBuf,
+    #[source]
+    source: std::io::Error,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Authentication failed for user {user:?}: tried {}, server accepts {}",
+    attempted.join(", "),
+    offered.join(", ")
+)]
+pub struct AuthenticationError {
+    user: String,
+    attempted: Vec<&'static str>,
+    offered: Vec<String>,
+    #[source]
+    last_error: Option<ssh2::Error>,
+}
+
+/// Authenticates with the configured private key file and password, in
+/// that order, and falls back to the SSH agent.
+fn authenticate(
+    session: &Session,
+    system: &RemoteTargetSystem,
+    offered: Vec<String>,
+) -> Result<(), ConnectionError> {
+    let mut attempted = Vec::new();
+    let mut last_error = None;
+
+    if let Some(private_key_path) = &system.private_key_path {
+        std::fs::File::open(private_key_path).map_err(|source| PrivateKeyError {
+            path: private_key_path.clone(),
+            source,
+        })?;
+
+        attempted.push("private key");
+
+        match session.userauth_pubkey_file(
+            &system.user,
+            None,
+            private_key_path,
+            system.passphrase.as_ref().map(SshPassword::expose),
+        ) {
+            Ok(()) if session.authenticated() => return Ok(()),
+            Ok(()) => {}
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    if let Some(password) = &system.password {
+        attempted.push("password");
+
+        match session.userauth_password(&system.user, password.expose()) {
+            Ok(()) if session.authenticated() => return Ok(()),
+            Ok(()) => {}
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    attempted.push("agent");
+
+    match session.userauth_agent(&system.user) {
+        Ok(()) if session.authenticated() => return Ok(()),
+        Ok(()) => {}
+        Err(error) => last_error = Some(error),
+    }
+
+    Err(AuthenticationError {
+        user: system.user.clone(),
+        attempted,
+        offered,
+        last_error,
+    }
+    .into())
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Unsupported {method} algorithm {algorithm:?}. Supported algorithms: {}",
+    supported.join(", ")
+)]
+pub struct UnsupportedAlgorithmError {
+    method: &'static str,
+    algorithm: String,
+    supported: Vec<&'static str>,
+}
+
+fn algorithm_preferences(algorithms: &SshAlgorithms) -> [(&'static str, MethodType, &[String]); 6] {
+    [
+        ("kex", MethodType::Kex, &algorithms.kex),
+        ("host key", MethodType::HostKey, &algorithms.host_key),
+        ("cipher", MethodType::CryptCs, &algorithms.ciphers),
+        ("cipher", MethodType::CryptSc, &algorithms.ciphers),
+        ("mac", MethodType::MacCs, &algorithms.macs),
+        ("mac", MethodType::MacSc, &algorithms.macs),
+    ]
+}
+
+fn apply_algorithm_preferences(
+    session: &Session,
+    algorithms: &SshAlgorithms,
+) -> Result<(), ConnectionError> {
+    for (method, method_type, preferences) in algorithm_preferences(algorithms) {
+        if preferences.is_empty() {
+            continue;
+        }
+
+        let supported = session.supported_algs(method_type)?;
+
+        if let Some(algorithm) = preferences
+            .iter()
+            .find(|algorithm| !supported.contains(&algorithm.as_str()))
+        {
+            return Err(UnsupportedAlgorithmError {
+                method,
+                algorithm: algorithm.clone(),
+                supported,
+            }
+            .into());
+        }
+
+        session.method_pref(method_type, &preferences.join(","))?;
+    }
+
+    Ok(())
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -34,27 +465,239 @@ pub enum ConnectionError {
 pub enum SshError {
     Io(#[from] std::io::Error),
     Ssh(#[from] ssh2::Error),
+    TimedOut(#[from] OperationTimeoutError),
+    TaskDeadline(#[from] TaskDeadlineError),
 }
 
-impl SshClient {
-    pub fn connect(system: &RemoteTargetSystem) -> Result<Self, ConnectionError> {
-        let tcp_stream =
-            TcpStream::connect(system.socket_address()).map_err(ConnectionError::TcpConnection)?;
+impl SshError {
+    pub fn is_task_deadline(&self) -> bool {
+        matches!(self, Self::TaskDeadline(_))
+    }
+}
+
+pub struct SshShell {
+    session: Session,
+    channel: Channel,
+    keepalive: bool,
+}
+
+impl SshShell {
+    pub fn run(
+        &mut self,
+        command: &str,
+        progress: &CommandProgress,
+    ) -> Result<Option<CommandResult>, SshError> {
+        let sentinel = Sentinel::new();
+
+        self.channel.write_all(sentinel.wrap(command).as_bytes())?;
+        self.channel.flush()?;
+
+        self.session.set_blocking(false);
+        let result = self.read_until_sentinel(&sentinel, progress);
+        self.session.set_blocking(true);
+
+        result
+    }
+
+    fn read_until_sentinel(
+        &mut self,
+        sentinel: &Sentinel,
+        progress: &CommandProgress,
+    ) -> Result<Option<CommandResult>, SshError> {
+        let mut output = ShellOutput::new();
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            // Checked before reading, as a command that keeps writing would
+            // otherwise never leave the loop
+            if progress.deadline_reached() {
+                return Err(TaskDeadlineError.into());
+            }
+
+            let mut received_data = false;
+
+            match self.channel.read(&mut buffer) {
+                Ok(0) => {}
+                Ok(bytes_read) => {
+                    output.push_stdout(&buffer[..bytes_read], sentinel, progress);
+                    received_data = true;
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(error) => return Err(error.into()),
+            }
 
-        let mut session = Session::new()?;
-        session.set_tcp_stream(tcp_stream);
-        session.handshake()?;
+            match self.channel.stderr().read(&mut buffer) {
+                Ok(0) => {}
+                Ok(bytes_read) => {
+                    output.push_stderr(&buffer[..bytes_read], sentinel, progress);
+                    received_data = true;
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(error) => return Err(error.into()),
+            }
+
+            if let Some(result) = output.complete(sentinel) {
+                return Ok(Some(result));
+            }
 
-        // Try to authenticate without agent (e.g. without credentials)
-        session.auth_methods(&system.user)?;
+            if !received_data {
+                if self.channel.eof() {
+                    return Ok(None);
+                }
+
+                if self.keepalive {
+                    send_keepalive(&self.session)?;
+                }
 
-        if !session.authenticated() {
-            session.userauth_agent(&system.user)?;
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
         }
+    }
+
+    pub fn close(mut self) {
+        _ = self.channel.write_all(b"exit\n");
+        _ = self.channel.send_eof();
+        _ = self.channel.close();
+        _ = self.channel.wait_close();
+    }
+}
 
-        let sftp = Arc::new(session.sftp()?);
+static REBOOT_COMMAND: &str = "reboot";
+static BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+const REBOOT_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
-        Ok(Self { session, sftp })
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to reboot")]
+pub enum RebootError {
+    Ssh(#[from] SshError),
+    Command(#[from] RebootCommandError),
+    Timeout(#[from] RebootTimeoutError),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("`{command}` exited with code {exit_code}: {stderr}")]
+pub struct RebootCommandError {
+    command: &'static str,
+    exit_code: i32,
+    stderr: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{address} was not reachable again within {} after the reboot", format_duration(*timeout))]
+pub struct RebootTimeoutError {
+    address: SocketAddr,
+    timeout: Duration,
+    #[source]
+    source: Option<Box<ConnectionError>>,
+}
+
+impl RebootError {
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            Self::Ssh(error) => error.is_task_deadline(),
+            Self::Command(_) => true,
+            Self::Timeout(_) => false,
+        }
+    }
+}
+
+impl SshConnection {
+    pub fn connect(
+        system: &RemoteTargetSystem,
+        home_path: &Path,
+        progress: ProgressContext,
+    ) -> Result<Self, ConnectionError> {
+        let client = SshClient::connect(system, home_path, progress.logger())?;
+
+        Ok(Self {
+            client: Rc::new(RefCell::new(client)),
+            system: Rc::new(system.clone()),
+            home_path: home_path.to_path_buf(),
+            progress,
+        })
+    }
+
+    /// Client of the current session
+    pub fn client(&self) -> SshClient {
+        self.client.borrow().clone()
+    }
+
+    /// Replaces the session broken by `error`. Returns whether a new session
+    /// could be opened.
+    fn reconnect(&self, error: &dyn std::error::Error) -> bool {
+        let address = SocketAddr::new(self.system.address, self.system.port);
+
+        self.progress.log(
+            LogLevel::Warn,
+            &format!(
+                "Lost the SSH session to {}, reconnecting: {}",
+                address,
+                ErrorReport::describe(error)
+            ),
+        );
+
+        match SshClient::connect(&self.system, &self.home_path, self.progress.logger()) {
+            Ok(client) => {
+                *self.client.borrow_mut() = client;
+                self.progress
+                    .log(LogLevel::Info, &format!("Reconnected to {}", address));
+
+                true
+            }
+            Err(error) => {
+                self.progress.log(
+                    LogLevel::Warn,
+                    &format!(
+                        "Failed to reconnect to {}: {}",
+                        address,
+                        ErrorReport::describe(&error)
+                    ),
+                );
+
+                false
+            }
+        }
+    }
+
+    /// Runs `operation` and repeats it once on a new session if the session
+    /// broke while it ran.
+    pub fn retrying<T>(
+        &self,
+        operation: impl Fn(&SshClient) -> Result<T, ExecutionError>,
+    ) -> Result<T, ExecutionError> {
+        match operation(&self.client()) {
+            Err(ExecutionError::Infrastructure(InfrastructureError::NeedsReconnect(error))) => {
+                if self.reconnect(error.as_ref()) {
+                    operation(&self.client())
+                } else {
+                    Err(ExecutionError::Infrastructure(
+                        InfrastructureError::NeedsReconnect(error),
+                    ))
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Opens a channel, reconnecting once if the session is broken. Commands
+    /// are only repeated if they never started, as running one twice is not
+    /// necessarily safe.
+    fn open_channel(&self) -> Result<(SshClient, Channel), SshError> {
+        let client = self.client();
+
+        match client.session.channel_session() {
+            Ok(channel) => Ok((client, channel)),
+            Err(error) if is_session_failure(&error) && self.reconnect(&error) => {
+                let client = self.client();
+                let channel = client
+                    .session
+                    .channel_session()
+                    .map_err(|error| client.ssh_error(error))?;
+
+                Ok((client, channel))
+            }
+            Err(error) => Err(client.ssh_error(error)),
+        }
     }
 
     pub fn execute_command(
@@ -62,13 +705,184 @@ impl SshClient {
         command: &str,
         progress: &CommandProgress,
     ) -> Result<CommandResult, SshError> {
-        let mut channel = self.session.channel_session()?;
-        channel.exec(command)?;
+        let (client, channel) = self.open_channel()?;
+
+        client.execute_on_channel(channel, command, progress)
+    }
+
+    pub fn open_shell(&self) -> Result<SshShell, SshError> {
+        let (client, channel) = self.open_channel()?;
+
+        client.start_shell(channel)
+    }
+
+    /// Reboots the system and waits until it accepts connections again, at
+    /// most `timeout` after the reboot was issued. The new session replaces
+    /// the current one. Returns how long the system was unreachable.
+    pub fn reboot(&self, timeout: Duration) -> Result<Duration, RebootError> {
+        let address = SocketAddr::new(self.system.address, self.system.port);
+        let boot_id = self.client().boot_id();
+
+        let started = Instant::now();
+
+        match self.execute_command(REBOOT_COMMAND, &CommandProgress::noop()) {
+            Ok(result) if result.exit_code != 0 => {
+                return Err(RebootCommandError {
+                    command: REBOOT_COMMAND,
+                    exit_code: result.exit_code,
+                    stderr: result.stderr_text().trim().to_string(),
+                }
+                .into());
+            }
+            Err(error) if error.is_task_deadline() => return Err(error.into()),
+            // The session usually ends while the command runs
+            _ => {}
+        }
+
+        let deadline = started + timeout;
+        let mut last_error = None;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return Err(RebootTimeoutError {
+                    address,
+                    timeout,
+                    source: last_error,
+                }
+                .into());
+            }
+
+            std::thread::sleep(REBOOT_POLL_INTERVAL.min(remaining));
+
+            match SshClient::connect(&self.system, &self.home_path, self.progress.logger()) {
+                // Until it shuts down, the system still accepts connections
+                // with its previous boot ID
+                Ok(client) if boot_id.is_some() && client.boot_id() == boot_id => {}
+                Ok(client) => {
+                    *self.client.borrow_mut() = client;
+
+                    return Ok(started.elapsed());
+                }
+                Err(error) => {
+                    self.progress.log_verbose(&format!(
+                        "{} is not reachable yet: {}",
+                        address,
+                        ErrorReport::describe(&error)
+                    ));
+                    last_error = Some(Box::new(error));
+                }
+            }
+        }
+    }
+}
+
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+
+/// Sends an SSH keepalive message if the configured interval has passed.
+fn send_keepalive(session: &Session) -> Result<(), SshError> {
+    match session.keepalive_send() {
+        Ok(_) => Ok(()),
+        Err(error) if error.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+impl SshClient {
+    fn start_shell(&self, mut channel: Channel) -> Result<SshShell, SshError> {
+        channel.exec("sh").map_err(|error| self.ssh_error(error))?;
+
+        Ok(SshShell {
+            session: self.session.clone(),
+            channel,
+            keepalive: self.timeouts.keepalive_interval.is_some(),
+        })
+    }
+
+    pub fn connect(
+        system: &RemoteTargetSystem,
+        home_path: &Path,
+        logger: &Logger,
+    ) -> Result<Self, ConnectionError> {
+        let (session, _) = open_session(system, home_path, logger)?;
+        let timeouts = system.timeouts;
+
+        let sftp = Arc::new(
+            session
+                .sftp()
+                .map_err(|error| connect_error(system, error))?,
+        );
+
+        // 0 disables the timeout for blocking operations
+        session.set_timeout(
+            timeouts
+                .operation
+                .map(|timeout| timeout.as_millis() as u32)
+                .unwrap_or(0),
+        );
+
+        if let Some(interval) = timeouts.keepalive_interval {
+            session.set_keepalive(false, interval.as_secs().max(1) as u32);
+        }
+
+        Ok(Self {
+            session,
+            sftp,
+            timeouts,
+        })
+    }
+
+    fn ssh_error(&self, error: ssh2::Error) -> SshError {
+        match self.timeouts.operation {
+            Some(timeout) if is_timeout(&error) => OperationTimeoutError { timeout }.into(),
+            _ => error.into(),
+        }
+    }
+
+    fn classify_ssh_error(&self, error: ssh2::Error, context_path: &Path) -> ExecutionError {
+        match self.timeouts.operation {
+            Some(timeout) if is_timeout(&error) => ExecutionError::Infrastructure(
+                InfrastructureError::TimedOut(OperationTimeoutError { timeout }),
+            ),
+            _ => classify_ssh_error(error, context_path),
+        }
+    }
+
+    pub fn negotiated_algorithms(&self) -> Vec<String> {
+        [
+            ("kex", MethodType::Kex),
+            ("host_key", MethodType::HostKey),
+            ("cipher", MethodType::CryptCs),
+            ("mac", MethodType::MacCs),
+        ]
+        .into_iter()
+        .filter_map(|(name, method_type)| {
+            self.session
+                .methods(method_type)
+                .map(|algorithm| format!("{}={}", name, algorithm))
+        })
+        .collect()
+    }
+
+    fn execute_on_channel(
+        &self,
+        mut channel: Channel,
+        command: &str,
+        progress: &CommandProgress,
+    ) -> Result<CommandResult, SshError> {
+        let started = Instant::now();
+
+        channel
+            .exec(command)
+            .map_err(|error| self.ssh_error(error))?;
 
         self.session.set_blocking(false);
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
+        // Decoded as a whole at the end, so that characters split between
+        // reads stay intact in the result
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
         let mut combined = String::new();
 
         let mut stdout_buffer = [0u8; 4096];
@@ -78,16 +892,23 @@ impl SshClient {
         let mut stderr_reached_eof = false;
 
         loop {
+            // Checked before reading, as a command that keeps writing would
+            // otherwise never leave the loop
+            if progress.deadline_reached() {
+                self.session.set_blocking(true);
+                _ = channel.close();
+
+                return Err(TaskDeadlineError.into());
+            }
+
             let mut received_data = false;
 
             if !stdout_reached_eof {
                 match channel.read(&mut stdout_buffer) {
                     Ok(0) => stdout_reached_eof = true,
                     Ok(bytes_read) => {
-                        let text = String::from_utf8_lossy(&stdout_buffer[..bytes_read]);
-
-                        stdout.push_str(&text);
-                        combined.push_str(&text);
+                        stdout.extend_from_slice(&stdout_buffer[..bytes_read]);
+                        combined.push_str(&String::from_utf8_lossy(&stdout_buffer[..bytes_read]));
 
                         received_data = true;
                     }
@@ -104,10 +925,8 @@ impl SshClient {
                 match channel.stderr().read(&mut stderr_buffer) {
                     Ok(0) => stderr_reached_eof = true,
                     Ok(bytes_read) => {
-                        let text = String::from_utf8_lossy(&stderr_buffer[..bytes_read]);
-
-                        stderr.push_str(&text);
-                        combined.push_str(&text);
+                        stderr.extend_from_slice(&stderr_buffer[..bytes_read]);
+                        combined.push_str(&String::from_utf8_lossy(&stderr_buffer[..bytes_read]));
 
                         received_data = true;
                     }
@@ -129,19 +948,31 @@ impl SshClient {
             }
 
             if !received_data {
+                if self.timeouts.keepalive_interval.is_some()
+                    && let Err(error) = send_keepalive(&self.session)
+                {
+                    self.session.set_blocking(true);
+
+                    return Err(error);
+                }
+
                 std::thread::sleep(std::time::Duration::from_millis(10));
             }
         }
 
         self.session.set_blocking(true);
 
-        channel.close()?;
-        let exit_code = channel.exit_status()?;
+        channel.close().map_err(|error| self.ssh_error(error))?;
+        let exit_code = channel
+            .exit_status()
+            .map_err(|error| self.ssh_error(error))?;
 
         Ok(CommandResult {
             stdout,
             stderr,
             exit_code,
+            elapsed: started.elapsed(),
+            skipped: false,
         })
     }
 
@@ -157,7 +988,7 @@ impl SshClient {
         let mut file = self
             .sftp
             .open(path)
-            .map_err(|error| classify_ssh_error(error, path))?;
+            .map_err(|error| self.classify_ssh_error(error, path))?;
 
         let mut content = Vec::new();
         let mut buf = vec![0u8; super::TRANSFER_BUFFER_SIZE];
@@ -183,12 +1014,28 @@ impl SshClient {
         &self,
         path: &Path,
         content: &[u8],
+        options: &WriteOptions,
         progress: &TransferProgress,
     ) -> Result<FileWriteResult, ExecutionError> {
+        let flags = OpenFlags::WRITE
+            | OpenFlags::CREATE
+            | match options.open_mode {
+                OpenMode::Truncate => OpenFlags::TRUNCATE,
+                OpenMode::Append => OpenFlags::APPEND,
+                OpenMode::Exclusive => OpenFlags::EXCLUSIVE,
+            };
+
+        let mode = options.mode.unwrap_or(super::DEFAULT_FILE_MODE) as i32;
+
         let mut file = self
             .sftp
-            .create(path)
-            .map_err(|error| classify_ssh_error(error, path))?;
+            .open_mode(path, flags, mode, OpenType::File)
+            .map_err(|error| match options.open_mode {
+                OpenMode::Exclusive if !is_timeout(&error) => {
+                    classify_exclusive_create_error(error, path, || self.sftp.stat(path).is_ok())
+                }
+                _ => self.classify_ssh_error(error, path),
+            })?;
 
         let mut written = 0;
         for chunk in content.chunks(super::TRANSFER_BUFFER_SIZE) {
@@ -202,28 +1049,165 @@ impl SshClient {
         Ok(FileWriteResult {
             path: path.to_path_buf(),
             bytes_written: content.len(),
+            backup_path: None,
         })
     }
 
     pub fn rename_file(&self, from: &Path, to: &Path) -> Result<(), ExecutionError> {
         self.sftp
             .rename(from, to, None)
-            .map_err(|error| classify_ssh_error(error, from))
+            .map_err(|error| self.classify_ssh_error(error, from))
+    }
+
+    /// Renames `from` to `to`, replacing `to` if it exists.
+    pub fn replace_file(&self, from: &Path, to: &Path) -> Result<(), ExecutionError> {
+        match self.rename_file(from, to) {
+            // SFTP v3 servers such as OpenSSH's refuse to rename over an
+            // existing file, `mv` within a directory is an atomic rename(2)
+            Err(ExecutionError::User(UserError::Failure(_))) => {
+                let command = format!(
+                    "mv -f -- {} {}",
+                    quote(&from.to_string_lossy()),
+                    quote(&to.to_string_lossy())
+                );
+
+                self.run_simple_command(&command, from)
+            }
+            result => result,
+        }
+    }
+
+    fn run_simple_command(&self, command: &str, context_path: &Path) -> Result<(), ExecutionError> {
+        match self.run_command_with_output(command, context_path)? {
+            (0, _, _) => Ok(()),
+            (_, _, stderr) => Err(ExecutionError::User(UserError::CommandFailed(
+                stderr.trim().to_string(),
+            ))),
+        }
+    }
+
+    /// Runs a short command and returns its exit status, stdout and stderr.
+    fn run_command_with_output(
+        &self,
+        command: &str,
+        context_path: &Path,
+    ) -> Result<(i32, String, String), ExecutionError> {
+        let mut channel = self
+            .session
+            .channel_session()
+            .map_err(|error| self.classify_ssh_error(error, context_path))?;
+        channel
+            .exec(command)
+            .map_err(|error| self.classify_ssh_error(error, context_path))?;
+
+        let mut stdout = String::new();
+        channel
+            .read_to_string(&mut stdout)
+            .map_err(classify_io_error)?;
+        let mut stderr = String::new();
+        channel
+            .stderr()
+            .read_to_string(&mut stderr)
+            .map_err(classify_io_error)?;
+        channel
+            .wait_close()
+            .map_err(|error| self.classify_ssh_error(error, context_path))?;
+
+        let exit_status = channel
+            .exit_status()
+            .map_err(|error| self.classify_ssh_error(error, context_path))?;
+
+        Ok((exit_status, stdout, stderr))
+    }
+
+    /// ID that changes whenever the system boots. `None` if the system does
+    /// not provide one.
+    fn boot_id(&self) -> Option<String> {
+        match self
+            .run_command_with_output(&format!("cat {}", BOOT_ID_PATH), Path::new(BOOT_ID_PATH))
+        {
+            Ok((0, stdout, _)) => Some(stdout.trim().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` in a name service database such as `passwd` or
+    /// `group`. Returns `None` if the key does not exist.
+    pub fn getent(&self, database: &str, key: &str) -> Result<Option<String>, ExecutionError> {
+        let command = format!("getent {} {}", quote(database), quote(key));
+
+        match self.run_command_with_output(&command, Path::new(database))? {
+            (0, stdout, _) => Ok(Some(stdout)),
+            (super::GETENT_KEY_NOT_FOUND, _, _) => Ok(None),
+            (_, _, stderr) => Err(ExecutionError::User(UserError::CommandFailed(
+                stderr.trim().to_string(),
+            ))),
+        }
+    }
+
+    pub fn set_ownership(
+        &self,
+        path: &Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> Result<(), ExecutionError> {
+        // SFTP sets uid and gid together, so the unchanged one has to be sent as is
+        let (uid, gid) = match (uid, gid) {
+            (Some(uid), Some(gid)) => (uid, gid),
+            (None, None) => return Ok(()),
+            _ => {
+                let stat = self
+                    .sftp
+                    .stat(path)
+                    .map_err(|error| self.classify_ssh_error(error, path))?;
+
+                match (uid.or(stat.uid), gid.or(stat.gid)) {
+                    (Some(uid), Some(gid)) => (uid, gid),
+                    _ => {
+                        return Err(ExecutionError::User(UserError::OwnershipUnavailable(
+                            path.to_path_buf(),
+                        )));
+                    }
+                }
+            }
+        };
+
+        let stat = ssh2::FileStat {
+            size: None,
+            uid: Some(uid),
+            gid: Some(gid),
+            perm: None,
+            atime: None,
+            mtime: None,
+        };
+
+        self.sftp
+            .setstat(path, stat)
+            .map_err(|error| self.classify_ssh_error(error, path))
     }
 
     pub fn remove_file(&self, path: &Path) -> Result<(), ExecutionError> {
         self.sftp
             .unlink(path)
-            .map_err(|error| classify_ssh_error(error, path))
+            .map_err(|error| self.classify_ssh_error(error, path))
     }
 
     pub fn remove_directory(&self, path: &Path) -> Result<(), ExecutionError> {
         self.sftp
             .rmdir(path)
-            .map_err(|error| classify_ssh_error(error, path))
+            .map_err(|error| match is_timeout(&error) {
+                false => classify_remove_directory_error(error, path, || {
+                    self.sftp
+                        .readdir(path)
+                        .is_ok_and(|entries| !entries.is_empty())
+                }),
+                true => self.classify_ssh_error(error, path),
+            })
     }
 
-    pub fn create_directory(&self, path: &Path) -> Result<(), ExecutionError> {
+    /// Creates `path` and its missing ancestors. Ancestors get mode `0o755`,
+    /// `path` itself `mode` if given.
+    pub fn create_directory(&self, path: &Path, mode: Option<u32>) -> Result<(), ExecutionError> {
         let ancestors = path
             .ancestors()
             .collect::<Vec<_>>()
@@ -241,12 +1225,19 @@ impl SshClient {
                 }
                 Err(error) => match error.code() {
                     ssh2::ErrorCode::SFTP(error::SFTP_NO_SUCH_FILE) => {
+                        let requested_mode = mode.filter(|_| ancestor_path == path);
+
                         self.sftp
-                            .mkdir(ancestor_path, 0o755)
-                            .map_err(|error| classify_ssh_error(error, ancestor_path))?;
+                            .mkdir(ancestor_path, requested_mode.unwrap_or(0o755) as i32)
+                            .map_err(|error| self.classify_ssh_error(error, ancestor_path))?;
+
+                        // The server applies its umask to the mode of `mkdir`
+                        if let Some(mode) = requested_mode {
+                            self.set_permissions(ancestor_path, mode)?;
+                        }
                     }
                     _ => {
-                        return Err(classify_ssh_error(error, ancestor_path));
+                        return Err(self.classify_ssh_error(error, ancestor_path));
                     }
                 },
             }
@@ -267,14 +1258,14 @@ impl SshClient {
 
         self.sftp
             .setstat(path, stat)
-            .map_err(|error| classify_ssh_error(error, path))
+            .map_err(|error| self.classify_ssh_error(error, path))
     }
 
     pub fn list_directory(&self, path: &Path) -> Result<Vec<MetadataResult>, ExecutionError> {
         let mut dir = self
             .sftp
             .opendir(path)
-            .map_err(|error| classify_ssh_error(error, path))?;
+            .map_err(|error| self.classify_ssh_error(error, path))?;
 
         let mut entries = Vec::new();
 
@@ -312,7 +1303,7 @@ impl SshClient {
                         break;
                     }
                     ssh2::ErrorCode::SFTP(_) | ssh2::ErrorCode::Session(_) => {
-                        return Err(classify_ssh_error(error, path));
+                        return Err(self.classify_ssh_error(error, path));
                     }
                 },
             }
@@ -327,7 +1318,7 @@ impl SshClient {
             Err(error) => match error.code() {
                 ssh2::ErrorCode::SFTP(error::SFTP_NO_SUCH_FILE) => return Ok(None),
                 ssh2::ErrorCode::SFTP(_) | ssh2::ErrorCode::Session(_) => {
-                    return Err(classify_ssh_error(error, path));
+                    return Err(self.classify_ssh_error(error, path));
                 }
             },
         };
@@ -375,7 +1366,7 @@ impl SshClient {
                 Err(error) => match error.code() {
                     ssh2::ErrorCode::SFTP(error::SFTP_NO_SUCH_FILE) => break,
                     _ => {
-                        return Err(classify_ssh_error(error, ancestor));
+                        return Err(self.classify_ssh_error(error, ancestor));
                     }
                 },
             }
@@ -394,7 +1385,7 @@ impl SshClient {
             Ok(_) => Ok(()),
             Err(error) => match error.code() {
                 ssh2::ErrorCode::SFTP(error::SFTP_NO_SUCH_FILE) => Ok(()),
-                _ => Err(classify_ssh_error(error, path)),
+                _ => Err(self.classify_ssh_error(error, path)),
             },
         }
     }
@@ -406,7 +1397,7 @@ impl SshClient {
         let file = self
             .sftp
             .open(path)
-            .map_err(|error| classify_ssh_error(error, path))?;
+            .map_err(|error| self.classify_ssh_error(error, path))?;
 
         Ok(std::io::BufReader::with_capacity(
             super::TRANSFER_BUFFER_SIZE,
@@ -423,7 +1414,7 @@ impl SshClient {
         let file = self
             .sftp
             .create(path)
-            .map_err(|error| classify_ssh_error(error, path))?;
+            .map_err(|error| self.classify_ssh_error(error, path))?;
 
         let buf_writer = std::io::BufWriter::with_capacity(super::TRANSFER_BUFFER_SIZE, file);
         let mut writer = ProgressWriter::new(buf_writer, progress);
@@ -433,6 +1424,7 @@ impl SshClient {
         Ok(FileWriteResult {
             path: path.to_path_buf(),
             bytes_written: bytes_written as usize,
+            backup_path: None,
         })
     }
 }
@@ -1,6 +1,11 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use crate::engine::delegator::error::ExecutionError as GenericExecutionError;
+use crate::{
+    engine::delegator::error::ExecutionError as GenericExecutionError, progress::format_duration,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum UserError {
@@ -8,12 +13,30 @@ pub enum UserError {
     NotFound(ssh2::Error),
     #[error("Permission denied")]
     PermissionDenied(ssh2::Error),
+    #[error("Already exists")]
+    AlreadyExists(ssh2::Error),
     #[error("Path is a directory")]
     IsADirectory,
     #[error("Not a directory: {0:?}")]
     NotADirectory(PathBuf),
+    #[error("Directory not empty")]
+    DirectoryNotEmpty(ssh2::Error),
+    #[error("Read-only filesystem")]
+    ReadOnlyFilesystem(ssh2::Error),
+    #[error("No space left on device")]
+    NoSpace(ssh2::Error),
+    #[error("Disk quota exceeded")]
+    QuotaExceeded(ssh2::Error),
+    #[error("Invalid filename")]
+    InvalidFilename(ssh2::Error),
+    #[error("Operation not supported by the server")]
+    Unsupported(ssh2::Error),
     #[error("Operation failed")]
     Failure(ssh2::Error),
+    #[error("Command failed: {0}")]
+    CommandFailed(String),
+    #[error("Server did not report the current owner and group of {0:?}")]
+    OwnershipUnavailable(PathBuf),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -22,33 +45,96 @@ pub enum InfrastructureError {
     NeedsReconnect(Box<dyn std::error::Error + Send + Sync>),
     OtherSsh(ssh2::Error),
     OtherIo(std::io::Error),
+    TimedOut(OperationTimeoutError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Operation timed out after {}", format_duration(self.timeout))]
+pub struct OperationTimeoutError {
+    pub timeout: Duration,
 }
 
 pub type ExecutionError = GenericExecutionError<UserError, InfrastructureError>;
 
 pub const SFTP_NO_SUCH_FILE: i32 = 2;
 const SFTP_PERMISSION_DENIED: i32 = 3;
+const SFTP_FAILURE: i32 = 4;
 const SFTP_BAD_MESSAGE: i32 = 5;
 const SFTP_NO_CONNECTION: i32 = 6;
 const SFTP_CONNECTION_LOST: i32 = 7;
+const SFTP_OP_UNSUPPORTED: i32 = 8;
+const SFTP_NO_SUCH_PATH: i32 = 10;
+const SFTP_FILE_ALREADY_EXISTS: i32 = 11;
+const SFTP_WRITE_PROTECT: i32 = 12;
+const SFTP_NO_SPACE_ON_FILESYSTEM: i32 = 14;
+const SFTP_QUOTA_EXCEEDED: i32 = 15;
+const SFTP_DIR_NOT_EMPTY: i32 = 18;
+const SFTP_NOT_A_DIRECTORY: i32 = 19;
+const SFTP_INVALID_FILENAME: i32 = 20;
+const SFTP_FILE_IS_A_DIRECTORY: i32 = 24;
 
 pub const SSH_SESSION_ERROR_CODE_FILE_ERROR: i32 = -16;
 
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
 const LIBSSH2_ERROR_SFTP_PROTOCOL: i32 = -31;
 
-pub fn classify_ssh_error(error: ssh2::Error, _context_path: &Path) -> ExecutionError {
+pub fn is_timeout(error: &ssh2::Error) -> bool {
+    error.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT)
+}
+
+/// Whether `error` comes from the SSH session itself rather than from the
+/// operation, in which case the session has to be reopened.
+pub fn is_session_failure(error: &ssh2::Error) -> bool {
+    matches!(
+        error.code(),
+        ssh2::ErrorCode::Session(code)
+            if code != LIBSSH2_ERROR_SFTP_PROTOCOL && code != LIBSSH2_ERROR_TIMEOUT
+    )
+}
+
+/// Classifies a failed SFTP operation on `context_path`. Status codes above
+/// `SFTP_FILE_ALREADY_EXISTS` are only sent by servers speaking protocol
+/// version 4 or later.
+pub fn classify_ssh_error(error: ssh2::Error, context_path: &Path) -> ExecutionError {
     match error.code() {
         ssh2::ErrorCode::SFTP(SFTP_BAD_MESSAGE)
         | ssh2::ErrorCode::SFTP(SFTP_NO_CONNECTION)
         | ssh2::ErrorCode::SFTP(SFTP_CONNECTION_LOST) => {
             ExecutionError::Infrastructure(InfrastructureError::OtherSsh(error))
         }
-        ssh2::ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => {
+        ssh2::ErrorCode::SFTP(SFTP_NO_SUCH_FILE) | ssh2::ErrorCode::SFTP(SFTP_NO_SUCH_PATH) => {
             ExecutionError::User(UserError::NotFound(error))
         }
         ssh2::ErrorCode::SFTP(SFTP_PERMISSION_DENIED) => {
             ExecutionError::User(UserError::PermissionDenied(error))
         }
+        ssh2::ErrorCode::SFTP(SFTP_FILE_ALREADY_EXISTS) => {
+            ExecutionError::User(UserError::AlreadyExists(error))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_DIR_NOT_EMPTY) => {
+            ExecutionError::User(UserError::DirectoryNotEmpty(error))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_NOT_A_DIRECTORY) => {
+            ExecutionError::User(UserError::NotADirectory(context_path.to_path_buf()))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_FILE_IS_A_DIRECTORY) => {
+            ExecutionError::User(UserError::IsADirectory)
+        }
+        ssh2::ErrorCode::SFTP(SFTP_WRITE_PROTECT) => {
+            ExecutionError::User(UserError::ReadOnlyFilesystem(error))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_NO_SPACE_ON_FILESYSTEM) => {
+            ExecutionError::User(UserError::NoSpace(error))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_QUOTA_EXCEEDED) => {
+            ExecutionError::User(UserError::QuotaExceeded(error))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_INVALID_FILENAME) => {
+            ExecutionError::User(UserError::InvalidFilename(error))
+        }
+        ssh2::ErrorCode::SFTP(SFTP_OP_UNSUPPORTED) => {
+            ExecutionError::User(UserError::Unsupported(error))
+        }
 
         ssh2::ErrorCode::SFTP(_) => ExecutionError::User(UserError::Failure(error)),
 
@@ -64,6 +150,173 @@ pub fn classify_ssh_error(error: ssh2::Error, _context_path: &Path) -> Execution
     }
 }
 
+/// Classifies a failed exclusive create.
+///
+/// OpenSSH's sftp-server speaks protocol version 3, which has no status for
+/// an existing file, so a collision is reported as a generic failure. In that
+/// case `exists` is asked whether the path is present.
+pub fn classify_exclusive_create_error(
+    error: ssh2::Error,
+    context_path: &Path,
+    exists: impl FnOnce() -> bool,
+) -> ExecutionError {
+    match error.code() {
+        ssh2::ErrorCode::SFTP(SFTP_FAILURE) if exists() => {
+            ExecutionError::User(UserError::AlreadyExists(error))
+        }
+        _ => classify_ssh_error(error, context_path),
+    }
+}
+
+/// Classifies a failed `rmdir`.
+///
+/// Protocol version 3 has no status for a directory that is not empty, and
+/// libssh2 does not even pass on the generic failure of `rmdir`. In that case
+/// `has_entries` is asked whether the directory still contains anything.
+pub fn classify_remove_directory_error(
+    error: ssh2::Error,
+    context_path: &Path,
+    has_entries: impl FnOnce() -> bool,
+) -> ExecutionError {
+    let is_failure = matches!(
+        error.code(),
+        ssh2::ErrorCode::SFTP(SFTP_FAILURE) | ssh2::ErrorCode::Session(LIBSSH2_ERROR_SFTP_PROTOCOL)
+    );
+
+    match is_failure && has_entries() {
+        true => ExecutionError::User(UserError::DirectoryNotEmpty(error)),
+        false => classify_ssh_error(error, context_path),
+    }
+}
+
 pub fn classify_io_error(error: std::io::Error) -> ExecutionError {
     ExecutionError::Infrastructure(InfrastructureError::OtherIo(error))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive_create_failure_on_existing_path_is_already_exists() {
+        let error = ssh2::Error::new(ssh2::ErrorCode::SFTP(SFTP_FAILURE), "Failure");
+
+        let classified = classify_exclusive_create_error(error, Path::new("/file"), || true);
+
+        assert!(matches!(
+            classified,
+            ExecutionError::User(UserError::AlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn exclusive_create_failure_on_missing_path_stays_a_failure() {
+        let error = ssh2::Error::new(ssh2::ErrorCode::SFTP(SFTP_FAILURE), "Failure");
+
+        let classified = classify_exclusive_create_error(error, Path::new("/file"), || false);
+
+        assert!(matches!(
+            classified,
+            ExecutionError::User(UserError::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn exclusive_create_other_errors_are_not_checked_for_existence() {
+        let error = ssh2::Error::new(
+            ssh2::ErrorCode::SFTP(SFTP_PERMISSION_DENIED),
+            "Permission denied",
+        );
+
+        let classified = classify_exclusive_create_error(error, Path::new("/file"), || {
+            panic!("existence must not be checked")
+        });
+
+        assert!(matches!(
+            classified,
+            ExecutionError::User(UserError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn sftp_status_codes_are_reported_as_readable_messages() {
+        let message = |code| {
+            let error = ssh2::Error::new(ssh2::ErrorCode::SFTP(code), "SFTP");
+
+            classify_ssh_error(error, Path::new("/srv/app")).to_string()
+        };
+
+        assert_eq!(message(SFTP_NO_SUCH_FILE), "Not found");
+        assert_eq!(message(SFTP_NO_SUCH_PATH), "Not found");
+        assert_eq!(message(SFTP_PERMISSION_DENIED), "Permission denied");
+        assert_eq!(message(SFTP_FILE_ALREADY_EXISTS), "Already exists");
+        assert_eq!(message(SFTP_DIR_NOT_EMPTY), "Directory not empty");
+        assert_eq!(
+            message(SFTP_NOT_A_DIRECTORY),
+            "Not a directory: \"/srv/app\""
+        );
+        assert_eq!(message(SFTP_FILE_IS_A_DIRECTORY), "Path is a directory");
+        assert_eq!(message(SFTP_WRITE_PROTECT), "Read-only filesystem");
+        assert_eq!(
+            message(SFTP_NO_SPACE_ON_FILESYSTEM),
+            "No space left on device"
+        );
+        assert_eq!(message(SFTP_QUOTA_EXCEEDED), "Disk quota exceeded");
+        assert_eq!(message(SFTP_FAILURE), "Operation failed");
+    }
+
+    #[test]
+    fn failed_removal_of_a_directory_with_entries_is_not_empty() {
+        let protocol_error = || {
+            ssh2::Error::new(
+                ssh2::ErrorCode::Session(LIBSSH2_ERROR_SFTP_PROTOCOL),
+                "SFTP Protocol Error",
+            )
+        };
+
+        let classified =
+            classify_remove_directory_error(protocol_error(), Path::new("/dir"), || true);
+
+        assert!(matches!(
+            classified,
+            ExecutionError::User(UserError::DirectoryNotEmpty(_))
+        ));
+
+        let classified =
+            classify_remove_directory_error(protocol_error(), Path::new("/dir"), || false);
+
+        assert!(matches!(
+            classified,
+            ExecutionError::User(UserError::Failure(_))
+        ));
+
+        let permission_error = ssh2::Error::new(
+            ssh2::ErrorCode::SFTP(SFTP_PERMISSION_DENIED),
+            "Permission denied",
+        );
+        let classified =
+            classify_remove_directory_error(permission_error, Path::new("/dir"), || {
+                panic!("entries must not be checked")
+            });
+
+        assert!(matches!(
+            classified,
+            ExecutionError::User(UserError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn only_session_errors_need_a_new_session() {
+        let session_error = |code| ssh2::Error::new(ssh2::ErrorCode::Session(code), "Session");
+
+        assert!(is_session_failure(&session_error(-7)));
+        assert!(!is_session_failure(&session_error(LIBSSH2_ERROR_TIMEOUT)));
+        assert!(!is_session_failure(&session_error(
+            LIBSSH2_ERROR_SFTP_PROTOCOL
+        )));
+        assert!(!is_session_failure(&ssh2::Error::new(
+            ssh2::ErrorCode::SFTP(SFTP_NO_SUCH_FILE),
+            "No such file"
+        )));
+    }
+}
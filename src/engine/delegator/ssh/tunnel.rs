@@ -0,0 +1,155 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+
+use rustix::event::{PollFd, PollFlags};
+use ssh2::{BlockDirections, Channel, Session};
+
+use super::SshError;
+use crate::{error::ErrorReport, logger::Logger};
+
+#[derive(thiserror::Error, Debug)]
+#[error("Lost the tunnel through jump host {jump_host} to {target}")]
+pub struct TunnelForwardError {
+    jump_host: SocketAddr,
+    target: SocketAddr,
+    #[source]
+    source: SshError,
+}
+
+/// The jump host connection a tunnel is opened through.
+pub struct JumpConnection {
+    pub session: Session,
+    /// Socket of `session`, used to wait for it to become ready
+    pub socket: OwnedFd,
+    pub address: SocketAddr,
+}
+
+/// Opens a `direct-tcpip` channel from the jump host to `target` and returns
+/// a local socket connected to it.
+///
+/// libssh2 sessions need a socket, so a background thread forwards data
+/// between the channel and the other end of a socket pair. The thread owns
+/// the jump host session and ends once either side is closed. A failure of
+/// the forwarding is logged as a warning.
+pub fn open(
+    jump: JumpConnection,
+    target: SocketAddr,
+    logger: Logger,
+) -> Result<UnixStream, SshError> {
+    let channel =
+        jump.session
+            .channel_direct_tcpip(&target.ip().to_string(), target.port(), None)?;
+    let (local, forwarded) = UnixStream::pair()?;
+
+    std::thread::spawn(move || {
+        if let Err(source) = forward(&jump.session, &jump.socket, channel, forwarded) {
+            logger.warn(&ErrorReport::describe(&TunnelForwardError {
+                jump_host: jump.address,
+                target,
+                source,
+            }));
+        }
+    });
+
+    Ok(local)
+}
+
+fn forward(
+    session: &Session,
+    session_socket: &OwnedFd,
+    mut channel: Channel,
+    mut socket: UnixStream,
+) -> Result<(), SshError> {
+    session.set_blocking(false);
+    socket.set_nonblocking(true)?;
+
+    let mut buffer = [0u8; 32 * 1024];
+
+    loop {
+        let mut transferred = false;
+
+        match socket.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes_read) => {
+                write_all(&mut channel, &buffer[..bytes_read], || {
+                    wait(&[(session_socket.as_fd(), session_events(session))])
+                })?;
+                transferred = true;
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error.into()),
+        }
+
+        match channel.read(&mut buffer) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(bytes_read) => {
+                write_all(&mut &socket, &buffer[..bytes_read], || {
+                    wait(&[(socket.as_fd(), PollFlags::OUT)])
+                })?;
+                transferred = true;
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error.into()),
+        }
+
+        if !transferred {
+            wait(&[
+                (socket.as_fd(), PollFlags::IN),
+                (session_socket.as_fd(), session_events(session)),
+            ])?;
+        }
+    }
+
+    _ = channel.send_eof();
+    _ = channel.close();
+
+    Ok(())
+}
+
+/// Socket events the session is waiting for. Incoming data is always of
+/// interest, as the channel is read from whenever the session socket is
+/// readable.
+fn session_events(session: &Session) -> PollFlags {
+    match session.block_directions() {
+        BlockDirections::Outbound | BlockDirections::Both => PollFlags::IN | PollFlags::OUT,
+        BlockDirections::Inbound | BlockDirections::None => PollFlags::IN,
+    }
+}
+
+/// Blocks until one of the sockets is ready for the given events.
+fn wait(sockets: &[(std::os::fd::BorrowedFd<'_>, PollFlags)]) -> Result<(), std::io::Error> {
+    let mut poll_fds = sockets
+        .iter()
+        .map(|(fd, events)| PollFd::new(fd, *events))
+        .collect::<Vec<_>>();
+
+    loop {
+        match rustix::event::poll(&mut poll_fds, None) {
+            Ok(_) => return Ok(()),
+            Err(rustix::io::Errno::INTR) => continue,
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+/// Writes all of `data` to a non-blocking writer, calling `wait` whenever
+/// the writer is not ready.
+fn write_all(
+    writer: &mut impl Write,
+    mut data: &[u8],
+    wait: impl Fn() -> Result<(), std::io::Error>,
+) -> Result<(), std::io::Error> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(bytes_written) => data = &data[bytes_written..],
+            Err(error) if error.kind() == ErrorKind::WouldBlock => wait()?,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
@@ -1,18 +1,24 @@
 use std::path::PathBuf;
 
+use indexmap::IndexMap;
+
 use crate::{
     memory::{
-        SharedMemory, target_groups::TargetGroupsMemory, target_systems::TargetSystemsMemory,
-        tasks::TasksMemory,
+        SharedMemory, config::ConfigMemory, target_groups::TargetGroupsMemory,
+        target_systems::TargetSystemsMemory, tasks::TasksMemory,
     },
     progress::ProgressContext,
 };
 
 mod arc;
 mod env;
+mod fmt;
 mod format;
 mod host;
+mod inventory;
 mod log;
+mod secrets;
+mod shell;
 mod targets;
 mod tasks;
 mod template;
@@ -20,40 +26,59 @@ mod template;
 pub struct Modules {
     template: template::Template,
     format: format::Format,
+    fmt: fmt::Fmt,
     targets: targets::TargetsTable,
     tasks: tasks::TasksTable,
     log: log::Log,
     env: env::Env,
+    secrets: secrets::Secrets,
+    shell: shell::Shell,
     host: host::Host,
+    inventory: inventory::Inventory,
     arc: arc::Arc,
 }
 
 impl Modules {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         target_systems: SharedMemory<TargetSystemsMemory>,
         target_groups: SharedMemory<TargetGroupsMemory>,
         tasks: SharedMemory<TasksMemory>,
+        config: SharedMemory<ConfigMemory>,
         progress: ProgressContext,
+        vars: IndexMap<String, String>,
         root_path: PathBuf,
         home_path: PathBuf,
     ) -> Self {
         let format = format::Format;
+        let fmt = fmt::Fmt;
         let targets = targets::TargetsTable::new(target_groups, target_systems.clone());
+        let inventory = inventory::Inventory::new(
+            root_path.clone(),
+            targets.systems.clone(),
+            targets.groups.clone(),
+        );
         let tasks = tasks::TasksTable::new(tasks);
-        let template = template::Template::new();
+        let template = template::Template::new(progress.clone());
         let env = env::Env;
+        let secrets = secrets::Secrets::new(root_path.clone());
+        let shell = shell::Shell;
         let host = host::Host::new(progress.clone());
         let log = log::Log::new(progress);
-        let arc = arc::Arc::new(root_path, home_path);
+        let arc = arc::Arc::new(root_path, home_path, vars, config);
 
         Self {
             format,
+            fmt,
             targets,
             tasks,
             template,
             log,
             env,
+            secrets,
+            shell,
             host,
+            inventory,
             arc,
         }
     }
@@ -62,9 +87,13 @@ impl Modules {
 impl MountToGlobals for Modules {
     fn mount_to_globals(self, lua: &mut mlua::Lua) -> Result<(), mlua::Error> {
         self.format.mount_to_globals(lua)?;
+        self.fmt.mount_to_globals(lua)?;
         self.targets.mount_to_globals(lua)?;
+        self.inventory.mount_to_globals(lua)?;
         self.tasks.mount_to_globals(lua)?;
         self.env.mount_to_globals(lua)?;
+        self.secrets.mount_to_globals(lua)?;
+        self.shell.mount_to_globals(lua)?;
         self.template.mount_to_globals(lua)?;
         self.log.mount_to_globals(lua)?;
 
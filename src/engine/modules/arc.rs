@@ -1,19 +1,71 @@
 use std::path::PathBuf;
 
-use mlua::UserData;
+use indexmap::IndexMap;
+use mlua::{FromLua, UserData};
 
-use crate::engine::modules::MountToGlobals;
+use crate::{
+    engine::{modules::MountToGlobals, readonly::set_readonly},
+    error::{ErrorReport, MutexLockError},
+    memory::{
+        SharedMemory,
+        config::{Config, ConfigMemory},
+    },
+    redact,
+};
+
+use super::targets::systems::parse_host_key_check;
 
 pub struct Arc {
     root_path: PathBuf,
     home_path: PathBuf,
+    vars: IndexMap<String, String>,
+    config_memory: SharedMemory<ConfigMemory>,
 }
 
 impl Arc {
-    pub fn new(root_path: PathBuf, home_path: PathBuf) -> Self {
+    pub fn new(
+        root_path: PathBuf,
+        home_path: PathBuf,
+        vars: IndexMap<String, String>,
+        config_memory: SharedMemory<ConfigMemory>,
+    ) -> Self {
         Self {
             root_path,
             home_path,
+            vars,
+            config_memory,
+        }
+    }
+}
+
+struct ArcConfig(Config);
+
+impl FromLua for ArcConfig {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Table(table) => {
+                let notify_webhook: Option<String> = table
+                    .get("notify_webhook")
+                    .or(Err(mlua::Error::runtime("\"notify_webhook\" is invalid")))?;
+                let retry_budget: Option<u32> = table
+                    .get("retry_budget")
+                    .or(Err(mlua::Error::runtime("\"retry_budget\" is invalid")))?;
+                let host_key_check = parse_host_key_check(&table)?;
+                let tasks_directory: Option<String> = table
+                    .get("tasks_directory")
+                    .or(Err(mlua::Error::runtime("\"tasks_directory\" is invalid")))?;
+
+                Ok(ArcConfig(Config {
+                    notify_webhook,
+                    retry_budget,
+                    host_key_check,
+                    tasks_directory,
+                }))
+            }
+            other => Err(mlua::Error::runtime(format!(
+                "{:?} is not a valid arc config",
+                other.type_name()
+            ))),
         }
     }
 }
@@ -26,6 +78,36 @@ impl UserData for Arc {
         fields.add_field_method_get("home_path", |_, this| {
             Ok(this.home_path.to_string_lossy().to_string())
         });
+        fields.add_field_method_get("vars", |lua, this| {
+            let vars_table = lua.create_table_from(this.vars.clone())?;
+
+            set_readonly(lua, vars_table).map_err(|error| {
+                mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+            })
+        });
+        fields.add_field_method_get("config", |lua, this| {
+            let config_memory = this.config_memory.clone();
+
+            lua.create_function(move |_, ArcConfig(config)| {
+                config_memory
+                    .lock()
+                    .map_err(|_| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(MutexLockError).build_report(),
+                        )
+                    })?
+                    .update(config);
+
+                Ok(())
+            })
+        });
+        fields.add_field_method_get("redact", |lua, _| {
+            lua.create_function(|_, value: String| {
+                redact::register(&value);
+
+                Ok(value)
+            })
+        });
     }
 }
 
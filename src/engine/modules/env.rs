@@ -1,6 +1,10 @@
 use mlua::UserData;
 
-use crate::engine::modules::MountToGlobals;
+use crate::{engine::modules::MountToGlobals, error::ErrorReport};
+
+#[derive(Debug, thiserror::Error)]
+#[error("Environment variable {0:?} is not set")]
+pub struct MissingVariableError(String);
 
 pub struct Env;
 
@@ -8,11 +12,22 @@ impl Env {
     fn get(var_name: &str) -> Option<String> {
         std::env::var(var_name).ok()
     }
+
+    fn require(var_name: &str) -> Result<String, MissingVariableError> {
+        Self::get(var_name).ok_or_else(|| MissingVariableError(var_name.to_string()))
+    }
 }
 
 impl UserData for Env {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_function("get", |_, var_name: String| Ok(Self::get(&var_name)));
+        methods.add_function("get", |_, (var_name, default): (String, Option<String>)| {
+            Ok(Self::get(&var_name).or(default))
+        });
+        methods.add_function("require", |_, var_name: String| {
+            Self::require(&var_name).map_err(|error| {
+                mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+            })
+        });
     }
 }
 
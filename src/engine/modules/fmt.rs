@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use indicatif::HumanBytes;
+use mlua::UserData;
+
+use crate::{engine::modules::MountToGlobals, progress::format_duration};
+
+pub struct Fmt;
+
+impl Fmt {
+    fn bytes(bytes: f64) -> Result<String, mlua::Error> {
+        if !bytes.is_finite() || bytes < 0.0 {
+            return Err(mlua::Error::runtime(format!(
+                "Invalid byte count: {}. Expected a non-negative number",
+                bytes
+            )));
+        }
+
+        Ok(HumanBytes(bytes as u64).to_string())
+    }
+
+    fn duration(seconds: f64) -> Result<String, mlua::Error> {
+        let duration = Duration::try_from_secs_f64(seconds).map_err(|_| {
+            mlua::Error::runtime(format!(
+                "Invalid duration: {}. Expected a non-negative number of seconds",
+                seconds
+            ))
+        })?;
+
+        Ok(format_duration(duration))
+    }
+}
+
+impl UserData for Fmt {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("bytes", |_, bytes: f64| Self::bytes(bytes));
+        methods.add_function("duration", |_, seconds: f64| Self::duration(seconds));
+    }
+}
+
+impl MountToGlobals for Fmt {
+    fn mount_to_globals(self, lua: &mut mlua::Lua) -> Result<(), mlua::Error> {
+        let globals = lua.globals();
+        globals.set("fmt", self)?;
+
+        Ok(())
+    }
+}
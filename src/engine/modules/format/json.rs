@@ -32,9 +32,27 @@ impl Json {
     fn decode(lua: &mlua::Lua, input: String) -> Result<mlua::Value, DecodeError> {
         Ok(lua.to_value(&serde_json::from_str::<serde_json::Value>(&input)?)?)
     }
+
+    /// Marks `table` as an array, so that it is encoded as `[]` when empty.
+    /// Decoded arrays are marked the same way.
+    fn array(lua: &mlua::Lua, table: Option<mlua::Table>) -> mlua::Result<mlua::Table> {
+        let table = match table {
+            Some(table) => table,
+            None => lua.create_table()?,
+        };
+        table.set_metatable(Some(lua.array_metatable()))?;
+
+        Ok(table)
+    }
 }
 
 impl UserData for Json {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        // Decoded `null` values, which are distinct from `nil` so that they
+        // are kept in tables
+        fields.add_field("null", mlua::Value::NULL);
+    }
+
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_function("encode", |_, value: mlua::Value| {
             Self::encode(value).map_err(|error| {
@@ -48,6 +66,10 @@ impl UserData for Json {
             })
         });
 
+        methods.add_function("array", |lua, table: Option<mlua::Table>| {
+            Self::array(lua, table)
+        });
+
         methods.add_function("decode", |lua, input: FileContentOrString| {
             let input = input.into_string().map_err(|error| {
                 mlua::Error::RuntimeError(
@@ -61,3 +83,26 @@ impl UserData for Json {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    #[test]
+    fn nested_values_arrays_and_null_survive_a_round_trip() {
+        let lua = mlua::Lua::new();
+        let input = r#"{"id":null,"mounts":[],"labels":{},"ports":[80,{"host":[443]}]}"#;
+
+        let value = Json::decode(&lua, input.to_string()).unwrap();
+        let id: mlua::Value = value.as_table().unwrap().get("id").unwrap();
+
+        assert_eq!(id, mlua::Value::NULL);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&Json::encode(value).unwrap()).unwrap(),
+            serde_json::from_str::<serde_json::Value>(input).unwrap()
+        );
+
+        let empty = Json::array(&lua, None).unwrap();
+        assert_eq!(Json::encode(mlua::Value::Table(empty)).unwrap(), "[]");
+    }
+}
@@ -23,7 +23,11 @@ enum DecodeError {
 
 impl Toml {
     fn encode(value: mlua::Value) -> Result<String, EncodeError> {
-        Ok(toml::to_string(&serde_json::to_value(&value)?)?)
+        // Converting to a TOML value turns decoded datetimes, which are
+        // tables with a special key, back into datetimes
+        let value: toml::Value = serde_json::from_value(serde_json::to_value(&value)?)?;
+
+        Ok(toml::to_string(&value)?)
     }
 
     fn decode(lua: &mlua::Lua, input: String) -> Result<mlua::Value, DecodeError> {
@@ -52,3 +56,28 @@ impl UserData for Toml {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Toml;
+
+    #[test]
+    fn decoded_documents_are_encoded_with_the_same_types() {
+        let lua = mlua::Lua::new();
+        let input = "port = 8080\n\
+                     ratio = 1.5\n\
+                     released = 1979-05-27T07:32:00Z\n\
+                     \n\
+                     [server]\n\
+                     hosts = [\"a\", \"b\"]\n\
+                     enabled = true\n";
+
+        let value = Toml::decode(&lua, input.to_string()).unwrap();
+        let encoded = Toml::encode(value).unwrap();
+
+        assert_eq!(
+            toml::from_str::<toml::Value>(&encoded).unwrap(),
+            toml::from_str::<toml::Value>(input).unwrap()
+        );
+    }
+}
@@ -1,12 +1,12 @@
-use std::{panic::resume_unwind, path::PathBuf};
+use std::path::PathBuf;
 
 use mlua::UserData;
 
 use crate::{
     engine::{
         delegator::{
-            error::{FfiError, FfiPanicError},
-            executor::Executor,
+            error::FfiError,
+            executor::{CommandOptions, Executor, RawCommandResult},
             operator::FileSystemOperator,
         },
         modules::MountToGlobals,
@@ -32,14 +32,37 @@ impl Host {
 
 impl UserData for Host {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method("run_command", |_, this, command: String| {
-            let result = this
-                .executor
-                .run_command(command)
-                .unwrap_or_else(|error| resume_unwind(Box::new(FfiPanicError(Box::new(error)))));
+        methods.add_method(
+            "run_command",
+            |_, this, (command, options): (String, CommandOptions)| {
+                let result = this
+                    .executor
+                    .run_guarded_command(command, &options, &this.file_system_operator)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })?;
 
-            Ok(result)
-        });
+                Ok(result)
+            },
+        );
+
+        methods.add_method(
+            "run_command_bytes",
+            |_, this, (command, options): (String, CommandOptions)| {
+                let result = this
+                    .executor
+                    .run_guarded_command(command, &options, &this.file_system_operator)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })?;
+
+                Ok(RawCommandResult(result))
+            },
+        );
 
         methods.add_method("file", |_, this, path: PathBuf| {
             this.file_system_operator.file(&path).map_err(|error| {
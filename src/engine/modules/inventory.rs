@@ -0,0 +1,147 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use mlua::{FromLua, LuaSerdeExt, UserData};
+use serde::Deserialize;
+
+use crate::{
+    engine::modules::{
+        MountToGlobals,
+        targets::{
+            groups::{GroupAdditionError, GroupConfig, GroupsTable},
+            systems::{SystemAdditionError, SystemDefinition, SystemsTable},
+        },
+    },
+    error::ErrorReport,
+};
+
+/// Schema of an inventory file. Entries use the same fields as the tables
+/// assigned to `targets.systems` and `targets.groups`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InventoryFile {
+    #[serde(default)]
+    systems: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    groups: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryLoadError {
+    #[error("Failed to read inventory {0:?}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("Inventory {0:?} must be a .yaml, .yml or .toml file")]
+    UnsupportedFormat(PathBuf),
+    #[error("Failed to parse inventory {0:?}")]
+    Yaml(PathBuf, #[source] serde_yaml::Error),
+    #[error("Failed to parse inventory {0:?}")]
+    Toml(PathBuf, #[source] toml::de::Error),
+    #[error("Invalid system {name:?} in inventory {path:?}")]
+    InvalidSystem {
+        path: PathBuf,
+        name: String,
+        #[source]
+        source: mlua::Error,
+    },
+    #[error("Invalid group {name:?} in inventory {path:?}")]
+    InvalidGroup {
+        path: PathBuf,
+        name: String,
+        #[source]
+        source: mlua::Error,
+    },
+    #[error(transparent)]
+    SystemAddition(#[from] SystemAdditionError),
+    #[error(transparent)]
+    GroupAddition(#[from] GroupAdditionError),
+}
+
+#[derive(Clone)]
+pub struct Inventory {
+    root_path: PathBuf,
+    systems: SystemsTable,
+    groups: GroupsTable,
+}
+
+impl Inventory {
+    pub fn new(root_path: PathBuf, systems: SystemsTable, groups: GroupsTable) -> Self {
+        Self {
+            root_path,
+            systems,
+            groups,
+        }
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<InventoryFile, InventoryLoadError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(content)
+                .map_err(|error| InventoryLoadError::Yaml(path.to_path_buf(), error)),
+            Some("toml") => toml::from_str(content)
+                .map_err(|error| InventoryLoadError::Toml(path.to_path_buf(), error)),
+            _ => Err(InventoryLoadError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Registers the systems and groups of the inventory file at `path`,
+    /// relative to the project root.
+    fn load(&self, lua: &mlua::Lua, path: PathBuf) -> Result<(), InventoryLoadError> {
+        let content = std::fs::read_to_string(self.root_path.join(&path))
+            .map_err(|error| InventoryLoadError::Read(path.clone(), error))?;
+        let inventory = Self::parse(&path, &content)?;
+
+        for (name, value) in inventory.systems {
+            let config = lua
+                .to_value(&value)
+                .and_then(|value| SystemDefinition::from_lua(value, lua))
+                .map_err(|source| InventoryLoadError::InvalidSystem {
+                    path: path.clone(),
+                    name: name.clone(),
+                    source,
+                })?;
+
+            self.systems.add(name, config)?;
+        }
+
+        for (name, value) in inventory.groups {
+            let config = lua
+                .to_value(&value)
+                .and_then(|value| GroupConfig::from_lua(value, lua))
+                .map_err(|source| InventoryLoadError::InvalidGroup {
+                    path: path.clone(),
+                    name: name.clone(),
+                    source,
+                })?;
+
+            self.groups.add(name, config)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserData for Inventory {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("load", |lua, path: PathBuf| {
+            let inventory = lua
+                .app_data_ref::<Self>()
+                .expect("inventory unavailable in app data");
+
+            inventory.load(lua, path).map_err(|error| {
+                mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+            })
+        });
+    }
+}
+
+impl MountToGlobals for Inventory {
+    fn mount_to_globals(self, lua: &mut mlua::Lua) -> Result<(), mlua::Error> {
+        lua.set_app_data(self.clone());
+
+        let globals = lua.globals();
+        globals.set("inventory", self)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::{cell::OnceCell, path::PathBuf, rc::Rc};
+
+use mlua::UserData;
+
+use crate::{
+    engine::modules::MountToGlobals,
+    error::ErrorReport,
+    redact,
+    vault::{self, VAULT_FILE, VaultError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretRetrievalError {
+    #[error(
+        "Secret {name:?} is not defined, set {variable} or add it with `arc secrets set {name}`"
+    )]
+    Missing { name: String, variable: String },
+    #[error("Failed to load secret {name:?}")]
+    Vault {
+        name: String,
+        #[source]
+        source: VaultError,
+    },
+}
+
+#[derive(Clone)]
+pub struct Secrets {
+    vault_path: PathBuf,
+    /// The vault is only decrypted once a secret is requested
+    vault: Rc<OnceCell<vault::Secrets>>,
+}
+
+impl Secrets {
+    pub fn new(root_path: PathBuf) -> Self {
+        Self {
+            vault_path: root_path.join(VAULT_FILE),
+            vault: Rc::new(OnceCell::new()),
+        }
+    }
+
+    /// Name of the environment variable that overrides the secret `name`.
+    fn variable_name(name: &str) -> String {
+        format!("ARC_SECRET_{}", name.to_uppercase())
+    }
+
+    fn get(&self, name: &str) -> Result<String, SecretRetrievalError> {
+        let variable = Self::variable_name(name);
+
+        if let Ok(value) = std::env::var(&variable) {
+            return Ok(value);
+        }
+
+        let vault = match self.vault.get() {
+            Some(vault) => vault,
+            None => {
+                let vault = vault::load(&self.vault_path).map_err(|source| {
+                    SecretRetrievalError::Vault {
+                        name: name.to_string(),
+                        source,
+                    }
+                })?;
+
+                self.vault.get_or_init(|| vault)
+            }
+        };
+
+        vault
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SecretRetrievalError::Missing {
+                name: name.to_string(),
+                variable,
+            })
+    }
+}
+
+impl UserData for Secrets {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("get", |lua, name: String| {
+            let secrets = lua
+                .app_data_ref::<Self>()
+                .expect("secrets unavailable in app data");
+
+            secrets
+                .get(&name)
+                .inspect(|value| redact::register(value))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+                })
+        });
+    }
+}
+
+impl MountToGlobals for Secrets {
+    fn mount_to_globals(self, lua: &mut mlua::Lua) -> Result<(), mlua::Error> {
+        lua.set_app_data(self.clone());
+
+        let globals = lua.globals();
+        globals.set("secrets", self)?;
+
+        Ok(())
+    }
+}
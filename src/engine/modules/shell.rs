@@ -0,0 +1,90 @@
+use mlua::UserData;
+
+use crate::engine::{delegator::shell::quote, modules::MountToGlobals};
+
+pub struct Shell;
+
+impl Shell {
+    /// Quotes each argument and joins them with spaces, so that the shell
+    /// splits the result into exactly these arguments.
+    fn join(arguments: &[String]) -> String {
+        arguments
+            .iter()
+            .map(|argument| quote(argument))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl UserData for Shell {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("quote", |_, value: String| Ok(quote(&value)));
+        methods.add_function("join", |_, arguments: Vec<String>| {
+            Ok(Self::join(&arguments))
+        });
+    }
+}
+
+impl MountToGlobals for Shell {
+    fn mount_to_globals(self, lua: &mut mlua::Lua) -> Result<(), mlua::Error> {
+        let globals = lua.globals();
+        globals.set("shell", self)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::{Shell, quote};
+
+    /// Arguments the shell splits the command line `arguments` into.
+    fn split_by_shell(arguments: &str) -> Vec<String> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s\\0' {}", arguments))
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn quoted_values_reach_the_command_unchanged() {
+        let values = [
+            "plain",
+            "with spaces",
+            "it's",
+            "'''",
+            "$HOME",
+            "`id`",
+            "$(id)",
+            "a\"b\\c",
+            "semi;colon && pipe |",
+            "new\nline",
+            "",
+        ];
+
+        for value in values {
+            assert_eq!(split_by_shell(&quote(value)), vec![value.to_string()]);
+        }
+    }
+
+    #[test]
+    fn joined_arguments_are_split_into_the_same_list() {
+        let arguments = vec![
+            "cp".to_string(),
+            "my file's copy".to_string(),
+            "$TARGET `dir`".to_string(),
+            "".to_string(),
+        ];
+
+        assert_eq!(split_by_shell(&Shell::join(&arguments)), arguments);
+    }
+}
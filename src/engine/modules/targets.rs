@@ -1,12 +1,14 @@
 use groups::GroupsTable;
-use mlua::IntoLua;
+use mlua::{IntoLua, LuaSerdeExt};
 use systems::SystemsTable;
 
 use crate::{
     engine::{modules::MountToGlobals, readonly::set_readonly},
     error::ErrorReport,
     memory::{
-        SharedMemory, target_groups::TargetGroupsMemory, target_systems::TargetSystemsMemory,
+        SharedMemory,
+        target_groups::TargetGroupsMemory,
+        target_systems::{TargetSystemsMemory, Vars},
     },
 };
 
@@ -30,6 +32,22 @@ impl TargetsTable {
     }
 }
 
+/// Reads the optional `vars` table of a system or group definition.
+pub fn parse_vars(table: &mlua::Table, lua: &mlua::Lua) -> mlua::Result<Vars> {
+    match table.get::<mlua::Value>("vars") {
+        Ok(mlua::Value::Nil) => Ok(Vars::new()),
+        Ok(vars @ mlua::Value::Table(_)) => lua.from_value(vars).map_err(|error| {
+            mlua::Error::runtime(format!(
+                "\"vars\" is invalid - must be a table of names to values: {}",
+                error
+            ))
+        }),
+        _ => Err(mlua::Error::runtime(
+            "\"vars\" is invalid - must be a table of names to values",
+        )),
+    }
+}
+
 impl IntoLua for TargetsTable {
     fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
         let targets_table = lua.create_table()?;
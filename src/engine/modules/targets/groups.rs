@@ -1,6 +1,7 @@
 use mlua::{FromLua, IntoLua, LuaSerdeExt, MetaMethod, UserData};
 use serde::{Deserialize, Serialize};
 
+use super::parse_vars;
 use crate::{
     engine::readonly::set_readonly,
     error::{ErrorReport, MutexLockError},
@@ -9,12 +10,15 @@ use crate::{
         target_groups::{
             TargetGroup, TargetGroupAdditionError, TargetGroupRetrievalError, TargetGroupsMemory,
         },
+        target_systems::Vars,
     },
 };
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct GroupConfig {
     pub members: Vec<String>,
+    pub vars: Vars,
+    pub confirm: bool,
 }
 
 impl FromLua for GroupConfig {
@@ -29,7 +33,18 @@ impl FromLua for GroupConfig {
                     "\"members\" is invalid".to_string(),
                 )))?;
 
-                Ok(GroupConfig { members })
+                let vars = parse_vars(&table, lua)?;
+
+                let confirm = table
+                    .get::<Option<bool>>("confirm")
+                    .or(Err(mlua::Error::runtime("\"confirm\" is invalid")))?
+                    .unwrap_or(false);
+
+                Ok(GroupConfig {
+                    members,
+                    vars,
+                    confirm,
+                })
             }
             mlua::Value::Function(_)
             | mlua::Value::Nil
@@ -62,6 +77,8 @@ impl IntoLua for TargetGroup {
         })?;
 
         config_table.set("members", members_table)?;
+        config_table.set("vars", lua.to_value(&self.vars)?)?;
+        config_table.set("confirm", self.confirm)?;
         let config_table = set_readonly(lua, config_table).map_err(|error| {
             mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
         })?;
@@ -84,17 +101,20 @@ pub enum GroupRetrievalError {
     TargetGroupRetrieval(#[from] TargetGroupRetrievalError),
 }
 
+#[derive(Clone)]
 pub struct GroupsTable {
     pub groups_memory: SharedMemory<TargetGroupsMemory>,
 }
 
 impl GroupsTable {
-    fn add(&self, name: String, config: GroupConfig) -> Result<(), GroupAdditionError> {
+    pub fn add(&self, name: String, config: GroupConfig) -> Result<(), GroupAdditionError> {
         let mut groups_memory = self.groups_memory.lock().map_err(|_| MutexLockError)?;
 
         groups_memory.add(TargetGroup {
             name,
             members: config.members,
+            vars: config.vars,
+            confirm: config.confirm,
         })?;
 
         Ok(())
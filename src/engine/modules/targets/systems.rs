@@ -1,30 +1,104 @@
-use std::net::AddrParseError;
+use std::{net::AddrParseError, path::PathBuf, str::FromStr, time::Duration};
 
 use mlua::{FromLua, IntoLua, LuaSerdeExt, MetaMethod, UserData};
 use serde::Serialize;
 
+use super::parse_vars;
 use crate::{
     engine::readonly::set_readonly,
     error::{ErrorReport, MutexLockError},
     memory::{
         SharedMemory,
         target_systems::{
-            RemoteTargetSystem, TargetSystem, TargetSystemAdditionError, TargetSystemKind,
-            TargetSystemRetrievalError, TargetSystemsMemory,
+            DEFAULT_CONNECT_TIMEOUT, HostKeyCheck, RemoteTargetSystem, SshAlgorithms, SshPassword,
+            SshTimeouts, TargetSystem, TargetSystemAdditionError, TargetSystemKind,
+            TargetSystemRetrievalError, TargetSystemsMemory, Vars,
         },
     },
 };
 
 #[derive(Debug, Clone, Serialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum SystemConfig {
     Local,
     Remote {
         address: String,
         port: u16,
         user: String,
+        #[serde(skip)]
+        password: Option<SshPassword>,
+        private_key_path: Option<PathBuf>,
+        #[serde(skip)]
+        passphrase: Option<SshPassword>,
+        host_key_check: Option<HostKeyCheck>,
+        timeouts: SshTimeouts,
+        algorithms: SshAlgorithms,
+        jump_host: Option<Box<SystemConfig>>,
     },
 }
 
+impl SystemConfig {
+    fn into_target_system_kind(self) -> Result<TargetSystemKind, AddrParseError> {
+        Ok(match self {
+            SystemConfig::Local => TargetSystemKind::Local,
+            SystemConfig::Remote {
+                address,
+                port,
+                user,
+                password,
+                private_key_path,
+                passphrase,
+                host_key_check,
+                timeouts,
+                algorithms,
+                jump_host,
+            } => {
+                let jump_host = match jump_host.map(|jump_host| jump_host.into_target_system_kind())
+                {
+                    Some(Ok(TargetSystemKind::Remote(jump_host))) => Some(Box::new(jump_host)),
+                    Some(Ok(TargetSystemKind::Local)) | None => None,
+                    Some(Err(error)) => return Err(error),
+                };
+
+                TargetSystemKind::Remote(RemoteTargetSystem {
+                    address: address.parse()?,
+                    port,
+                    user,
+                    password,
+                    private_key_path,
+                    passphrase,
+                    host_key_check,
+                    timeouts,
+                    algorithms,
+                    jump_host,
+                })
+            }
+        })
+    }
+}
+
+/// A system assigned to `targets.systems`: its connection settings and
+/// variables.
+#[derive(Debug, Clone)]
+pub struct SystemDefinition {
+    pub config: SystemConfig,
+    pub vars: Vars,
+}
+
+impl FromLua for SystemDefinition {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        let vars = match &value {
+            mlua::Value::Table(table) => parse_vars(table, lua)?,
+            _ => Vars::new(),
+        };
+
+        Ok(SystemDefinition {
+            config: SystemConfig::from_lua(value, lua)?,
+            vars,
+        })
+    }
+}
+
 #[derive(Default)]
 enum SystemType {
     Local,
@@ -44,6 +118,33 @@ impl SystemType {
     }
 }
 
+pub fn parse_host_key_check(table: &mlua::Table) -> mlua::Result<Option<HostKeyCheck>> {
+    table
+        .get::<Option<String>>("host_key_check")
+        .or(Err(mlua::Error::runtime("\"host_key_check\" is invalid")))?
+        .map(|value| {
+            HostKeyCheck::from_str(&value).or(Err(mlua::Error::runtime(format!(
+                "Invalid host_key_check value: \"{}\". Expected \"strict\", \"accept_new\", or \"off\"",
+                value
+            ))))
+        })
+        .transpose()
+}
+
+fn parse_seconds(table: &mlua::Table, key: &str) -> mlua::Result<Option<Duration>> {
+    table
+        .get::<Option<f64>>(key)
+        .or(Err(mlua::Error::runtime(format!("\"{}\" is invalid", key))))?
+        .map(|seconds| match Duration::try_from_secs_f64(seconds) {
+            Ok(duration) if !duration.is_zero() => Ok(duration),
+            _ => Err(mlua::Error::runtime(format!(
+                "Invalid {} value: {}. Expected a positive number of seconds",
+                key, seconds
+            ))),
+        })
+        .transpose()
+}
+
 impl FromLua for SystemConfig {
     fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
         match value {
@@ -90,10 +191,73 @@ impl FromLua for SystemConfig {
                             .or(Err(mlua::Error::runtime("\"port\" is invalid")))?
                             .unwrap_or(22);
 
+                        let password = table
+                            .get::<Option<String>>("password")
+                            .or(Err(mlua::Error::runtime("\"password\" is invalid")))?
+                            .map(SshPassword::new);
+
+                        let private_key_path = table
+                            .get::<Option<String>>("private_key_path")
+                            .or(Err(mlua::Error::runtime("\"private_key_path\" is invalid")))?
+                            .map(PathBuf::from);
+
+                        let passphrase = table
+                            .get::<Option<String>>("passphrase")
+                            .or(Err(mlua::Error::runtime("\"passphrase\" is invalid")))?
+                            .map(SshPassword::new);
+
+                        let host_key_check = parse_host_key_check(&table)?;
+
+                        let timeouts = SshTimeouts {
+                            connect: parse_seconds(&table, "connect_timeout")?
+                                .unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+                            operation: parse_seconds(&table, "operation_timeout")?,
+                            keepalive_interval: parse_seconds(&table, "keepalive_interval")?,
+                        };
+
+                        if passphrase.is_some() && private_key_path.is_none() {
+                            return Err(mlua::Error::runtime(
+                                "\"passphrase\" requires \"private_key_path\"",
+                            ));
+                        }
+
+                        let algorithms = match table.get::<mlua::Value>("algorithms") {
+                            Ok(mlua::Value::Nil) => SshAlgorithms::default(),
+                            Ok(algorithms_field) => {
+                                lua.from_value(algorithms_field).map_err(|error| {
+                                    mlua::Error::runtime(format!(
+                                        "\"algorithms\" is invalid: {}",
+                                        error
+                                    ))
+                                })?
+                            }
+                            Err(_) => Err(mlua::Error::runtime("\"algorithms\" is invalid"))?,
+                        };
+
+                        let jump_host = match table
+                            .get::<Option<SystemConfig>>("jump_host")
+                            .map_err(|error| {
+                                mlua::Error::runtime(format!("\"jump_host\" is invalid: {}", error))
+                            })? {
+                            Some(SystemConfig::Local) => {
+                                return Err(mlua::Error::runtime(
+                                    "\"jump_host\" must be a remote system",
+                                ));
+                            }
+                            jump_host => jump_host.map(Box::new),
+                        };
+
                         Ok(SystemConfig::Remote {
                             address,
                             port,
                             user,
+                            password,
+                            private_key_path,
+                            passphrase,
+                            host_key_check,
+                            timeouts,
+                            algorithms,
+                            jump_host,
                         })
                     }
                 }
@@ -140,6 +304,7 @@ impl IntoLua for TargetSystem {
         config_table.set("address", address)?;
         config_table.set("port", port)?;
         config_table.set("user", user)?;
+        config_table.set("vars", lua.to_value(&self.vars)?)?;
 
         let config_table = set_readonly(lua, config_table).map_err(|error| {
             mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
@@ -164,28 +329,23 @@ pub enum SystemRetrievalError {
     TargetSystemRetrieval(#[from] TargetSystemRetrievalError),
 }
 
+#[derive(Clone)]
 pub struct SystemsTable {
     pub systems_memory: SharedMemory<TargetSystemsMemory>,
 }
 
 impl SystemsTable {
-    fn add(&self, name: String, config: SystemConfig) -> Result<(), SystemAdditionError> {
+    pub fn add(
+        &self,
+        name: String,
+        definition: SystemDefinition,
+    ) -> Result<(), SystemAdditionError> {
         let mut guard = self.systems_memory.lock().map_err(|_| MutexLockError)?;
 
         guard.add(TargetSystem {
             name,
-            kind: match config {
-                SystemConfig::Local => TargetSystemKind::Local,
-                SystemConfig::Remote {
-                    address,
-                    port,
-                    user,
-                } => TargetSystemKind::Remote(RemoteTargetSystem {
-                    address: address.parse()?,
-                    port,
-                    user,
-                }),
-            },
+            kind: definition.config.into_target_system_kind()?,
+            vars: definition.vars,
         })?;
 
         Ok(())
@@ -202,8 +362,8 @@ impl UserData for SystemsTable {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_meta_method(
             MetaMethod::NewIndex,
-            |_, this, (name, config): (String, SystemConfig)| {
-                this.add(name, config).map_err(|error| {
+            |_, this, (name, definition): (String, SystemDefinition)| {
+                this.add(name, definition).map_err(|error| {
                     mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
                 })
             },
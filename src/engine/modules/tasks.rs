@@ -1,5 +1,6 @@
-use std::{collections::HashSet, path::PathBuf, str::FromStr};
+use std::{collections::HashSet, path::PathBuf, str::FromStr, time::Duration};
 
+use indexmap::IndexMap;
 use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData};
 
 use crate::{
@@ -7,10 +8,37 @@ use crate::{
     error::{ErrorReport, MutexLockError},
     memory::{
         SharedMemory,
-        tasks::{OnFailBehavior, Task, TaskAdditionError, TaskRetrievalError, TasksMemory},
+        tasks::{
+            OnFailBehavior, ParamType, Serial, Task, TaskAdditionError, TaskParam,
+            TaskRetrievalError, TasksMemory,
+        },
     },
 };
 
+static INVALID_SERIAL_MESSAGE: &str =
+    "\"serial\" is invalid - must be a positive number of systems or a percentage like \"25%\"";
+
+fn parse_serial(table: &mlua::Table) -> mlua::Result<Option<Serial>> {
+    let invalid = || mlua::Error::runtime(INVALID_SERIAL_MESSAGE);
+
+    match table.get::<mlua::Value>("serial") {
+        Ok(mlua::Value::Nil) => Ok(None),
+        Ok(mlua::Value::Integer(count)) if count > 0 => Ok(Some(Serial::Count(count as usize))),
+        Ok(mlua::Value::Number(count)) if count > 0.0 && count.fract() == 0.0 => {
+            Ok(Some(Serial::Count(count as usize)))
+        }
+        Ok(mlua::Value::String(value)) => {
+            let value = value.to_str().map_err(|_| invalid())?;
+
+            match value.strip_suffix('%').map(|number| number.trim().parse()) {
+                Some(Ok(percentage @ 1..=100)) => Ok(Some(Serial::Percentage(percentage))),
+                _ => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TaskConfig {
     pub handler: mlua::Function,
@@ -20,6 +48,57 @@ pub struct TaskConfig {
     pub targets: HashSet<String>,
     pub requires: HashSet<String>,
     pub important: bool,
+    pub on_notify: bool,
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    pub serial: Option<Serial>,
+    pub params: IndexMap<String, TaskParam>,
+}
+
+struct TaskParamConfig(TaskParam);
+
+impl FromLua for TaskParamConfig {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Table(table) => {
+                let type_str: Option<String> = table
+                    .get("type")
+                    .or(Err(mlua::Error::runtime("\"type\" is invalid")))?;
+                let r#type = match type_str {
+                    Some(string) => ParamType::from_str(&string).or(Err(mlua::Error::runtime(format!(
+                        "Invalid type value: \"{}\". Expected \"string\", \"number\", \"integer\", or \"boolean\"",
+                        string
+                    ))))?,
+                    None => ParamType::default(),
+                };
+                let required = table
+                    .get::<Option<bool>>("required")
+                    .or(Err(mlua::Error::runtime("\"required\" is invalid")))?
+                    .unwrap_or(false);
+                let default = match table.get::<mlua::Value>("default")? {
+                    mlua::Value::Nil => None,
+                    value if r#type.accepts(&value) => Some(value),
+                    value => {
+                        return Err(mlua::Error::runtime(format!(
+                            "\"default\" is invalid: expected {}, got {}",
+                            r#type,
+                            value.type_name()
+                        )));
+                    }
+                };
+
+                Ok(TaskParamConfig(TaskParam {
+                    r#type,
+                    required,
+                    default,
+                }))
+            }
+            other => Err(mlua::Error::runtime(format!(
+                "{:?} is not a valid task parameter",
+                other.type_name()
+            ))),
+        }
+    }
 }
 
 impl FromLua for TaskConfig {
@@ -72,6 +151,44 @@ impl FromLua for TaskConfig {
                     .get::<Option<bool>>("important")
                     .or(Err(mlua::Error::runtime("\"important\" is invalid")))?
                     .unwrap_or(false);
+                let on_notify: bool = table
+                    .get::<Option<bool>>("on_notify")
+                    .or(Err(mlua::Error::runtime("\"on_notify\" is invalid")))?
+                    .unwrap_or(false);
+                let timeout = table
+                    .get::<Option<f64>>("timeout")
+                    .or(Err(mlua::Error::runtime("\"timeout\" is invalid")))?
+                    .map(|seconds| match Duration::try_from_secs_f64(seconds) {
+                        Ok(timeout) if !timeout.is_zero() => Ok(timeout),
+                        _ => Err(mlua::Error::runtime(format!(
+                            "Invalid timeout value: {}. Expected a positive number of seconds",
+                            seconds
+                        ))),
+                    })
+                    .transpose()?;
+                let retries = table
+                    .get::<Option<u32>>("retries")
+                    .or(Err(mlua::Error::runtime("\"retries\" is invalid")))?
+                    .unwrap_or(0);
+                let serial = parse_serial(&table)?;
+                let params = table
+                    .get::<Option<mlua::Table>>("params")
+                    .or(Err(mlua::Error::runtime("\"params\" is invalid")))?
+                    .map(|params_table| {
+                        params_table
+                            .pairs::<String, TaskParamConfig>()
+                            .map(|pair| pair.map(|(name, TaskParamConfig(param))| (name, param)))
+                            .collect::<mlua::Result<IndexMap<_, _>>>()
+                            .map(|mut params| {
+                                params.sort_keys();
+                                params
+                            })
+                    })
+                    .transpose()
+                    .map_err(|error| {
+                        mlua::Error::runtime(format!("\"params\" is invalid: {}", error))
+                    })?
+                    .unwrap_or_default();
 
                 Ok(TaskConfig {
                     handler,
@@ -81,6 +198,11 @@ impl FromLua for TaskConfig {
                     targets,
                     requires,
                     important,
+                    on_notify,
+                    timeout,
+                    retries,
+                    serial,
+                    params,
                 })
             }
             mlua::Value::Function(_)
@@ -109,6 +231,10 @@ impl IntoLua for Task {
         task_table.set("tags", self.tags.into_iter().collect::<Vec<_>>())?;
         task_table.set("requires", self.requires.into_iter().collect::<Vec<_>>())?;
         task_table.set("important", self.important)?;
+        task_table.set("on_notify", self.on_notify)?;
+        task_table.set("timeout", self.timeout.map(|timeout| timeout.as_secs_f64()))?;
+        task_table.set("retries", self.retries)?;
+        task_table.set("serial", self.serial.map(|serial| serial.to_string()))?;
         task_table.set("result", self.result)?;
         task_table.set("handler", self.handler)?;
 
@@ -165,9 +291,15 @@ impl TasksTable {
             targets: config.targets,
             requires: config.requires,
             important: config.important,
+            on_notify: config.on_notify,
+            timeout: config.timeout,
+            retries: config.retries,
+            serial: config.serial,
+            params: config.params,
             result: None,
             state: None,
             error: None,
+            duration: None,
         })?;
 
         Ok(())
@@ -1,22 +1,228 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
-use mlua::UserData;
+use mlua::{FromLua, IntoLua, LuaSerdeExt, UserData, UserDataRef};
 use tera::Tera;
 use thiserror::Error;
 
 use crate::{
     engine::{
-        delegator::error::FfiError, modules::MountToGlobals,
-        objects::file_content::FileContentOrString,
+        delegator::{
+            error::FfiError,
+            operator::{
+                CreateDirectoryError, DirectoryWalkError, FileReadError, FileStreamError,
+                FileSystemOperator, FileWriteError, MetadataType, SetPermissionsError,
+                WriteOptions,
+            },
+        },
+        modules::MountToGlobals,
+        objects::{
+            file_content::{FileContent, FileContentOrString},
+            system::System,
+        },
+        readonly::set_readonly,
+        selection::matches_pattern,
     },
     error::{ErrorReport, MutexLockError},
+    progress::ProgressContext,
 };
 
+mod filters;
+
+/// Name of the argument that holds the system of the running task
+static SYSTEM_ARGUMENT: &str = "system";
+
 type SharedTemplatingEngine = Arc<Mutex<Tera>>;
+type LuaFilters = Rc<RefCell<HashMap<String, mlua::Function>>>;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Template {
     tera: SharedTemplatingEngine,
+    /// Reads template files from the machine arc runs on
+    file_system_operator: FileSystemOperator,
+    /// Filters registered with `template.add_filter`, called by [`LuaFilter`]
+    filters: LuaFilters,
+}
+
+/// Lua state and filters of the render in progress on this thread.
+struct RenderScope {
+    lua: mlua::Lua,
+    filters: LuaFilters,
+}
+
+thread_local! {
+    static RENDER_SCOPE: RefCell<Option<RenderScope>> = const { RefCell::new(None) };
+}
+
+/// Tera filters must be `Send + Sync`, which Lua functions are not. The
+/// filter therefore only knows its name and looks up the function in the
+/// [`RenderScope`] of the current render.
+struct LuaFilter {
+    name: String,
+}
+
+impl tera::Filter for LuaFilter {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        RENDER_SCOPE
+            .with_borrow(|scope| {
+                let scope = scope.as_ref().ok_or_else(|| {
+                    mlua::Error::runtime("filter called outside of a template render")
+                })?;
+                let function = scope
+                    .filters
+                    .borrow()
+                    .get(&self.name)
+                    .cloned()
+                    .ok_or_else(|| mlua::Error::runtime("filter is not defined"))?;
+
+                let result: mlua::Value =
+                    function.call((scope.lua.to_value(value)?, scope.lua.to_value(args)?))?;
+
+                scope.lua.from_value::<tera::Value>(result)
+            })
+            // Lua errors are not `Send`, so only their message is kept. Tera
+            // adds the name of the failed filter.
+            .map_err(|error: mlua::Error| tera::Error::msg(error))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub autoescape: bool,
+}
+
+impl FromLua for RenderOptions {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(table) => {
+                let autoescape = table
+                    .get::<Option<bool>>("autoescape")
+                    .or(Err(mlua::Error::runtime("\"autoescape\" is invalid")))?
+                    .unwrap_or(false);
+
+                Ok(Self { autoescape })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "RenderOptions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderTreeOptions {
+    pub render: RenderOptions,
+    /// Patterns of files that are copied without rendering them
+    pub skip: Vec<String>,
+}
+
+impl FromLua for RenderTreeOptions {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Self::default()),
+            mlua::Value::Table(ref table) => {
+                let skip = table
+                    .get::<Option<Vec<String>>>("skip")
+                    .or(Err(mlua::Error::runtime("\"skip\" is invalid")))?
+                    .unwrap_or_default();
+
+                Ok(Self {
+                    render: RenderOptions::from_lua(value, lua)?,
+                    skip,
+                })
+            }
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "RenderTreeOptions".to_string(),
+                message: Some("expected table".to_string()),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TemplateTreeResult {
+    pub files_rendered: usize,
+    /// Files matching a skip pattern
+    pub files_copied: usize,
+}
+
+impl IntoLua for TemplateTreeResult {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        let result_table = lua.create_table()?;
+
+        result_table.set("files_rendered", self.files_rendered)?;
+        result_table.set("files_copied", self.files_copied)?;
+
+        let result_table = set_readonly(lua, result_table).map_err(|error| {
+            mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+        })?;
+
+        Ok(mlua::Value::Table(result_table))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to render template {path:?}")]
+pub struct TemplateFileRenderError {
+    path: PathBuf,
+    #[source]
+    source: TemplateRenderError,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Template {0:?} is not valid UTF-8, add it to \"skip\" to copy it unchanged")]
+pub struct TemplateEncodingError(PathBuf);
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub enum TemplateTreeFailure {
+    Walk(#[from] DirectoryWalkError),
+    CreateDirectory(#[from] CreateDirectoryError),
+    Read(#[from] FileReadError),
+    Encoding(#[from] TemplateEncodingError),
+    Render(#[from] TemplateFileRenderError),
+    Write(#[from] FileWriteError),
+    Stream(#[from] FileStreamError),
+    SetPermissions(#[from] SetPermissionsError),
+}
+
+impl TemplateTreeFailure {
+    /// Like [`FfiError::enforce_ffi_boundary`], which cannot be implemented
+    /// because render errors may hold Lua errors that are not `Send`.
+    /// Template errors are always user errors.
+    fn enforce_ffi_boundary(self) -> Self {
+        match self {
+            Self::Walk(error) => Self::Walk(error.enforce_ffi_boundary()),
+            Self::CreateDirectory(error) => Self::CreateDirectory(error.enforce_ffi_boundary()),
+            Self::Read(error) => Self::Read(error.enforce_ffi_boundary()),
+            Self::Write(error) => Self::Write(error.enforce_ffi_boundary()),
+            Self::Stream(error) => Self::Stream(error.enforce_ffi_boundary()),
+            Self::SetPermissions(error) => Self::SetPermissions(error.enforce_ffi_boundary()),
+            Self::Encoding(_) | Self::Render(_) => self,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to render template directory {source_path:?} to {target_path:?}")]
+pub struct TemplateTreeError {
+    source_path: PathBuf,
+    target_path: PathBuf,
+    #[source]
+    source: Box<TemplateTreeFailure>,
 }
 
 #[derive(Debug, Error)]
@@ -44,23 +250,184 @@ pub struct InvalidArgumentNameError(String);
 pub struct InvalidArgumentTypeError(String);
 
 impl Template {
-    pub fn new() -> Self {
+    pub fn new(progress: ProgressContext) -> Self {
+        let mut tera = Tera::default();
+        filters::register(&mut tera);
+
         Self {
-            tera: Arc::new(Mutex::new(Tera::default())),
+            tera: Arc::new(Mutex::new(tera)),
+            file_system_operator: FileSystemOperator::new_host(progress),
+            filters: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
+    fn add_filter(&self, name: String, function: mlua::Function) -> Result<(), MutexLockError> {
+        let mut guard = self.tera.lock().map_err(|_| MutexLockError)?;
+
+        guard.register_filter(&name, LuaFilter { name: name.clone() });
+        self.filters.borrow_mut().insert(name, function);
+
+        Ok(())
+    }
+
+    fn render_file_with_lua_context(
+        &self,
+        lua: &mlua::Lua,
+        path: PathBuf,
+        lua_context: mlua::Table,
+        options: &RenderOptions,
+    ) -> Result<String, mlua::Error> {
+        let template_string = FileContentOrString::FileContent(FileContent {
+            path,
+            file_system_operator: self.file_system_operator.clone(),
+        })
+        .into_string()
+        .map_err(|error| {
+            mlua::Error::RuntimeError(
+                ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+            )
+        })?;
+
+        self.render_string_with_lua_context(lua, &template_string, lua_context, options)
+            .map_err(|error| {
+                mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+            })
+    }
+
+    /// Renders every file below `source_path` on the machine arc runs on
+    /// into the same relative path below `target_path` on `target`, keeping
+    /// permissions. Files matching a skip pattern are copied unchanged.
+    fn render_tree(
+        &self,
+        lua: &mlua::Lua,
+        source_path: &Path,
+        target: &FileSystemOperator,
+        target_path: &Path,
+        lua_context: mlua::Table,
+        options: &RenderTreeOptions,
+    ) -> Result<TemplateTreeResult, TemplateTreeError> {
+        self.render_tree_entries(lua, source_path, target, target_path, lua_context, options)
+            .map_err(|source| TemplateTreeError {
+                source_path: source_path.to_path_buf(),
+                target_path: target_path.to_path_buf(),
+                source: Box::new(source.enforce_ffi_boundary()),
+            })
+    }
+
+    fn render_tree_entries(
+        &self,
+        lua: &mlua::Lua,
+        source_path: &Path,
+        target: &FileSystemOperator,
+        target_path: &Path,
+        lua_context: mlua::Table,
+        options: &RenderTreeOptions,
+    ) -> Result<TemplateTreeResult, TemplateTreeFailure> {
+        let mut result = TemplateTreeResult::default();
+
+        target.create_directory(target_path)?;
+
+        // Entries are sorted by path, so directories are created before
+        // their content. Symbolic links are left out.
+        for entry in self
+            .file_system_operator
+            .walk_directory(source_path, usize::MAX)?
+        {
+            let relative_path = entry.path.strip_prefix(source_path).unwrap_or(&entry.path);
+            let target_entry_path = target_path.join(relative_path);
+
+            match entry.r#type {
+                MetadataType::Directory => target.create_directory(&target_entry_path)?,
+                MetadataType::File if Self::is_skipped(relative_path, &options.skip) => {
+                    self.file_system_operator.stream_to_other(
+                        &entry.path,
+                        target,
+                        &target_entry_path,
+                    )?;
+                    result.files_copied += 1;
+                }
+                MetadataType::File => {
+                    let content =
+                        String::from_utf8(self.file_system_operator.read_file(&entry.path)?)
+                            .map_err(|_| TemplateEncodingError(entry.path.clone()))?;
+                    let rendered = self
+                        .render_string_with_lua_context(
+                            lua,
+                            &content,
+                            lua_context.clone(),
+                            &options.render,
+                        )
+                        .map_err(|source| TemplateFileRenderError {
+                            path: entry.path.clone(),
+                            source,
+                        })?;
+
+                    target.write_file(
+                        &target_entry_path,
+                        rendered.as_bytes(),
+                        &WriteOptions::default(),
+                    )?;
+                    result.files_rendered += 1;
+                }
+                MetadataType::Unknown => continue,
+            }
+
+            if let Some(permissions) = entry.permissions {
+                target.set_permissions(&target_entry_path, permissions & 0o7777)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Whether a file is copied instead of rendered. Patterns containing `/`
+    /// are matched against the relative path, others against the file name.
+    fn is_skipped(relative_path: &Path, skip_patterns: &[String]) -> bool {
+        let file_name = relative_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy())
+            .unwrap_or_default();
+        let relative_path = relative_path.to_string_lossy();
+
+        skip_patterns
+            .iter()
+            .any(|pattern| match pattern.contains('/') {
+                true => matches_pattern(pattern, &relative_path),
+                false => matches_pattern(pattern, &file_name),
+            })
+    }
+
     pub fn render_string_with_lua_context(
         &self,
+        lua: &mlua::Lua,
         template_content: &str,
         lua_context: mlua::Table,
+        options: &RenderOptions,
     ) -> Result<String, TemplateRenderError> {
-        let context =
-            tera::Context::from_value(Self::build_template_arguments(lua_context)?.into())?;
+        let mut arguments = Self::build_template_arguments(lua_context)?;
+
+        // Arguments passed explicitly take precedence over the system
+        if let Some(system) = lua.app_data_ref::<System>()
+            && !arguments.contains_key(SYSTEM_ARGUMENT)
+        {
+            arguments.insert(SYSTEM_ARGUMENT.to_string(), system.template_context());
+        }
+
+        let context = tera::Context::from_value(arguments.into())?;
 
         let mut guard = self.tera.lock().map_err(|_| MutexLockError)?;
 
-        Ok(guard.render_str(template_content, &context)?)
+        // An empty suffix matches every template name
+        guard.autoescape_on(if options.autoescape { vec![""] } else { vec![] });
+
+        let previous_scope = RENDER_SCOPE.replace(Some(RenderScope {
+            lua: lua.clone(),
+            filters: self.filters.clone(),
+        }));
+        let result = guard.render_str(template_content, &context);
+        RENDER_SCOPE.set(previous_scope);
+
+        Ok(result?)
     }
 
     fn build_template_arguments(
@@ -78,40 +445,57 @@ impl Template {
                 other => Err(InvalidArgumentNameError(other.type_name().to_string()))?,
             };
 
-            match value {
-                mlua::Value::Nil => {
-                    map.insert(key_string, ().into());
-                }
-                mlua::Value::Boolean(boolean) => {
-                    map.insert(key_string, boolean.into());
-                }
-                mlua::Value::Integer(integer) => {
-                    map.insert(key_string, integer.into());
-                }
-                mlua::Value::Number(number) => {
-                    map.insert(key_string, number.into());
-                }
-                mlua::Value::String(string) => {
-                    if let Ok(string) = string.to_str() {
-                        map.insert(key_string, string.to_string().into());
-                    }
-                }
-                mlua::Value::Table(table) => {
-                    map.insert(key_string, Self::build_template_arguments(table)?.into());
-                }
-                other => Err(InvalidArgumentTypeError(other.type_name().to_string()))?,
-            }
+            map.insert(key_string, Self::build_template_value(value)?);
         }
 
         Ok(map)
     }
+
+    fn build_template_value(value: mlua::Value) -> Result<tera::Value, TemplateArgumentsError> {
+        Ok(match value {
+            mlua::Value::Nil => ().into(),
+            mlua::Value::Boolean(boolean) => boolean.into(),
+            mlua::Value::Integer(integer) => integer.into(),
+            mlua::Value::Number(number) => number.into(),
+            mlua::Value::String(string) => string.to_str()?.to_string().into(),
+            mlua::Value::Table(table) if Self::is_sequence(&table)? => table
+                .sequence_values::<mlua::Value>()
+                .map(|value| Self::build_template_value(value?))
+                .collect::<Result<Vec<_>, _>>()?
+                .into(),
+            mlua::Value::Table(table) => Self::build_template_arguments(table)?.into(),
+            other => Err(InvalidArgumentTypeError(other.type_name().to_string()))?,
+        })
+    }
+
+    /// Whether the keys of `table` are exactly the integers from 1 to its
+    /// length. Empty tables are sequences, so that loops over them render
+    /// nothing.
+    fn is_sequence(table: &mlua::Table) -> Result<bool, mlua::Error> {
+        let length = table.raw_len() as i64;
+        let mut key_count = 0;
+
+        for pair in table.pairs::<mlua::Value, mlua::Value>() {
+            match pair?.0 {
+                mlua::Value::Integer(index) if (1..=length).contains(&index) => key_count += 1,
+                _ => return Ok(false),
+            }
+        }
+
+        Ok(key_count == length)
+    }
 }
 
 impl UserData for Template {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_function(
             "render",
-            |lua, (template_content, context): (FileContentOrString, mlua::Table)| {
+            |lua,
+             (template_content, context, options): (
+                FileContentOrString,
+                mlua::Table,
+                RenderOptions,
+            )| {
                 let template = lua
                     .app_data_ref::<Self>()
                     .expect("templating engine unavailable in app data");
@@ -123,12 +507,94 @@ impl UserData for Template {
                 })?;
 
                 template
-                    .render_string_with_lua_context(&template_string, context)
+                    .render_string_with_lua_context(lua, &template_string, context, &options)
                     .map_err(|error| {
                         mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
                     })
             },
         );
+
+        methods.add_function(
+            "render_file",
+            |lua, (path, context, options): (PathBuf, mlua::Table, RenderOptions)| {
+                let template = lua
+                    .app_data_ref::<Self>()
+                    .expect("templating engine unavailable in app data");
+
+                template.render_file_with_lua_context(lua, path, context, &options)
+            },
+        );
+
+        methods.add_function(
+            "render_file_to",
+            |lua,
+             (source_path, target_path, context, system, options): (
+                PathBuf,
+                PathBuf,
+                mlua::Table,
+                UserDataRef<System>,
+                RenderOptions,
+            )| {
+                let template = lua
+                    .app_data_ref::<Self>()
+                    .expect("templating engine unavailable in app data");
+
+                let rendered =
+                    template.render_file_with_lua_context(lua, source_path, context, &options)?;
+
+                system
+                    .kind
+                    .file_system_operator()
+                    .write_file(&target_path, rendered.as_bytes(), &WriteOptions::default())
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })
+            },
+        );
+
+        methods.add_function(
+            "render_tree",
+            |lua,
+             (source_path, target_path, context, system, options): (
+                PathBuf,
+                PathBuf,
+                mlua::Table,
+                UserDataRef<System>,
+                RenderTreeOptions,
+            )| {
+                let template = lua
+                    .app_data_ref::<Self>()
+                    .expect("templating engine unavailable in app data");
+
+                template
+                    .render_tree(
+                        lua,
+                        &source_path,
+                        system.kind.file_system_operator(),
+                        &target_path,
+                        context,
+                        &options,
+                    )
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+                    })
+            },
+        );
+
+        methods.add_function(
+            "add_filter",
+            |lua, (name, function): (String, mlua::Function)| {
+                let template = lua
+                    .app_data_ref::<Self>()
+                    .expect("templating engine unavailable in app data");
+
+                template.add_filter(name, function).map_err(|error| {
+                    mlua::Error::RuntimeError(ErrorReport::boxed_from(error).build_report())
+                })
+            },
+        );
     }
 }
 
@@ -142,3 +608,76 @@ impl MountToGlobals for Template {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Template;
+
+    fn context(lua: &mlua::Lua, source: &str) -> tera::Value {
+        let table = lua.load(source).eval::<mlua::Table>().unwrap();
+
+        Template::build_template_arguments(table).unwrap().into()
+    }
+
+    #[test]
+    fn sequence_tables_become_arrays() {
+        let lua = mlua::Lua::new();
+
+        let value = context(
+            &lua,
+            r#"{
+                servers = {
+                    { name = "web", ports = { 80, 443 } },
+                    { name = "db", ports = {} },
+                },
+                nested = { { "a", "b" }, { "c" } },
+                sparse = { [1] = "a", [3] = "c" },
+                mixed = { "a", key = "b" },
+            }"#,
+        );
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "servers": [
+                    { "name": "web", "ports": [80, 443] },
+                    { "name": "db", "ports": [] },
+                ],
+                "nested": [["a", "b"], ["c"]],
+                "sparse": { "1": "a", "3": "c" },
+                "mixed": { "1": "a", "key": "b" },
+            })
+        );
+    }
+
+    #[test]
+    fn arrays_can_be_iterated_in_templates() {
+        let lua = mlua::Lua::new();
+
+        let value = context(
+            &lua,
+            r#"{ servers = { { name = "web" }, { name = "db" } } }"#,
+        );
+        let rendered = tera::Tera::one_off(
+            "{% for server in servers %}{{ loop.index }}:{{ server.name }} {% endfor %}",
+            &tera::Context::from_value(value).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(rendered, "1:web 2:db ");
+    }
+
+    #[test]
+    fn skip_patterns_match_file_names_or_relative_paths() {
+        let skip = ["*.raw".to_string(), "static/*".to_string()];
+
+        assert!(Template::is_skipped(Path::new("data.raw"), &skip));
+        assert!(Template::is_skipped(Path::new("conf.d/data.raw"), &skip));
+        assert!(Template::is_skipped(Path::new("static/logo.png"), &skip));
+        assert!(!Template::is_skipped(Path::new("site/static.conf"), &skip));
+        assert!(!Template::is_skipped(Path::new("app.conf"), &skip));
+    }
+}
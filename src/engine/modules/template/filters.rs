@@ -0,0 +1,219 @@
+//! Filters every template gets in addition to the built-in ones of Tera.
+
+use std::collections::HashMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tera::{Tera, Value};
+
+pub fn register(tera: &mut Tera) {
+    tera.register_filter("to_json", to_json);
+    tera.register_filter("b64encode", b64encode);
+    tera.register_filter("b64decode", b64decode);
+    // Replaces the filter of Tera, whose arguments it still accepts
+    tera.register_filter("indent", indent);
+    tera.register_filter("regex_replace", regex_replace);
+}
+
+fn expect_string<'a>(filter: &str, name: &str, value: &'a Value) -> tera::Result<&'a str> {
+    value.as_str().ok_or_else(|| {
+        tera::Error::msg(format!(
+            "Filter `{}` expected {} to be a string, got {}",
+            filter, name, value
+        ))
+    })
+}
+
+fn optional_bool(
+    filter: &str,
+    args: &HashMap<String, Value>,
+    name: &str,
+) -> tera::Result<Option<bool>> {
+    args.get(name)
+        .map(|value| {
+            value.as_bool().ok_or_else(|| {
+                tera::Error::msg(format!(
+                    "Filter `{}` expected {} to be a boolean, got {}",
+                    filter, name, value
+                ))
+            })
+        })
+        .transpose()
+}
+
+fn required_string<'a>(
+    filter: &str,
+    args: &'a HashMap<String, Value>,
+    name: &str,
+) -> tera::Result<&'a str> {
+    match args.get(name) {
+        Some(value) => expect_string(filter, name, value),
+        None => Err(tera::Error::msg(format!(
+            "Filter `{}` expected an argument {:?}",
+            filter, name
+        ))),
+    }
+}
+
+/// Serializes the value as JSON, indented with `pretty=true`.
+fn to_json(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let json = if optional_bool("to_json", args, "pretty")?.unwrap_or(false) {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .map_err(tera::Error::json)?;
+
+    Ok(Value::String(json))
+}
+
+fn b64encode(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let string = expect_string("b64encode", "the value", value)?;
+
+    Ok(Value::String(STANDARD.encode(string)))
+}
+
+fn b64decode(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let string = expect_string("b64decode", "the value", value)?;
+
+    let bytes = STANDARD
+        .decode(string.trim())
+        .map_err(|error| tera::Error::chain("Filter `b64decode` got invalid base64", error))?;
+
+    String::from_utf8(bytes)
+        .map(Value::String)
+        .map_err(|error| tera::Error::chain("Filter `b64decode` decoded invalid UTF-8", error))
+}
+
+/// Indents every line but the first by `width` spaces or by `prefix`
+/// (default: 4 spaces). `first=true` indents the first line as well and
+/// `blank=true` blank lines.
+fn indent(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let string = expect_string("indent", "the value", value)?;
+
+    let prefix = match (args.get("width"), args.get("prefix")) {
+        (Some(_), Some(_)) => {
+            return Err(tera::Error::msg(
+                "Filter `indent` expected either width or prefix, not both",
+            ));
+        }
+        (Some(width), None) => match width.as_u64() {
+            Some(width) => " ".repeat(width as usize),
+            None => {
+                return Err(tera::Error::msg(format!(
+                    "Filter `indent` expected width to be a non-negative integer, got {}",
+                    width
+                )));
+            }
+        },
+        (None, Some(prefix)) => expect_string("indent", "prefix", prefix)?.to_string(),
+        (None, None) => "    ".to_string(),
+    };
+    let first = optional_bool("indent", args, "first")?.unwrap_or(false);
+    let blank = optional_bool("indent", args, "blank")?.unwrap_or(false);
+
+    let indented = string
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let indents = match index {
+                0 => first,
+                _ => blank || !line.trim_start().is_empty(),
+            };
+
+            match indents {
+                true => format!("{}{}", prefix, line),
+                false => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(Value::String(indented))
+}
+
+/// Replaces every match of the regular expression `pattern` by
+/// `replacement`, in which `$1` or `${name}` insert capture groups.
+fn regex_replace(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let string = expect_string("regex_replace", "the value", value)?;
+    let pattern = required_string("regex_replace", args, "pattern")?;
+    let replacement = required_string("regex_replace", args, "replacement")?;
+
+    let regex = regex::Regex::new(pattern).map_err(|error| {
+        tera::Error::chain(
+            format!(
+                "Filter `regex_replace` got the invalid pattern {:?}",
+                pattern
+            ),
+            error,
+        )
+    })?;
+
+    Ok(Value::String(
+        regex.replace_all(string, replacement).into_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tera::{Context, Tera};
+
+    use super::register;
+
+    fn render(template: &str) -> Result<String, tera::Error> {
+        let mut tera = Tera::default();
+        register(&mut tera);
+
+        let mut context = Context::new();
+        context.insert(
+            "server",
+            &serde_json::json!({ "name": "web", "ports": [80] }),
+        );
+        context.insert("block", "a: 1\n\nb: 2");
+
+        tera.render_str(template, &context)
+    }
+
+    #[test]
+    fn filters_transform_values_for_config_files() {
+        assert_eq!(
+            render("{{ server | to_json }}").unwrap(),
+            r#"{"name":"web","ports":[80]}"#
+        );
+        assert_eq!(
+            render("{{ server.ports | to_json(pretty=true) }}").unwrap(),
+            "[\n  80\n]"
+        );
+        assert_eq!(
+            render("{{ 'user:pass' | b64encode }}").unwrap(),
+            "dXNlcjpwYXNz"
+        );
+        assert_eq!(
+            render("{{ 'dXNlcjpwYXNz' | b64decode }}").unwrap(),
+            "user:pass"
+        );
+        assert_eq!(
+            render("config:\n  {{ block | indent(width=2) }}").unwrap(),
+            "config:\n  a: 1\n\n  b: 2"
+        );
+        assert_eq!(
+            render("{{ block | indent(prefix='# ', first=true, blank=true) }}").unwrap(),
+            "# a: 1\n# \n# b: 2"
+        );
+        assert_eq!(
+            render(
+                r#"{{ 'web-01.eu' | regex_replace(pattern='^(\w+)-(\d+)', replacement='$2-$1') }}"#
+            )
+            .unwrap(),
+            "01-web.eu"
+        );
+    }
+
+    #[test]
+    fn invalid_filter_input_is_an_error() {
+        assert!(render("{{ 'not base64!' | b64decode }}").is_err());
+        assert!(render("{{ '/w==' | b64decode }}").is_err());
+        assert!(render("{{ block | indent(width=2, prefix=' ') }}").is_err());
+        assert!(render("{{ block | regex_replace(pattern='(', replacement='') }}").is_err());
+        assert!(render("{{ block | regex_replace(pattern='a') }}").is_err());
+    }
+}
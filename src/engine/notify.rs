@@ -0,0 +1,43 @@
+use std::{sync::Arc, time::Duration};
+
+use super::summary::RunSummary;
+
+static WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to send webhook notification to {url:?}")]
+pub struct WebhookNotificationError {
+    url: String,
+    #[source]
+    source: WebhookRequestError,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(transparent)]
+pub enum WebhookRequestError {
+    Tls(#[from] native_tls::Error),
+    Serialization(#[from] serde_json::Error),
+    Request(#[from] Box<ureq::Error>),
+}
+
+fn post_json(url: &str, summary: &RunSummary) -> Result<(), WebhookRequestError> {
+    let agent = ureq::AgentBuilder::new()
+        .tls_connector(Arc::new(native_tls::TlsConnector::new()?))
+        .timeout(WEBHOOK_TIMEOUT)
+        .build();
+
+    agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&serde_json::to_string(summary)?)
+        .map_err(Box::new)?;
+
+    Ok(())
+}
+
+pub fn send_webhook(url: &str, summary: &RunSummary) -> Result<(), WebhookNotificationError> {
+    post_json(url, summary).map_err(|source| WebhookNotificationError {
+        url: url.to_string(),
+        source,
+    })
+}
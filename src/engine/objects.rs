@@ -1,4 +1,5 @@
 pub mod directory;
 pub mod file;
 pub mod file_content;
+pub mod shell;
 pub mod system;
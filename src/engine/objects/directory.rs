@@ -1,9 +1,16 @@
 use std::path::PathBuf;
 
 use mlua::UserData;
+use regex::Regex;
 
 use crate::{
-    engine::delegator::{error::FfiError, operator::FileSystemOperator},
+    engine::delegator::{
+        error::FfiError,
+        operator::{
+            CreateDirectoryOptions, FileSystemOperator, GrepOptions, MetadataType, OwnerSpec,
+            RecursivePermissions, SearchOptions,
+        },
+    },
     error::ErrorReport,
 };
 
@@ -46,30 +53,74 @@ impl UserData for Directory {
                 })
         });
 
-        fields.add_field_method_get("file_name", |_, this| {
-            Ok(this.file_system_operator.file_name(&this.path))
+        fields.add_field_method_get("owner", |_, this| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| maybe_metadata.and_then(|metadata| metadata.uid))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
         });
-        fields.add_field_method_set("file_name", |_, this, new_name: String| {
+        fields.add_field_method_set("owner", |_, this, owner: OwnerSpec| {
             this.file_system_operator
-                .set_file_name(&this.path, &new_name)
+                .set_ownership(&this.path, Some(&owner), None)
                 .map_err(|error| {
                     mlua::Error::RuntimeError(
                         ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
                     )
                 })
         });
-    }
 
-    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method("create", |_, this, (): ()| {
+        fields.add_field_method_get("group", |_, this| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| maybe_metadata.and_then(|metadata| metadata.gid))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        fields.add_field_method_set("group", |_, this, group: OwnerSpec| {
+            this.file_system_operator
+                .set_ownership(&this.path, None, Some(&group))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+
+        fields.add_field_method_get("file_name", |_, this| {
+            Ok(this.file_system_operator.file_name(&this.path))
+        });
+        fields.add_field_method_set("file_name", |_, this, new_name: String| {
             this.file_system_operator
-                .create_directory(&this.path)
+                .set_file_name(&this.path, &new_name)
                 .map_err(|error| {
                     mlua::Error::RuntimeError(
                         ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
                     )
                 })
         });
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("create", |_, this, options: CreateDirectoryOptions| {
+            match options.mode {
+                Some(mode) => this
+                    .file_system_operator
+                    .create_directory_with_mode(&this.path, mode),
+                None => this.file_system_operator.create_directory(&this.path),
+            }
+            .map_err(|error| {
+                mlua::Error::RuntimeError(
+                    ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                )
+            })
+        });
         methods.add_method("remove", |_, this, (): ()| {
             this.file_system_operator
                 .remove_directory(&this.path)
@@ -79,6 +130,18 @@ impl UserData for Directory {
                     )
                 })
         });
+        methods.add_method(
+            "set_permissions_recursive",
+            |_, this, modes: RecursivePermissions| {
+                this.file_system_operator
+                    .set_permissions_recursive(&this.path, modes)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })
+            },
+        );
         methods.add_method("metadata", |_, this, (): ()| {
             this.file_system_operator
                 .metadata(&this.path)
@@ -108,6 +171,88 @@ impl UserData for Directory {
 
             Ok(result)
         });
+        methods.add_method(
+            "grep",
+            |_, this, (pattern, options): (String, GrepOptions)| {
+                let pattern = Regex::new(&pattern)
+                    .map_err(|error| mlua::Error::runtime(format!("Invalid pattern: {}", error)))?;
+
+                this.file_system_operator
+                    .grep(&this.path, &pattern, &options)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })
+            },
+        );
+        methods.add_method(
+            "glob",
+            |lua, this, (pattern, options): (String, SearchOptions)| {
+                let entries = this
+                    .file_system_operator
+                    .glob(&this.path, &pattern, &options)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })?;
+
+                let mut result = Vec::with_capacity(entries.len());
+
+                for entry in entries {
+                    result.push(entry.into_lua(lua)?);
+                }
+
+                Ok(result)
+            },
+        );
+        methods.add_method(
+            "find",
+            |lua, this, (predicate, options): (mlua::Function, SearchOptions)| {
+                let metadata_entries = this
+                    .file_system_operator
+                    .walk_directory(&this.path, options.max_depth)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })?;
+
+                let mut result = Vec::new();
+
+                for metadata in metadata_entries {
+                    let Some(entry) = this.file_system_operator.entry(&metadata) else {
+                        continue;
+                    };
+
+                    if predicate.call::<bool>(metadata)? {
+                        result.push(entry.into_lua(lua)?);
+                    }
+                }
+
+                Ok(result)
+            },
+        );
+        methods.add_method("upload", |_, this, local_path: PathBuf| {
+            this.file_system_operator
+                .host()
+                .copy_directory_to(&local_path, &this.file_system_operator, &this.path)
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        methods.add_method("download", |_, this, local_path: PathBuf| {
+            this.file_system_operator
+                .copy_directory_to(&this.path, &this.file_system_operator.host(), &local_path)
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
         methods.add_method("parent", |_, this, (): ()| {
             this.file_system_operator
                 .parent_directory(&this.path)
@@ -127,5 +272,30 @@ impl UserData for Directory {
                     )
                 })
         });
+        methods.add_method("is_file", |_, this, (): ()| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| {
+                    maybe_metadata.is_some_and(|metadata| metadata.r#type == MetadataType::File)
+                })
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        methods.add_method("is_directory", |_, this, (): ()| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| {
+                    maybe_metadata
+                        .is_some_and(|metadata| metadata.r#type == MetadataType::Directory)
+                })
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
     }
 }
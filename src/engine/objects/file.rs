@@ -1,10 +1,16 @@
 use std::path::PathBuf;
 
-use mlua::UserData;
+use mlua::{UserData, UserDataRef};
 
 use super::file_content::FileContent;
 use crate::{
-    engine::delegator::{error::FfiError, operator::FileSystemOperator},
+    engine::delegator::{
+        error::FfiError,
+        operator::{
+            ChecksumAlgorithm, FileSystemOperator, MetadataResult, MetadataType, OwnerSpec,
+            WriteOptions,
+        },
+    },
     error::ErrorReport,
 };
 
@@ -14,6 +20,58 @@ pub struct File {
     pub file_system_operator: FileSystemOperator,
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum FileComparisonError {
+    #[error("Cannot compare {0:?} because it does not exist")]
+    Missing(PathBuf),
+    #[error("Cannot compare {path:?} because its {field} is unavailable")]
+    Unavailable { path: PathBuf, field: &'static str },
+}
+
+impl File {
+    /// Metadata of the file for comparing it with another file.
+    fn comparison_metadata(&self) -> mlua::Result<MetadataResult> {
+        self.file_system_operator
+            .metadata(&self.path)
+            .map_err(|error| {
+                mlua::Error::RuntimeError(
+                    ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                )
+            })?
+            .ok_or_else(|| {
+                mlua::Error::RuntimeError(
+                    ErrorReport::boxed_from(FileComparisonError::Missing(self.path.clone()))
+                        .build_report(),
+                )
+            })
+    }
+
+    /// The value `get` reads from the metadata of this and the `other` file,
+    /// an error if either file is missing or `field` is unavailable.
+    fn compared_values(
+        &self,
+        other: &File,
+        field: &'static str,
+        get: impl Fn(&MetadataResult) -> Option<u64>,
+    ) -> mlua::Result<(u64, u64)> {
+        let value = |file: &File| {
+            let metadata = file.comparison_metadata()?;
+
+            get(&metadata).ok_or_else(|| {
+                mlua::Error::RuntimeError(
+                    ErrorReport::boxed_from(FileComparisonError::Unavailable {
+                        path: metadata.path,
+                        field,
+                    })
+                    .build_report(),
+                )
+            })
+        };
+
+        Ok((value(self)?, value(other)?))
+    }
+}
+
 const FILE_CONTENT_ASSIGNMENT_TYPE_ERROR: &str =
     "Expected FileContent or string for content setter";
 
@@ -68,7 +126,7 @@ impl UserData for File {
             }
             mlua::Value::String(string) => {
                 this.file_system_operator
-                    .write_file(&this.path, &string.as_bytes())
+                    .write_file(&this.path, &string.as_bytes(), &WriteOptions::default())
                     .map_err(|error| {
                         mlua::Error::RuntimeError(
                             ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
@@ -101,9 +159,70 @@ impl UserData for File {
                     )
                 })
         });
+
+        fields.add_field_method_get("owner", |_, this| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| maybe_metadata.and_then(|metadata| metadata.uid))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        fields.add_field_method_set("owner", |_, this, owner: OwnerSpec| {
+            this.file_system_operator
+                .set_ownership(&this.path, Some(&owner), None)
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+
+        fields.add_field_method_get("group", |_, this| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| maybe_metadata.and_then(|metadata| metadata.gid))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        fields.add_field_method_set("group", |_, this, group: OwnerSpec| {
+            this.file_system_operator
+                .set_ownership(&this.path, None, Some(&group))
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
     }
 
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method(
+            "write",
+            |_, this, (content, options): (mlua::String, WriteOptions)| {
+                this.file_system_operator
+                    .write_file(&this.path, &content.as_bytes(), &options)
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })
+            },
+        );
+        methods.add_method("append", |_, this, content: mlua::String| {
+            this.file_system_operator
+                .append_file(&this.path, &content.as_bytes())
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
         methods.add_method("metadata", |_, this, (): ()| {
             this.file_system_operator
                 .metadata(&this.path)
@@ -131,6 +250,36 @@ impl UserData for File {
                     )
                 })
         });
+        methods.add_method("sha256", |_, this, (): ()| {
+            this.file_system_operator
+                .checksum(&this.path, ChecksumAlgorithm::Sha256)
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        methods.add_method("md5", |_, this, (): ()| {
+            this.file_system_operator
+                .checksum(&this.path, ChecksumAlgorithm::Md5)
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        methods.add_method("newer_than", |_, this, other: UserDataRef<File>| {
+            let (modified, other_modified) =
+                this.compared_values(&other, "modification time", |metadata| metadata.modified)?;
+
+            Ok(modified > other_modified)
+        });
+        methods.add_method("same_size", |_, this, other: UserDataRef<File>| {
+            let (size, other_size) =
+                this.compared_values(&other, "size", |metadata| metadata.size)?;
+
+            Ok(size == other_size)
+        });
         methods.add_method("exists", |_, this, (): ()| {
             this.file_system_operator
                 .metadata(&this.path)
@@ -141,5 +290,30 @@ impl UserData for File {
                     )
                 })
         });
+        methods.add_method("is_file", |_, this, (): ()| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| {
+                    maybe_metadata.is_some_and(|metadata| metadata.r#type == MetadataType::File)
+                })
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
+        methods.add_method("is_directory", |_, this, (): ()| {
+            this.file_system_operator
+                .metadata(&this.path)
+                .map(|maybe_metadata| {
+                    maybe_metadata
+                        .is_some_and(|metadata| metadata.r#type == MetadataType::Directory)
+                })
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })
+        });
     }
 }
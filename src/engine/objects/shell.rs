@@ -0,0 +1,29 @@
+use mlua::UserData;
+
+use crate::{
+    engine::delegator::{error::FfiError, shell::ShellSession},
+    error::ErrorReport,
+};
+
+#[derive(Clone)]
+pub struct Shell {
+    pub session: ShellSession,
+}
+
+impl UserData for Shell {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("run", |_, this, command: String| {
+            this.session.run(&command).map_err(|error| {
+                mlua::Error::RuntimeError(
+                    ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                )
+            })
+        });
+
+        methods.add_method("close", |_, this, ()| {
+            this.session.close();
+
+            Ok(())
+        });
+    }
+}
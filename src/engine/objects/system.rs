@@ -1,15 +1,33 @@
-use std::{net::IpAddr, panic::resume_unwind, path::PathBuf};
+use std::{
+    cell::RefCell, collections::HashSet, net::IpAddr, panic::resume_unwind, path::PathBuf, rc::Rc,
+    time::Duration,
+};
 
-use mlua::UserData;
+use mlua::{LuaSerdeExt, UserData};
 
 use crate::engine::delegator::error::FfiPanicError;
-use crate::engine::delegator::{error::FfiError, executor::Executor, operator::FileSystemOperator};
+use crate::engine::delegator::{
+    error::FfiError,
+    executor::{CommandOptions, Executor, RawCommandResult, WaitOptions},
+    operator::FileSystemOperator,
+};
+use crate::engine::objects::shell::Shell;
 use crate::error::ErrorReport;
+use crate::memory::target_systems::Vars;
+
+/// How long `system:reboot()` waits for the system to come back by default
+const DEFAULT_REBOOT_TIMEOUT: Duration = Duration::from_secs(600);
 
 #[derive(Clone)]
 pub struct System {
     pub name: String,
     pub kind: SystemKind,
+    /// Variables of the system including those inherited from its groups
+    pub vars: Vars,
+    /// Names of the groups the system is a member of, sorted
+    pub groups: Vec<String>,
+    /// Names of the tasks notified through `system:notify()`
+    pub notified: Rc<RefCell<HashSet<String>>>,
 }
 
 #[derive(Clone)]
@@ -44,7 +62,7 @@ impl SystemKind {
         }
     }
 
-    fn file_system_operator(&self) -> &FileSystemOperator {
+    pub fn file_system_operator(&self) -> &FileSystemOperator {
         match self {
             SystemKind::Remote(remote_system) => &remote_system.file_system_operator,
             SystemKind::Local(_executor, file_system_operator) => file_system_operator,
@@ -59,6 +77,29 @@ impl SystemKind {
     }
 }
 
+impl System {
+    pub fn close_shells(&self) {
+        self.kind.executor().close_shells();
+    }
+
+    pub fn is_notified(&self, task_name: &str) -> bool {
+        self.notified.borrow().contains(task_name)
+    }
+
+    /// Properties of the system that templates can access as `system`.
+    pub fn template_context(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "type": self.kind.r#type(),
+            "address": self.kind.address(),
+            "port": self.kind.port(),
+            "user": self.kind.user(),
+            "vars": self.vars,
+            "groups": self.groups,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct RemoteSystem {
     pub address: IpAddr,
@@ -76,17 +117,95 @@ impl UserData for System {
         fields.add_field_method_get("address", |_, this| Ok(this.kind.address()));
         fields.add_field_method_get("port", |_, this| Ok(this.kind.port()));
         fields.add_field_method_get("user", |_, this| Ok(this.kind.user()));
+        fields.add_field_method_get("vars", |lua, this| lua.to_value(&this.vars));
+        fields.add_field_method_get("groups", |_, this| Ok(this.groups.clone()));
     }
 
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method("run_command", |_, this, command: String| {
-            let result = this
+        methods.add_method(
+            "run_command",
+            |_, this, (command, options): (String, CommandOptions)| {
+                let result = this
+                    .kind
+                    .executor()
+                    .run_guarded_command(command, &options, this.kind.file_system_operator())
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })?;
+
+                Ok(result)
+            },
+        );
+
+        methods.add_method(
+            "run_command_bytes",
+            |_, this, (command, options): (String, CommandOptions)| {
+                let result = this
+                    .kind
+                    .executor()
+                    .run_guarded_command(command, &options, this.kind.file_system_operator())
+                    .map_err(|error| {
+                        mlua::Error::RuntimeError(
+                            ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                        )
+                    })?;
+
+                Ok(RawCommandResult(result))
+            },
+        );
+
+        methods.add_method("wait_for", |_, this, options: WaitOptions| {
+            let waited = this
                 .kind
                 .executor()
-                .run_command(command)
-                .unwrap_or_else(|error| resume_unwind(Box::new(FfiPanicError(Box::new(error)))));
+                .wait_for(&options, this.kind.file_system_operator())
+                .map_err(|error| {
+                    mlua::Error::RuntimeError(
+                        ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                    )
+                })?;
+
+            Ok(waited.map(|waited| waited.as_secs_f64()))
+        });
+
+        methods.add_method("reboot", |_, this, timeout: Option<f64>| {
+            let timeout = match timeout {
+                None => DEFAULT_REBOOT_TIMEOUT,
+                Some(seconds) => match Duration::try_from_secs_f64(seconds) {
+                    Ok(timeout) if !timeout.is_zero() => timeout,
+                    _ => {
+                        return Err(mlua::Error::runtime(format!(
+                            "Invalid timeout value: {}. Expected a positive number of seconds",
+                            seconds
+                        )));
+                    }
+                },
+            };
+
+            let downtime = this.kind.executor().reboot(timeout).map_err(|error| {
+                mlua::Error::RuntimeError(
+                    ErrorReport::boxed_from(error.enforce_ffi_boundary()).build_report(),
+                )
+            })?;
+
+            Ok(downtime.map(|downtime| downtime.as_secs_f64()))
+        });
+
+        methods.add_method("notify", |_, this, task_name: String| {
+            this.notified.borrow_mut().insert(task_name);
 
-            Ok(result)
+            Ok(())
+        });
+
+        methods.add_method("shell", |_, this, ()| {
+            let session =
+                this.kind.executor().open_shell().unwrap_or_else(|error| {
+                    resume_unwind(Box::new(FfiPanicError(Box::new(error))))
+                });
+
+            Ok(Shell { session })
         });
 
         methods.add_method("file", |_, this, path: PathBuf| {
@@ -112,3 +231,112 @@ impl UserData for System {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, path::Path};
+
+    use super::{RemoteSystem, System, SystemKind};
+    use crate::{
+        engine::delegator::{executor::Executor, operator::FileSystemOperator},
+        logger::Logger,
+        progress::ProgressContext,
+    };
+
+    /// A task body written for remote systems that reports what it did.
+    static TASK_BODY: &str = r#"
+        local system, directory = ...
+
+        local methods = {}
+        for _, name in ipairs({ "run_command", "run_command_bytes", "reboot", "wait_for", "shell", "notify", "file", "directory" }) do
+            table.insert(methods, type(system[name]))
+        end
+
+        system:directory(directory):create()
+        local file = system:file(directory .. "/app.conf")
+        file.content = "port = 8080"
+        local output = system:run_command_bytes("printf '\\377\\000\\001'").stdout
+
+        return {
+            bytes = table.concat({ string.byte(output, 1, -1) }, " "),
+            methods = table.concat(methods, ","),
+            content = tostring(file.content),
+            exists = file:exists(),
+            is_directory = system:directory(directory):is_directory(),
+            size = file:metadata().size,
+            entries = #system:directory(directory):entries(),
+            stdout = system:run_command("cat app.conf", { cwd = directory }).stdout,
+            shell = system:shell():run("echo shell").stdout,
+        }
+    "#;
+
+    fn system(kind: SystemKind) -> System {
+        System {
+            name: "test".to_string(),
+            kind,
+            vars: Default::default(),
+            groups: Vec::new(),
+            notified: Default::default(),
+        }
+    }
+
+    fn run_task_body(lua: &mlua::Lua, system: System, directory: &Path) -> String {
+        let result: mlua::Table = lua
+            .load(TASK_BODY)
+            .call((system, directory.to_string_lossy().to_string()))
+            .unwrap();
+
+        let mut pairs = result
+            .pairs::<String, mlua::Value>()
+            .map(|pair| {
+                let (key, value) = pair.unwrap();
+                format!("{}={}", key, value.to_string().unwrap())
+            })
+            .collect::<Vec<_>>();
+        pairs.sort();
+
+        pairs.join("\n")
+    }
+
+    #[test]
+    fn local_and_remote_systems_run_the_same_task_body() {
+        let lua = mlua::Lua::new();
+        let progress = ProgressContext::new(Logger::new(), false, false);
+        let home_path = std::env::temp_dir();
+        let root = home_path.join(format!("arc-system-parity-{}", std::process::id()));
+
+        let local = system(SystemKind::Local(
+            Executor::new_local(progress.clone(), home_path.clone()),
+            FileSystemOperator::new_local(progress.clone(), home_path.clone()),
+        ));
+        // Stands in for an SSH connection, so only the Lua API is compared
+        let remote = system(SystemKind::Remote(RemoteSystem {
+            address: IpAddr::from([127, 0, 0, 1]),
+            port: 22,
+            user: "root".to_string(),
+            executor: Executor::new_local(progress.clone(), home_path.clone()),
+            file_system_operator: FileSystemOperator::new_local(progress, home_path),
+        }));
+
+        let local_result = run_task_body(&lua, local, &root.join("local"));
+        let remote_result = run_task_body(&lua, remote, &root.join("remote"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(local_result, remote_result);
+        assert_eq!(
+            local_result,
+            [
+                "bytes=255 0 1",
+                "content=port = 8080",
+                "entries=1",
+                "exists=true",
+                "is_directory=true",
+                "methods=function,function,function,function,function,function,function,function",
+                "shell=shell\n",
+                "size=11",
+                "stdout=port = 8080",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RetryUsage {
+    pub used: u32,
+    pub budget: Option<u32>,
+}
+
+/// Bounds the number of task retries across a whole run, including systems
+/// running in parallel.
+pub struct RetryBudget {
+    budget: Option<u32>,
+    used: AtomicU32,
+}
+
+impl RetryBudget {
+    pub fn new(budget: Option<u32>) -> Self {
+        Self {
+            budget,
+            used: AtomicU32::new(0),
+        }
+    }
+
+    /// Takes one retry from the budget. Returns `false` if it is exhausted.
+    pub fn try_consume(&self) -> bool {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                match self.budget.is_some_and(|budget| used >= budget) {
+                    true => None,
+                    false => Some(used + 1),
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn usage(&self) -> RetryUsage {
+        RetryUsage {
+            used: self.used.load(Ordering::Relaxed),
+            budget: self.budget,
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Condvar, Mutex},
+};
+
+use crate::{error::MutexLockError, memory::tasks::Serial};
+
+use super::SystemTasks;
+
+/// Whether a system may run a task with `serial` batches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Turn {
+    Proceed,
+    /// The task failed on a system of an earlier batch
+    Halted,
+}
+
+/// Batches of a task with `serial`, in plan order of the systems.
+struct TaskRollout {
+    batch_of_system: HashMap<String, usize>,
+    /// Number of systems of each batch that did not finish the task yet
+    pending: Vec<usize>,
+    finished: HashSet<String>,
+    /// The earliest batch in which the task failed
+    failed_batch: Option<usize>,
+}
+
+impl TaskRollout {
+    fn new(serial: Serial, systems: &[&str]) -> Self {
+        let batch_size = serial.batch_size(systems.len());
+        let batch_of_system: HashMap<String, usize> = systems
+            .iter()
+            .enumerate()
+            .map(|(index, system)| (system.to_string(), index / batch_size))
+            .collect();
+
+        let mut pending = vec![0; systems.len().div_ceil(batch_size)];
+        for batch in batch_of_system.values() {
+            pending[*batch] += 1;
+        }
+
+        Self {
+            batch_of_system,
+            pending,
+            finished: HashSet::new(),
+            failed_batch: None,
+        }
+    }
+
+    fn finish(&mut self, system: &str, failed: bool) -> bool {
+        let Some(batch) = self.batch_of_system.get(system).copied() else {
+            return false;
+        };
+
+        if !self.finished.insert(system.to_string()) {
+            return false;
+        }
+
+        self.pending[batch] -= 1;
+
+        if failed {
+            self.failed_batch = Some(self.failed_batch.map_or(batch, |failed| failed.min(batch)));
+        }
+
+        true
+    }
+}
+
+/// Rolls out tasks with `serial` batch by batch, including systems running
+/// in parallel. A system of a later batch waits until all systems of the
+/// earlier batches finished the task.
+///
+/// Systems only wait for systems that come earlier in the plan, which
+/// workers start first, so waiting cannot deadlock as long as every started
+/// system is passed to [`Rollout::finish_system`] in the end.
+pub struct Rollout {
+    tasks: Mutex<HashMap<String, TaskRollout>>,
+    progressed: Condvar,
+}
+
+impl Rollout {
+    pub fn plan(system_tasks: &SystemTasks) -> Self {
+        let mut systems_of_task: HashMap<&str, (Serial, Vec<&str>)> = HashMap::new();
+
+        for (system, tasks) in system_tasks {
+            for task in tasks {
+                if let Some(serial) = task.serial {
+                    systems_of_task
+                        .entry(&task.name)
+                        .or_insert_with(|| (serial, Vec::new()))
+                        .1
+                        .push(&system.name);
+                }
+            }
+        }
+
+        let tasks = systems_of_task
+            .into_iter()
+            .map(|(task, (serial, systems))| (task.to_string(), TaskRollout::new(serial, &systems)))
+            .collect();
+
+        Self {
+            tasks: Mutex::new(tasks),
+            progressed: Condvar::new(),
+        }
+    }
+
+    /// Blocks until all systems of earlier batches finished `task`.
+    pub fn wait_for_turn(&self, task: &str, system: &str) -> Result<Turn, MutexLockError> {
+        let tasks = self.tasks.lock().map_err(|_| MutexLockError)?;
+
+        let is_waiting = |tasks: &mut HashMap<String, TaskRollout>| {
+            tasks.get(task).is_some_and(|rollout| {
+                let batch = rollout.batch_of_system.get(system).copied().unwrap_or(0);
+
+                rollout.pending[..batch].iter().any(|pending| *pending > 0)
+            })
+        };
+
+        let tasks = self
+            .progressed
+            .wait_while(tasks, is_waiting)
+            .map_err(|_| MutexLockError)?;
+
+        let halted = tasks.get(task).is_some_and(|rollout| {
+            let batch = rollout.batch_of_system.get(system).copied().unwrap_or(0);
+
+            rollout.failed_batch.is_some_and(|failed| failed < batch)
+        });
+
+        Ok(if halted { Turn::Halted } else { Turn::Proceed })
+    }
+
+    /// Records that `system` is done with `task`. A failure halts the
+    /// rollout before the next batch.
+    pub fn finish(&self, task: &str, system: &str, failed: bool) -> Result<(), MutexLockError> {
+        let mut tasks = self.tasks.lock().map_err(|_| MutexLockError)?;
+
+        if tasks
+            .get_mut(task)
+            .is_some_and(|rollout| rollout.finish(system, failed))
+        {
+            self.progressed.notify_all();
+        }
+
+        Ok(())
+    }
+
+    /// Releases all tasks `system` did not get to, e.g. because it failed to
+    /// connect or the run was aborted.
+    pub fn finish_system(&self, system: &str) -> Result<(), MutexLockError> {
+        let mut tasks = self.tasks.lock().map_err(|_| MutexLockError)?;
+
+        let mut progressed = false;
+        for rollout in tasks.values_mut() {
+            progressed |= rollout.finish(system, false);
+        }
+
+        if progressed {
+            self.progressed.notify_all();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TaskRollout;
+    use crate::memory::tasks::Serial;
+
+    #[test]
+    fn failures_halt_later_batches_only() {
+        let mut rollout = TaskRollout::new(Serial::Percentage(40), &["a", "b", "c", "d", "e"]);
+
+        assert_eq!(rollout.pending, vec![2, 2, 1]);
+
+        rollout.finish("c", true);
+        rollout.finish("c", false);
+        assert_eq!(rollout.pending, vec![2, 1, 1]);
+        assert_eq!(rollout.failed_batch, Some(1));
+
+        rollout.finish("a", true);
+        assert_eq!(rollout.failed_batch, Some(0));
+    }
+
+    #[test]
+    fn batch_sizes_are_at_least_one() {
+        assert_eq!(Serial::Count(3).batch_size(10), 3);
+        assert_eq!(Serial::Percentage(25).batch_size(10), 3);
+        assert_eq!(Serial::Percentage(1).batch_size(10), 1);
+        assert_eq!(Serial::Percentage(100).batch_size(0), 1);
+    }
+}
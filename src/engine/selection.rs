@@ -1,11 +1,72 @@
-use std::collections::HashSet;
+use std::{borrow::Borrow, collections::HashSet};
+
+use serde::Serialize;
 
 use crate::memory::{
-    target_groups::TargetGroups,
-    target_systems::TargetSystems,
+    target_groups::{TargetGroup, TargetGroups},
+    target_systems::{TargetSystem, TargetSystems, Vars},
     tasks::{Task, Tasks},
 };
 
+#[derive(Debug)]
+pub struct Selection {
+    pub tags: TagSelection,
+    /// Tags whose tasks are excluded via `--skip-tag`
+    pub skip_tags: HashSet<String>,
+    pub tasks: TaskSelection,
+    pub groups: GroupSelection,
+    pub systems: SystemSelection,
+    /// Systems the selected systems are narrowed to via `--limit`
+    pub limit: SystemSelection,
+    pub no_reqs: bool,
+    pub start_at: Option<String>,
+    /// Whether selected tasks that target none of the selected systems are
+    /// an error via `--strict` instead of a warning
+    pub strict: bool,
+}
+
+/// Whether `name` matches a glob pattern. `*` matches any run of characters
+/// and `?` matches a single character, everything else matches itself.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut pattern_index, mut name_index) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut backtrack = None;
+
+    while name_index < name.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                pattern_index += 1;
+                backtrack = Some((pattern_index, name_index));
+            }
+            Some(&character) if character == '?' || character == name[name_index] => {
+                pattern_index += 1;
+                name_index += 1;
+            }
+            _ => match backtrack {
+                Some((star_pattern_index, star_name_index)) => {
+                    pattern_index = star_pattern_index;
+                    name_index = star_name_index + 1;
+                    backtrack = Some((star_pattern_index, name_index));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pattern_index..]
+        .iter()
+        .all(|character| *character == '*')
+}
+
+pub fn matches_any(patterns: &HashSet<String>, name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| matches_pattern(pattern, name))
+}
+
 #[derive(Debug)]
 pub enum GroupSelection {
     None,
@@ -16,7 +77,7 @@ impl GroupSelection {
     pub fn contains(&self, group_name: &str) -> bool {
         match self {
             GroupSelection::None => false,
-            GroupSelection::Set(selected_set) => selected_set.contains(group_name),
+            GroupSelection::Set(patterns) => matches_any(patterns, group_name),
         }
     }
 }
@@ -27,6 +88,21 @@ pub enum TagSelection {
     Set(HashSet<String>),
 }
 
+#[derive(Debug)]
+pub enum TaskSelection {
+    None,
+    Set(HashSet<String>),
+}
+
+impl TaskSelection {
+    pub fn contains(&self, task_name: &str) -> bool {
+        match self {
+            TaskSelection::None => false,
+            TaskSelection::Set(selected_set) => selected_set.contains(task_name),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SystemSelection {
     None,
@@ -39,25 +115,126 @@ impl SystemSelection {
         match self {
             SystemSelection::None => false,
             SystemSelection::All => true,
-            SystemSelection::Set(selected_set) => selected_set.contains(system_name),
+            SystemSelection::Set(patterns) => matches_any(patterns, system_name),
         }
     }
 }
 
-pub fn task_matches_groups(task: &Task, selection: &GroupSelection) -> bool {
-    match selection {
-        GroupSelection::None => true,
-        GroupSelection::Set(selected_set) => {
-            task.targets.is_empty() || !task.targets.is_disjoint(selected_set)
-        }
+/// Why a task is part of the execution plan.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum InclusionReason {
+    /// Selected by name via `--task`
+    Named,
+    /// Selected by tag via `--tag` or `--all-tags`
+    Tagged,
+    /// Marked as important
+    Important,
+    /// Required by another planned task
+    Required,
+}
+
+pub fn inclusion_reason(task: &Task, selection: &Selection) -> InclusionReason {
+    if selection.tasks.contains(&task.name) {
+        InclusionReason::Named
+    } else if task_matches_tags(task, &selection.tags) {
+        InclusionReason::Tagged
+    } else if task.important {
+        InclusionReason::Important
+    } else {
+        InclusionReason::Required
     }
 }
 
+/// Whether the task runs on the system: tasks without `targets` run on every
+/// system, others only on the systems and the members of the groups they
+/// name. `system_groups` are the groups containing the system, directly or
+/// through nested groups.
+pub fn task_targets_system(task: &Task, system_name: &str, system_groups: &[&String]) -> bool {
+    task.targets.is_empty()
+        || task.targets.contains(system_name)
+        || task
+            .targets
+            .iter()
+            .any(|target| system_groups.contains(&target))
+}
+
+/// Whether the task has a tag excluded via `--skip-tag`. Tasks named via
+/// `--task` are never excluded.
+pub fn task_is_excluded(
+    task: &Task,
+    skip_tags: &HashSet<String>,
+    task_selection: &TaskSelection,
+) -> bool {
+    task.tags.iter().any(|tag| matches_any(skip_tags, tag)) && !task_selection.contains(&task.name)
+}
+
 pub fn task_matches_tags(task: &Task, selection: &TagSelection) -> bool {
     match selection {
         TagSelection::All => true,
-        TagSelection::Set(selected_set) => !task.tags.is_disjoint(selected_set),
+        TagSelection::Set(patterns) => task.tags.iter().any(|tag| matches_any(patterns, tag)),
+    }
+}
+
+fn task_is_selected(
+    task: &Task,
+    tag_selection: &TagSelection,
+    skip_tags: &HashSet<String>,
+    task_selection: &TaskSelection,
+) -> bool {
+    !task_is_excluded(task, skip_tags, task_selection)
+        && (task.important
+            || task_matches_tags(task, tag_selection)
+            || task_selection.contains(&task.name))
+}
+
+/// A task that another selected task requires but that is excluded via
+/// `--skip-tag` and therefore not run.
+#[derive(Debug)]
+pub struct ExcludedRequirement {
+    pub task: String,
+    pub required_by: String,
+}
+
+/// Replaces groups among the members of each group with their systems, so
+/// that a group lists all systems it contains directly or through other
+/// groups. Groups that contain each other are not expanded again.
+pub fn flatten_groups(groups: &TargetGroups) -> TargetGroups {
+    fn collect_systems(
+        groups: &TargetGroups,
+        name: &String,
+        visited: &mut HashSet<String>,
+        systems: &mut Vec<String>,
+    ) {
+        if !visited.insert(name.clone()) {
+            return;
+        }
+
+        for member in &groups[name].members {
+            if groups.contains_key(member) {
+                collect_systems(groups, member, visited, systems);
+            } else if !systems.contains(member) {
+                systems.push(member.clone());
+            }
+        }
     }
+
+    groups
+        .iter()
+        .map(|(name, group)| {
+            let mut members = Vec::new();
+            collect_systems(groups, name, &mut HashSet::new(), &mut members);
+
+            (
+                name.clone(),
+                TargetGroup {
+                    members,
+                    ..group.clone()
+                },
+            )
+        })
+        .collect()
 }
 
 pub fn select_groups(mut groups: TargetGroups, selection: &GroupSelection) -> TargetGroups {
@@ -80,26 +257,28 @@ pub fn select_systems(
     systems
 }
 
+/// Selects tasks by name, tag and `important`. Which of them run on a system
+/// is decided by their targets, see [`select_tasks_for_system`].
 pub fn select_tasks(
     mut tasks: Tasks,
-    group_selection: &GroupSelection,
     tag_selection: &TagSelection,
+    skip_tags: &HashSet<String>,
+    task_selection: &TaskSelection,
 ) -> Tasks {
-    tasks.retain(|_, task| {
-        let matches_groups = task_matches_groups(task, group_selection);
-        let matches_tags = task_matches_tags(task, tag_selection);
-
-        matches_groups && (task.important || matches_tags)
-    });
+    tasks.retain(|_, task| task_is_selected(task, tag_selection, skip_tags, task_selection));
 
     tasks
 }
 
+/// Selects the tasks like [`select_tasks`] and adds the tasks they require.
+/// Required tasks excluded via `--skip-tag` are left out and returned
+/// alongside the selection.
 pub fn select_tasks_with_requires(
     all_tasks: Tasks,
-    group_selection: &GroupSelection,
     tag_selection: &TagSelection,
-) -> Tasks {
+    skip_tags: &HashSet<String>,
+    task_selection: &TaskSelection,
+) -> (Tasks, Vec<ExcludedRequirement>) {
     let all_tags: HashSet<&String> = all_tasks
         .values()
         .flat_map(|task| task.tags.iter())
@@ -108,35 +287,57 @@ pub fn select_tasks_with_requires(
     let tasks_with_tag = |tag: &String| -> Vec<&String> {
         all_tasks
             .iter()
-            .filter(|(_, task)| {
-                task.tags.contains(tag) && task_matches_groups(task, group_selection)
-            })
+            .filter(|(_, task)| task.tags.contains(tag))
             .map(|(name, _)| name)
             .collect()
     };
 
     let mut selected_task_names: HashSet<String> = all_tasks
         .iter()
-        .filter(|(_, task)| {
-            task_matches_groups(task, group_selection)
-                && (task.important || task_matches_tags(task, tag_selection))
-        })
+        .filter(|(_, task)| task_is_selected(task, tag_selection, skip_tags, task_selection))
         .map(|(name, _)| name.clone())
         .collect();
 
-    let mut tasks_to_expand: Vec<String> = selected_task_names.iter().cloned().collect();
+    let mut excluded_requirements = Vec::new();
+    let mut excluded_task_names = HashSet::new();
+
+    // Expanded in definition order and with sorted requires, so that the
+    // excluded requirements are reported in the same order on every run
+    let mut tasks_to_expand: Vec<String> = all_tasks
+        .keys()
+        .rev()
+        .filter(|name| selected_task_names.contains(*name))
+        .cloned()
+        .collect();
 
     while let Some(task_name) = tasks_to_expand.pop() {
         let Some(task) = all_tasks.get(&task_name) else {
             continue;
         };
 
-        for dependency_tag in &task.requires {
+        let mut requires: Vec<&String> = task.requires.iter().collect();
+        requires.sort();
+
+        for dependency_tag in requires {
             if !all_tags.contains(dependency_tag) {
                 continue;
             }
 
             for name in tasks_with_tag(dependency_tag) {
+                if selected_task_names.contains(name) {
+                    continue;
+                }
+
+                if task_is_excluded(&all_tasks[name], skip_tags, task_selection) {
+                    if excluded_task_names.insert(name.clone()) {
+                        excluded_requirements.push(ExcludedRequirement {
+                            task: name.clone(),
+                            required_by: task_name.clone(),
+                        });
+                    }
+                    continue;
+                }
+
                 if selected_task_names.insert(name.clone()) {
                     tasks_to_expand.push(name.clone());
                 }
@@ -147,7 +348,7 @@ pub fn select_tasks_with_requires(
     let mut selected_tasks = all_tasks;
     selected_tasks.retain(|name, _| selected_task_names.contains(name));
 
-    selected_tasks
+    (selected_tasks, excluded_requirements)
 }
 
 pub fn select_groups_for_system<'a>(
@@ -161,20 +362,413 @@ pub fn select_groups_for_system<'a>(
         .collect()
 }
 
+/// Variables of the system merged over the variables of its groups. Groups
+/// are applied in alphabetical order, so a later group overrides an earlier
+/// one and the system overrides all of them.
+pub fn inherit_group_vars(groups: &TargetGroups, system: &TargetSystem) -> Vars {
+    let mut system_groups = select_groups_for_system(groups, &system.name);
+    system_groups.sort();
+
+    let mut vars = Vars::new();
+
+    for group_name in system_groups {
+        vars.extend(groups[group_name].vars.clone());
+    }
+
+    vars.extend(system.vars.clone());
+
+    vars
+}
+
 pub fn select_tasks_for_system<'a>(
     tasks: &'a Tasks,
     system_name: &str,
     system_groups: &[&String],
 ) -> Vec<&'a Task> {
+    tasks
+        .values()
+        .filter(|task| task_targets_system(task, system_name, system_groups))
+        .collect()
+}
+
+/// Names of the tasks selected via `--task` or `--tag` that are `planned` on
+/// none of the selected systems, in definition order. Tasks that are only
+/// part of the selection because they are important, required or selected
+/// via `--all-tags` are left out, as they commonly target other systems.
+pub fn select_untargeted_tasks<'a>(
+    tasks: &Tasks,
+    tag_selection: &TagSelection,
+    task_selection: &TaskSelection,
+    planned: impl Iterator<Item = &'a Task>,
+) -> Vec<String> {
+    let planned: HashSet<&String> = planned.map(|task| &task.name).collect();
+
     tasks
         .values()
         .filter(|task| {
-            task.targets.is_empty()
-                || task.targets.contains(system_name)
-                || task
-                    .targets
-                    .iter()
-                    .any(|target| system_groups.contains(&target))
+            task_selection.contains(&task.name)
+                || matches!(tag_selection, TagSelection::Set(_))
+                    && task_matches_tags(task, tag_selection)
         })
+        .filter(|task| !planned.contains(&task.name))
+        .map(|task| task.name.clone())
+        .collect()
+}
+
+/// Orders tasks the way they run on a system: in definition order, followed by
+/// the tasks that only run when notified.
+pub fn sort_by_execution_order<T: Borrow<Task>>(tasks: &mut [T]) {
+    tasks.sort_by_key(|task| task.borrow().on_notify);
+}
+
+/// Drops the tasks planned before `start_at`, keeping important tasks.
+/// Plans that do not contain `start_at` keep only their important tasks.
+pub fn select_tasks_starting_at(tasks: Vec<Task>, start_at: &str) -> Vec<Task> {
+    let start_index = tasks
+        .iter()
+        .position(|task| task.name == start_at)
+        .unwrap_or(tasks.len());
+
+    tasks
+        .into_iter()
+        .enumerate()
+        .filter(|(index, task)| *index >= start_index || task.important)
+        .map(|(_, task)| task)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::collections::HashSet;
+
+    use super::{
+        GroupSelection, SystemSelection, TagSelection, TaskSelection, flatten_groups,
+        inherit_group_vars, matches_pattern, select_groups, select_groups_for_system,
+        select_systems, select_tasks_for_system, select_tasks_with_requires,
+        select_untargeted_tasks, sort_by_execution_order,
+    };
+    use crate::memory::{
+        target_groups::{TargetGroup, TargetGroups},
+        target_systems::{TargetSystem, TargetSystemKind, TargetSystems, Vars},
+        tasks::{OnFailBehavior, Task, Tasks},
+    };
+
+    fn vars(value: serde_json::Value) -> Vars {
+        value.as_object().cloned().unwrap()
+    }
+
+    fn group(name: &str, members: &[&str], group_vars: serde_json::Value) -> (String, TargetGroup) {
+        (
+            name.to_string(),
+            TargetGroup {
+                name: name.to_string(),
+                members: members.iter().map(|member| member.to_string()).collect(),
+                vars: vars(group_vars),
+                confirm: false,
+            },
+        )
+    }
+
+    fn names(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn task(
+        lua: &mlua::Lua,
+        name: &str,
+        tags: &[&str],
+        requires: &[&str],
+        on_notify: bool,
+    ) -> (String, Task) {
+        let mut tags = names(tags);
+        tags.insert(name.to_string());
+
+        (
+            name.to_string(),
+            Task {
+                name: name.to_string(),
+                handler: lua.create_function(|_, ()| Ok(())).unwrap(),
+                when: None,
+                on_fail: OnFailBehavior::default(),
+                tags,
+                targets: HashSet::new(),
+                requires: names(requires),
+                important: false,
+                on_notify,
+                timeout: None,
+                retries: 0,
+                serial: None,
+                params: Default::default(),
+                result: None,
+                state: None,
+                error: None,
+                duration: None,
+            },
+        )
+    }
+
+    /// Names of the planned tasks in execution order and the excluded
+    /// requirements of selecting the tag `app` while skipping `legacy`.
+    fn plan(lua: &mlua::Lua) -> (Vec<String>, Vec<(String, String)>) {
+        let tasks = Tasks::from_iter([
+            task(lua, "restart", &[], &[], true),
+            task(lua, "packages", &["base"], &[], false),
+            task(lua, "old_packages", &["base", "legacy"], &[], false),
+            task(lua, "users", &["base"], &[], false),
+            task(lua, "config", &["app"], &["base", "old_config"], false),
+            task(lua, "old_config", &["legacy"], &[], false),
+            task(
+                lua,
+                "deploy",
+                &["app"],
+                &["config", "restart", "base"],
+                false,
+            ),
+            task(lua, "unrelated", &[], &[], false),
+        ]);
+
+        let (selected, excluded) = select_tasks_with_requires(
+            tasks,
+            &TagSelection::Set(names(&["app"])),
+            &names(&["legacy"]),
+            &TaskSelection::None,
+        );
+
+        let mut planned: Vec<Task> = selected.into_values().collect();
+        sort_by_execution_order(&mut planned);
+
+        (
+            planned.into_iter().map(|task| task.name).collect(),
+            excluded
+                .into_iter()
+                .map(|excluded| (excluded.task, excluded.required_by))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn tasks_are_planned_in_the_same_order_on_every_run() {
+        let lua = mlua::Lua::new();
+
+        let (planned, excluded) = plan(&lua);
+
+        assert_eq!(
+            planned,
+            vec!["packages", "users", "config", "deploy", "restart"]
+        );
+        assert_eq!(
+            excluded,
+            vec![
+                ("old_packages".to_string(), "config".to_string()),
+                ("old_config".to_string(), "config".to_string()),
+            ]
+        );
+
+        // Every run creates new hash sets with different iteration orders
+        for _ in 0..50 {
+            assert_eq!(plan(&lua), (planned.clone(), excluded.clone()));
+        }
+    }
+
+    #[test]
+    fn patterns_match_wildcards() {
+        assert!(matches_pattern("web-1", "web-1"));
+        assert!(!matches_pattern("web-1", "web-10"));
+        assert!(matches_pattern("web-*", "web-10"));
+        assert!(matches_pattern("web-*", "web-"));
+        assert!(!matches_pattern("web-*", "db-1"));
+        assert!(matches_pattern("*-prod-*", "eu-prod-web"));
+        assert!(matches_pattern("web-?", "web-1"));
+        assert!(!matches_pattern("web-?", "web-10"));
+        assert!(matches_pattern("a*b*c", "axxbxxbc"));
+        assert!(!matches_pattern("a*b*c", "axxbxxb"));
+    }
+
+    #[test]
+    fn system_vars_override_group_vars_in_alphabetical_order() {
+        let groups = TargetGroups::from([
+            group("prod", &["web-1"], json!({ "env": "prod", "replicas": 3 })),
+            group(
+                "eu",
+                &["web-1"],
+                json!({ "env": "eu", "region": "eu-west" }),
+            ),
+            group("other", &["db-1"], json!({ "env": "other" })),
+        ]);
+        let system = TargetSystem {
+            name: "web-1".to_string(),
+            kind: TargetSystemKind::Local,
+            vars: vars(json!({ "replicas": 5 })),
+        };
+
+        assert_eq!(
+            inherit_group_vars(&groups, &system),
+            vars(json!({ "env": "prod", "region": "eu-west", "replicas": 5 }))
+        );
+    }
+
+    #[test]
+    fn nested_groups_are_flattened() {
+        let groups = TargetGroups::from([
+            group("all-web", &["web-eu", "web-us", "web-1"], json!({})),
+            group("web-eu", &["web-1", "web-2"], json!({})),
+            group("web-us", &["web-3"], json!({})),
+            group("loop-a", &["loop-b", "db-1"], json!({})),
+            group("loop-b", &["loop-a"], json!({})),
+        ]);
+
+        let flattened = flatten_groups(&groups);
+
+        assert_eq!(
+            flattened["all-web"].members,
+            vec!["web-1", "web-2", "web-3"]
+        );
+        assert_eq!(flattened["web-eu"].members, vec!["web-1", "web-2"]);
+        assert_eq!(flattened["loop-b"].members, vec!["db-1"]);
+    }
+
+    /// The tasks that run on each system, as `system: task, …` sorted by
+    /// system, when the systems are selected like `--group`, `--system` and
+    /// `--limit` do.
+    fn resolve(lua: &mlua::Lua, groups: &[&str], systems: &[&str], limit: &[&str]) -> Vec<String> {
+        let all_systems = TargetSystems::from_iter(["web-1", "web-2", "db-1"].map(|name| {
+            (
+                name.to_string(),
+                TargetSystem {
+                    name: name.to_string(),
+                    kind: TargetSystemKind::Local,
+                    vars: Vars::new(),
+                },
+            )
+        }));
+        let all_groups = flatten_groups(&TargetGroups::from([
+            group("web", &["web-1", "web-2"], json!({})),
+            group("db", &["db-1"], json!({})),
+            group("prod", &["web", "db"], json!({})),
+        ]));
+        let targeted_task = |name: &str, targets: &[&str]| {
+            let (name, mut task) = task(lua, name, &[], &[], false);
+            task.targets = names(targets);
+            (name, task)
+        };
+        let (tasks, _) = select_tasks_with_requires(
+            Tasks::from_iter([
+                targeted_task("everywhere", &[]),
+                targeted_task("web_group", &["web"]),
+                targeted_task("db_system", &["db-1"]),
+                targeted_task("nested_group", &["prod"]),
+                targeted_task("mixed", &["web-2", "db"]),
+            ]),
+            &TagSelection::All,
+            &HashSet::new(),
+            &TaskSelection::None,
+        );
+
+        let selection = |system_names: &[&str]| match system_names {
+            [] => SystemSelection::None,
+            system_names => SystemSelection::Set(names(system_names)),
+        };
+        let group_selection = match groups {
+            [] => GroupSelection::None,
+            groups => GroupSelection::Set(names(groups)),
+        };
+        let limit = match limit {
+            [] => SystemSelection::All,
+            limit => selection(limit),
+        };
+
+        let selected_groups = select_groups(all_groups.clone(), &group_selection);
+        let mut selected_systems =
+            select_systems(all_systems, &selected_groups, &selection(systems));
+        selected_systems.retain(|name, _| limit.contains(name));
+
+        let mut resolved: Vec<String> = selected_systems
+            .keys()
+            .map(|system_name| {
+                let system_groups = select_groups_for_system(&all_groups, system_name);
+                let tasks: Vec<String> =
+                    select_tasks_for_system(&tasks, system_name, &system_groups)
+                        .into_iter()
+                        .map(|task| task.name.clone())
+                        .collect();
+
+                format!("{}: {}", system_name, tasks.join(", "))
+            })
+            .collect();
+        resolved.sort();
+
+        resolved
+    }
+
+    #[test]
+    fn task_targets_are_resolved_against_the_selected_systems() {
+        let lua = mlua::Lua::new();
+
+        assert_eq!(
+            resolve(&lua, &["*"], &[], &[]),
+            vec![
+                "db-1: everywhere, db_system, nested_group, mixed",
+                "web-1: everywhere, web_group, nested_group",
+                "web-2: everywhere, web_group, nested_group, mixed",
+            ]
+        );
+        // Selecting a group does not drop tasks that target one of its
+        // systems by name or another group containing them
+        assert_eq!(
+            resolve(&lua, &["web"], &[], &[]),
+            vec![
+                "web-1: everywhere, web_group, nested_group",
+                "web-2: everywhere, web_group, nested_group, mixed",
+            ]
+        );
+        assert_eq!(
+            resolve(&lua, &["web"], &["db-1"], &[]),
+            vec![
+                "db-1: everywhere, db_system, nested_group, mixed",
+                "web-1: everywhere, web_group, nested_group",
+                "web-2: everywhere, web_group, nested_group, mixed",
+            ]
+        );
+        assert_eq!(
+            resolve(&lua, &[], &["web-2"], &[]),
+            vec!["web-2: everywhere, web_group, nested_group, mixed"]
+        );
+        assert_eq!(
+            resolve(&lua, &["prod"], &[], &["db-*"]),
+            vec!["db-1: everywhere, db_system, nested_group, mixed"]
+        );
+    }
+
+    #[test]
+    fn only_tasks_selected_by_name_or_tag_are_reported_as_untargeted() {
+        let lua = mlua::Lua::new();
+        let tasks = Tasks::from_iter([
+            task(&lua, "planned", &["web"], &[], false),
+            task(&lua, "named", &[], &[], false),
+            task(&lua, "tagged", &["web"], &[], false),
+            task(&lua, "required", &["base"], &[], false),
+        ]);
+        let planned = [&tasks["planned"]];
+
+        assert_eq!(
+            select_untargeted_tasks(
+                &tasks,
+                &TagSelection::Set(names(&["w*"])),
+                &TaskSelection::Set(names(&["named"])),
+                planned.into_iter(),
+            ),
+            vec!["named", "tagged"]
+        );
+        assert!(
+            select_untargeted_tasks(
+                &tasks,
+                &TagSelection::All,
+                &TaskSelection::None,
+                planned.into_iter(),
+            )
+            .is_empty()
+        );
+    }
+}
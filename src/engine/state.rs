@@ -1,12 +1,15 @@
+use std::time::Duration;
+
 use crate::{
     error::MutexLockError,
     memory::{
         SharedMemory,
+        config::{Config, ConfigMemory},
         target_groups::{TargetGroups, TargetGroupsMemory},
         target_systems::{TargetSystems, TargetSystemsMemory},
         tasks::{
-            TaskState, Tasks, TasksErrorSetError, TasksMemory, TasksResultSetError,
-            TasksStateSetError,
+            TaskState, Tasks, TasksDurationSetError, TasksErrorSetError, TasksMemory,
+            TasksResultSetError, TasksStateSetError,
         },
     },
 };
@@ -15,6 +18,7 @@ pub struct State {
     target_systems: SharedMemory<TargetSystemsMemory>,
     target_groups: SharedMemory<TargetGroupsMemory>,
     tasks: SharedMemory<TasksMemory>,
+    config: SharedMemory<ConfigMemory>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -44,16 +48,25 @@ pub enum TasksErrorStateSetError {
     TaskErrorSet(#[from] TasksErrorSetError),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to set task's duration")]
+pub enum TasksDurationStateSetError {
+    Lock(#[from] MutexLockError),
+    TaskDurationSet(#[from] TasksDurationSetError),
+}
+
 impl State {
     pub fn new(
         target_systems: SharedMemory<TargetSystemsMemory>,
         target_groups: SharedMemory<TargetGroupsMemory>,
         tasks: SharedMemory<TasksMemory>,
+        config: SharedMemory<ConfigMemory>,
     ) -> Self {
         Self {
             target_systems,
             target_groups,
             tasks,
+            config,
         }
     }
 
@@ -73,6 +86,10 @@ impl State {
         Ok(self.tasks.lock().map_err(|_| MutexLockError)?.all())
     }
 
+    pub fn config(&self) -> Result<Config, MutexLockError> {
+        Ok(self.config.lock().map_err(|_| MutexLockError)?.get())
+    }
+
     pub fn reset_execution_state(&self) -> Result<(), TasksExecutionStateResetError> {
         let mut guard = self.tasks.lock().map_err(|_| MutexLockError)?;
 
@@ -112,4 +129,16 @@ impl State {
 
         Ok(())
     }
+
+    pub fn set_task_duration(
+        &self,
+        name: &str,
+        duration: Duration,
+    ) -> Result<(), TasksDurationStateSetError> {
+        let mut guard = self.tasks.lock().map_err(|_| MutexLockError)?;
+
+        guard.set_task_duration(name, duration)?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,208 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tabled::{
+    Table, Tabled,
+    settings::{Padding, Style, object::Columns},
+};
+
+use super::retry::RetryUsage;
+use crate::{
+    memory::tasks::{TaskState, Tasks},
+    progress::format_duration,
+    redact::redact,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RunStatus {
+    Success,
+    Failed,
+    Aborted,
+    /// Stopped by Ctrl-C
+    Interrupted,
+    Error,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RunTotals {
+    pub success: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedTask {
+    pub system: String,
+    pub task: String,
+    pub error: Option<String>,
+}
+
+/// Outcome and timing of a task that ran, or was skipped, on a system
+#[derive(Debug, Serialize)]
+pub struct TaskReport {
+    pub system: String,
+    pub task: String,
+    pub state: TaskState,
+    /// Time spent in the handler, including retries. Skipped tasks have none.
+    pub duration_seconds: Option<f64>,
+}
+
+/// A system that failed as a whole, e.g. because it could not be connected to
+#[derive(Debug, Serialize)]
+pub struct FailedSystem {
+    pub system: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub status: RunStatus,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub totals: RunTotals,
+    pub tasks: Vec<TaskReport>,
+    pub failed_tasks: Vec<FailedTask>,
+    pub failed_systems: Vec<FailedSystem>,
+    pub retries: RetryUsage,
+    /// Why the run stopped before executing any task, e.g. invalid task definitions
+    pub error: Option<String>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl RunSummary {
+    pub fn start() -> Self {
+        let now = jiff::Timestamp::now();
+
+        Self {
+            run_id: format!("{}-{}", now.strftime("%Y%m%dT%H%M%SZ"), std::process::id()),
+            status: RunStatus::Success,
+            started_at: now.to_string(),
+            finished_at: None,
+            duration_seconds: None,
+            totals: RunTotals::default(),
+            tasks: Vec::new(),
+            failed_tasks: Vec::new(),
+            failed_systems: Vec::new(),
+            retries: RetryUsage::default(),
+            error: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Records the outcome of the given tasks on a system. Tasks that did not
+    /// run (e.g. after an abort) have no state and are not counted.
+    pub fn record_system<'a>(
+        &mut self,
+        system_name: &str,
+        task_names: impl IntoIterator<Item = &'a String>,
+        all_tasks: &Tasks,
+    ) {
+        for task in task_names
+            .into_iter()
+            .filter_map(|task_name| all_tasks.get(task_name))
+        {
+            if let Some(state) = task.state {
+                self.tasks.push(TaskReport {
+                    system: system_name.to_string(),
+                    task: task.name.clone(),
+                    state,
+                    duration_seconds: task.duration.map(|duration| duration.as_secs_f64()),
+                });
+            }
+
+            match task.state {
+                Some(TaskState::Success) => self.totals.success += 1,
+                Some(TaskState::Skipped) => self.totals.skipped += 1,
+                Some(TaskState::Failed) => {
+                    self.totals.failed += 1;
+                    self.failed_tasks.push(FailedTask {
+                        system: system_name.to_string(),
+                        task: task.name.clone(),
+                        error: task.error.clone(),
+                    });
+                }
+                None => {}
+            }
+        }
+    }
+
+    pub fn record_failed_system(&mut self, system_name: &str, error: String) {
+        self.failed_systems.push(FailedSystem {
+            system: system_name.to_string(),
+            error,
+        });
+    }
+
+    pub fn finish(&mut self, status: RunStatus) {
+        self.status = match status {
+            RunStatus::Success if self.totals.failed > 0 || !self.failed_systems.is_empty() => {
+                RunStatus::Failed
+            }
+            status => status,
+        };
+        self.finished_at = Some(jiff::Timestamp::now().to_string());
+        self.duration_seconds = Some(self.started.elapsed().as_secs_f64());
+    }
+
+    /// Table of all recorded tasks, slowest first, followed by the totals.
+    pub fn report_table(&self) -> String {
+        let mut tasks = self.tasks.iter().collect::<Vec<_>>();
+        tasks.sort_by(|a, b| {
+            let seconds = |task: &TaskReport| task.duration_seconds.unwrap_or_default();
+            seconds(b).total_cmp(&seconds(a))
+        });
+
+        let rows = tasks.into_iter().map(|task| TaskReportRow {
+            task: task.task.clone(),
+            system: task.system.clone(),
+            state: task.state.to_string(),
+            duration: task
+                .duration_seconds
+                .map(|seconds| format_duration(Duration::from_secs_f64(seconds)))
+                .unwrap_or_default(),
+        });
+
+        let mut table = Table::new(rows);
+        table
+            .with(Style::blank())
+            .modify(Columns::first(), Padding::new(0, 1, 0, 0))
+            .modify(Columns::last(), Padding::new(1, 0, 0, 0));
+
+        format!(
+            "{}\n\n{} succeeded, {} failed, {} skipped in {}",
+            table,
+            self.totals.success,
+            self.totals.failed,
+            self.totals.skipped,
+            format_duration(self.started.elapsed()),
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to write run report to {0:?}")]
+pub struct ReportWriteError(PathBuf, #[source] std::io::Error);
+
+pub fn write_report(path: &Path, summary: &RunSummary) -> Result<(), ReportWriteError> {
+    let content = serde_json::to_string_pretty(summary).expect("run summary is serializable");
+
+    std::fs::write(path, redact(&content).as_bytes())
+        .map_err(|error| ReportWriteError(path.to_path_buf(), error))
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct TaskReportRow {
+    task: String,
+    system: String,
+    state: String,
+    duration: String,
+}
@@ -0,0 +1,110 @@
+//! Task files that are loaded after the entrypoint, so that tasks can be
+//! split across files without `require`-ing each of them.
+
+use std::path::{Path, PathBuf};
+
+pub static DEFAULT_TASKS_DIRECTORY: &str = "tasks";
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to list task files in {path:?}")]
+pub struct TaskFilesListingError {
+    path: PathBuf,
+    #[source]
+    source: std::io::Error,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to load task file {path:?}")]
+pub struct TaskFileLoadError {
+    pub path: PathBuf,
+    #[source]
+    pub source: mlua::Error,
+}
+
+/// All `.lua` files below `directory`, sorted by path. Files of a directory
+/// come before those of its subdirectories. A missing directory has no
+/// task files.
+pub fn find_task_files(directory: &Path) -> Result<Vec<PathBuf>, TaskFilesListingError> {
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let to_error = |source| TaskFilesListingError {
+        path: directory.to_path_buf(),
+        source,
+    };
+
+    let mut files = Vec::new();
+    let mut subdirectories = Vec::new();
+
+    for entry in std::fs::read_dir(directory).map_err(to_error)? {
+        let path = entry.map_err(to_error)?.path();
+
+        if path.is_dir() {
+            subdirectories.push(path);
+        } else if path.extension().is_some_and(|extension| extension == "lua") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    subdirectories.sort();
+
+    for subdirectory in subdirectories {
+        files.extend(find_task_files(&subdirectory)?);
+    }
+
+    Ok(files)
+}
+
+/// Name under which `require` loads the file, e.g. `tasks.web.nginx` for
+/// `tasks/web/nginx.lua`.
+pub fn module_name(path: &Path) -> String {
+    path.with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(component) => Some(component.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{find_task_files, module_name};
+
+    #[test]
+    fn task_files_are_sorted_with_subdirectories_last() {
+        let root = std::env::temp_dir().join(format!("arc-task-files-{}", std::process::id()));
+        let directory = root.join("tasks");
+
+        for file in ["b.lua", "a.lua", "notes.txt", "web/nginx.lua", "app/z.lua"] {
+            let path = directory.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let files = find_task_files(&directory).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            files,
+            ["a.lua", "b.lua", "app/z.lua", "web/nginx.lua"]
+                .map(|file| directory.join(file))
+                .to_vec()
+        );
+        assert!(find_task_files(&root.join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn module_names_match_require() {
+        assert_eq!(
+            module_name(Path::new("tasks/web/nginx.lua")),
+            "tasks.web.nginx"
+        );
+        assert_eq!(module_name(&PathBuf::from("./tasks/app.lua")), "tasks.app");
+    }
+}
@@ -1,11 +1,61 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
-use crate::memory::{target_groups::TargetGroups, target_systems::TargetSystems, tasks::Tasks};
+use indexmap::IndexMap;
 
-use super::selection::{GroupSelection, SystemSelection, TagSelection};
+use crate::memory::{
+    target_groups::TargetGroups,
+    target_systems::TargetSystems,
+    tasks::{Task, Tasks},
+};
+
+use super::selection::{
+    GroupSelection, SystemSelection, TagSelection, TaskSelection, matches_pattern,
+};
 
 #[derive(Debug, thiserror::Error)]
-#[error("The selected group {0:?} does not exist")]
+pub enum EmptyDefinitionError {
+    #[error(
+        "No systems are defined by {entrypoint:?}. Define at least one, e.g. `targets.systems[\"localhost\"] = {{ type = \"local\" }}`"
+    )]
+    NoSystems { entrypoint: PathBuf },
+    #[error(
+        "No tasks are defined by {entrypoint:?} or the files in {tasks_directory:?}. Define at least one, e.g. `tasks[\"hello\"] = {{ handler = function(system) end }}`"
+    )]
+    NoTasks {
+        entrypoint: PathBuf,
+        tasks_directory: PathBuf,
+    },
+}
+
+/// Ensures that systems and tasks are defined. `entrypoint` and
+/// `tasks_directory` are only used to tell where they are expected.
+pub fn validate_definitions(
+    systems: &TargetSystems,
+    tasks: &Tasks,
+    entrypoint: &Path,
+    tasks_directory: &Path,
+) -> Result<(), EmptyDefinitionError> {
+    if systems.is_empty() {
+        return Err(EmptyDefinitionError::NoSystems {
+            entrypoint: entrypoint.to_path_buf(),
+        });
+    }
+
+    if tasks.is_empty() {
+        return Err(EmptyDefinitionError::NoTasks {
+            entrypoint: entrypoint.to_path_buf(),
+            tasks_directory: tasks_directory.to_path_buf(),
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The selected group {0:?} does not match any group")]
 pub struct MissingSelectedGroupError(pub Vec<String>);
 
 pub fn validate_selected_groups(
@@ -15,7 +65,7 @@ pub fn validate_selected_groups(
     if let GroupSelection::Set(requested) = selection {
         let missing: Vec<String> = requested
             .iter()
-            .filter(|name| !groups.contains_key(*name))
+            .filter(|pattern| !groups.keys().any(|name| matches_pattern(pattern, name)))
             .cloned()
             .collect();
 
@@ -28,7 +78,7 @@ pub fn validate_selected_groups(
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("The selected system {0:?} does not exist")]
+#[error("The selected system {0:?} does not match any system")]
 pub struct MissingSelectedSystemError(Vec<String>);
 
 pub fn validate_selected_systems(
@@ -38,7 +88,7 @@ pub fn validate_selected_systems(
     if let SystemSelection::Set(requested) = selection {
         let missing: Vec<String> = requested
             .iter()
-            .filter(|name| !systems.contains_key(*name))
+            .filter(|pattern| !systems.keys().any(|name| matches_pattern(pattern, name)))
             .cloned()
             .collect();
 
@@ -100,7 +150,7 @@ pub fn validate_group_members(
             let missing: Vec<String> = group
                 .members
                 .iter()
-                .filter(|member| !systems.contains_key(*member))
+                .filter(|member| !systems.contains_key(*member) && !groups.contains_key(*member))
                 .cloned()
                 .collect();
 
@@ -119,6 +169,64 @@ pub fn validate_group_members(
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Groups contain each other: {}", format_cycle(.0))]
+pub struct GroupCycleError(pub Vec<String>);
+
+pub fn format_cycle(cycle: &[String]) -> String {
+    cycle
+        .iter()
+        .map(|name| format!("{name:?}"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Ensures that no group contains itself through other groups, which would
+/// make its membership infinite.
+pub fn validate_group_cycles(groups: &TargetGroups) -> Result<(), GroupCycleError> {
+    fn visit(
+        groups: &TargetGroups,
+        name: &String,
+        path: &mut Vec<String>,
+        done: &mut HashSet<String>,
+    ) -> Result<(), GroupCycleError> {
+        if let Some(start) = path.iter().position(|visited| visited == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.clone());
+
+            return Err(GroupCycleError(cycle));
+        }
+
+        if done.contains(name) {
+            return Ok(());
+        }
+
+        path.push(name.clone());
+
+        for member in &groups[name].members {
+            if groups.contains_key(member) {
+                visit(groups, member, path, done)?;
+            }
+        }
+
+        path.pop();
+        done.insert(name.clone());
+
+        Ok(())
+    }
+
+    let mut names: Vec<&String> = groups.keys().collect();
+    names.sort();
+
+    let mut done = HashSet::new();
+
+    for name in names {
+        visit(groups, name, &mut Vec::new(), &mut done)?;
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct UndefinedTaskTargetsError(pub Vec<(String, Vec<String>)>);
 
@@ -169,7 +277,7 @@ pub fn validate_task_targets(
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("The selected tag {0:?} does not exist")]
+#[error("The selected tag {0:?} does not match any tag")]
 pub struct MissingSelectedTagError(Vec<String>);
 
 pub fn validate_selected_tags(
@@ -181,7 +289,7 @@ pub fn validate_selected_tags(
 
         let missing: Vec<String> = requested
             .iter()
-            .filter(|tag| !all_tags.contains(tag))
+            .filter(|pattern| !all_tags.iter().any(|tag| matches_pattern(pattern, tag)))
             .cloned()
             .collect();
 
@@ -193,6 +301,144 @@ pub fn validate_selected_tags(
     Ok(())
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("The selected task {0:?} does not exist")]
+pub struct MissingSelectedTaskError(Vec<String>);
+
+pub fn validate_selected_tasks(
+    tasks: &Tasks,
+    selection: &TaskSelection,
+) -> Result<(), MissingSelectedTaskError> {
+    if let TaskSelection::Set(requested) = selection {
+        let missing: Vec<String> = requested
+            .iter()
+            .filter(|name| !tasks.contains_key(*name))
+            .cloned()
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(MissingSelectedTaskError(missing));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The start task {0:?} is not part of the execution plan")]
+pub struct UnplannedStartTaskError(String);
+
+pub fn validate_start_task<'a>(
+    mut planned_tasks: impl Iterator<Item = &'a Task>,
+    start_at: &str,
+) -> Result<(), UnplannedStartTaskError> {
+    if !planned_tasks.any(|task| task.name == start_at) {
+        return Err(UnplannedStartTaskError(start_at.to_string()));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Selected tasks target none of the selected systems: {}",
+    .0.join(", ")
+)]
+pub struct UntargetedTasksError(pub Vec<String>);
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "The limit {patterns:?} does not match any selected system, selected are: {}",
+    available.join(", ")
+)]
+pub struct UnmatchedLimitError {
+    patterns: Vec<String>,
+    available: Vec<String>,
+}
+
+/// Ensures that every `--limit` pattern matches one of the `selected` systems.
+pub fn validate_limit(
+    selected: &TargetSystems,
+    limit: &SystemSelection,
+) -> Result<(), UnmatchedLimitError> {
+    if let SystemSelection::Set(patterns) = limit {
+        let mut unmatched: Vec<String> = patterns
+            .iter()
+            .filter(|pattern| !selected.keys().any(|name| matches_pattern(pattern, name)))
+            .cloned()
+            .collect();
+
+        if !unmatched.is_empty() {
+            unmatched.sort();
+
+            return Err(UnmatchedLimitError {
+                patterns: unmatched,
+                available: selected.keys().cloned().collect(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Missing required task parameters: {}", .0.join(", "))]
+pub struct MissingTaskParamsError(Vec<String>);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid task parameters: {}", .0.join(", "))]
+pub struct InvalidTaskParamsError(Vec<String>);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to validate task parameters")]
+pub enum TaskParamsError {
+    Missing(#[from] MissingTaskParamsError),
+    Invalid(#[from] InvalidTaskParamsError),
+}
+
+pub fn validate_task_params<'a>(
+    planned_tasks: impl Iterator<Item = &'a Task>,
+    vars: &IndexMap<String, String>,
+) -> Result<(), TaskParamsError> {
+    let mut checked_tasks = HashSet::new();
+    let mut missing = Vec::new();
+    let mut invalid = Vec::new();
+
+    for task in planned_tasks {
+        if !checked_tasks.insert(&task.name) {
+            continue;
+        }
+
+        for (param_name, param) in &task.params {
+            match vars.get(param_name) {
+                Some(value) if param.r#type.parse(value).is_none() => {
+                    invalid.push(format!(
+                        "{}.{} (expected {}, got {:?})",
+                        task.name, param_name, param.r#type, value
+                    ));
+                }
+                Some(_) => {}
+                None if param.required && param.default.is_none() => {
+                    missing.push(format!("{}.{}", task.name, param_name));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        missing.sort();
+        return Err(MissingTaskParamsError(missing).into());
+    }
+
+    if !invalid.is_empty() {
+        invalid.sort();
+        return Err(InvalidTaskParamsError(invalid).into());
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct UndefinedRequiresError(Vec<(String, Vec<String>)>);
 
@@ -236,3 +482,102 @@ pub fn validate_task_requires(tasks: &Tasks) -> Result<(), UndefinedRequiresErro
         Err(UndefinedRequiresError(undefined_requires))
     }
 }
+
+/// Returns tasks that require each other, directly or through other tasks,
+/// with the first of them repeated at the end. Requires only decide which
+/// tasks are planned, so such a cycle is valid, but at least one of its
+/// tasks runs before a task it requires. A task that carries a tag it
+/// requires, e.g. the tag of its own file, does not require itself.
+pub fn find_requires_cycle(tasks: &Tasks) -> Option<Vec<String>> {
+    let required_tasks: IndexMap<String, Vec<String>> = tasks
+        .iter()
+        .map(|(name, task)| {
+            let required = tasks
+                .iter()
+                .filter(|(other_name, other)| {
+                    *other_name != name && task.requires.iter().any(|tag| other.tags.contains(tag))
+                })
+                .map(|(other_name, _)| other_name.clone())
+                .collect();
+
+            (name.clone(), required)
+        })
+        .collect();
+
+    find_cycle(&required_tasks)
+}
+
+/// Returns the first cycle of the graph, visiting nodes in order, with the
+/// node that closes the cycle repeated at the end.
+fn find_cycle(edges: &IndexMap<String, Vec<String>>) -> Option<Vec<String>> {
+    fn visit(
+        edges: &IndexMap<String, Vec<String>>,
+        name: &String,
+        path: &mut Vec<String>,
+        done: &mut HashSet<String>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|visited| visited == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.clone());
+
+            return Some(cycle);
+        }
+
+        if done.contains(name) {
+            return None;
+        }
+
+        path.push(name.clone());
+
+        for next in edges.get(name).into_iter().flatten() {
+            if let Some(cycle) = visit(edges, next, path, done) {
+                return Some(cycle);
+            }
+        }
+
+        path.pop();
+        done.insert(name.clone());
+
+        None
+    }
+
+    let mut done = HashSet::new();
+
+    edges
+        .keys()
+        .find_map(|name| visit(edges, name, &mut Vec::new(), &mut done))
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::find_cycle;
+
+    fn graph(edges: &[(&str, &[&str])]) -> IndexMap<String, Vec<String>> {
+        edges
+            .iter()
+            .map(|(name, next)| {
+                (
+                    name.to_string(),
+                    next.iter().map(|next| next.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cycles_name_their_members() {
+        let edges = graph(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"]), ("d", &["a"])]);
+
+        assert_eq!(find_cycle(&edges).unwrap(), vec!["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn self_dependency_is_a_cycle() {
+        let edges = graph(&[("a", &[]), ("b", &["a", "b"])]);
+
+        assert_eq!(find_cycle(&edges).unwrap(), vec!["b", "b"]);
+        assert_eq!(find_cycle(&graph(&[("a", &[]), ("b", &["a"])])), None);
+    }
+}
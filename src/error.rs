@@ -1,3 +1,7 @@
+use crate::engine::delegator::error::FfiPanicError;
+
+static INFRASTRUCTURE_ERROR_HINT: &str = "This was caused by the connection or the system, not by the configuration, and may be transient. Retrying the run may succeed.";
+
 #[derive(thiserror::Error)]
 pub struct ErrorReport(Box<dyn std::error::Error>);
 
@@ -9,9 +13,43 @@ impl ErrorReport {
         Self(Box::new(value))
     }
 
+    /// The error and its sources, followed by a retry hint and the details
+    /// of the failure if it was caused by the connection or the system
+    /// rather than by the configuration.
     pub fn build_report(&self) -> String {
-        let error = &self.0;
+        let report = Self::describe(self.0.as_ref());
+
+        match Self::infrastructure_error(self.0.as_ref()) {
+            Some(infrastructure_error) => format!(
+                "{}\n{}\nDetails: {}",
+                report,
+                INFRASTRUCTURE_ERROR_HINT,
+                crate::redact::redact(&format!("{:?}", infrastructure_error.0))
+            ),
+            None => report,
+        }
+    }
+
+    /// The first error in the chain that failed a system instead of being
+    /// returned to Lua, because it is not a user error.
+    fn infrastructure_error<'a>(
+        error: &'a (dyn std::error::Error + 'static),
+    ) -> Option<&'a FfiPanicError> {
+        let mut curr_err = Some(error);
+
+        while let Some(current_error) = curr_err {
+            if let Some(infrastructure_error) = current_error.downcast_ref::<FfiPanicError>() {
+                return Some(infrastructure_error);
+            }
+
+            curr_err = current_error.source();
+        }
 
+        None
+    }
+
+    /// Formats an error together with its chain of sources.
+    pub fn describe(error: &dyn std::error::Error) -> String {
         let mut message = error.to_string();
         let mut curr_err = error.source();
 
@@ -21,7 +59,7 @@ impl ErrorReport {
             curr_err = current_error.source();
         }
 
-        message
+        crate::redact::redact(&message).into_owned()
     }
 }
 
@@ -40,3 +78,33 @@ impl std::fmt::Debug for ErrorReport {
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to lock mutex")]
 pub struct MutexLockError;
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorReport, INFRASTRUCTURE_ERROR_HINT};
+    use crate::engine::delegator::error::FfiPanicError;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Task failed")]
+    struct TaskError(#[source] FfiPanicError);
+
+    #[test]
+    fn only_infrastructure_errors_are_reported_with_a_retry_hint() {
+        let user_error = std::io::Error::new(std::io::ErrorKind::NotFound, "No such file");
+        let report = ErrorReport::boxed_from(user_error).build_report();
+
+        assert_eq!(report, "No such file");
+
+        let infrastructure_error = std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "Connection reset by peer",
+        );
+        let report =
+            ErrorReport::boxed_from(TaskError(FfiPanicError(Box::new(infrastructure_error))))
+                .build_report();
+
+        assert!(report.starts_with("Task failed\nCaused by:\n    Connection reset by peer\n"));
+        assert!(report.contains(INFRASTRUCTURE_ERROR_HINT));
+        assert!(report.contains("Details: Custom { kind: ConnectionReset"));
+    }
+}
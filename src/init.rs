@@ -1,16 +1,46 @@
-use std::{io::Write, path::PathBuf};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::InitTemplate;
 
 static TYPES_LUA: &str = include_str!("types.lua");
-static ARC_LUA: &str = include_str!("arc.lua");
 static LUA_RC_JSON: &str = include_str!(".luarc.json");
+static GITIGNORE: &str = include_str!("init/gitignore");
+
+static MINIMAL_FILES: &[(&str, &str)] = &[
+    ("arc.lua", include_str!("init/minimal/arc.lua")),
+    (
+        "tasks/hello.lua",
+        include_str!("init/minimal/tasks/hello.lua"),
+    ),
+    (".env.example", include_str!("init/minimal/env.example")),
+];
+
+static WEBSERVICE_FILES: &[(&str, &str)] = &[
+    ("arc.lua", include_str!("init/webservice/arc.lua")),
+    (
+        "tasks/nginx.lua",
+        include_str!("init/webservice/tasks/nginx.lua"),
+    ),
+    (
+        "tasks/site.lua",
+        include_str!("init/webservice/tasks/site.lua"),
+    ),
+    (
+        "templates/index.html",
+        include_str!("init/webservice/templates/index.html"),
+    ),
+    (".env.example", include_str!("init/webservice/env.example")),
+];
 
 #[derive(Debug, thiserror::Error)]
 #[error("Project initialization failed")]
 pub enum InitializationFailure {
     RootDirectory(#[from] RootDirectoryCreationError),
-    LspTypes(#[from] LspTypesCreationError),
-    ArcLua(#[from] ArcLuaCreationError),
-    LuaRc(#[from] LuaRcCreationError),
+    ExistingFiles(#[from] ExistingFilesError),
+    File(#[from] FileCreationError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -18,62 +48,91 @@ pub enum InitializationFailure {
 pub struct RootDirectoryCreationError(#[source] std::io::Error);
 
 #[derive(Debug, thiserror::Error)]
-#[error("Failed to write lsp types file")]
-pub struct LspTypesCreationError(#[source] std::io::Error);
+#[error("Files already exist, nothing was written: {}", format_paths(.0))]
+pub struct ExistingFilesError(Vec<PathBuf>);
 
-#[derive(Debug, thiserror::Error)]
-#[error("Failed to write initial arc.lua")]
-pub struct ArcLuaCreationError(#[source] std::io::Error);
+fn format_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{:?}", path))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
 #[derive(Debug, thiserror::Error)]
-#[error("Failed to write initial arc.lua")]
-pub struct LuaRcCreationError(#[source] std::io::Error);
+#[error("Failed to write {path:?}")]
+pub struct FileCreationError {
+    path: PathBuf,
+    #[source]
+    source: std::io::Error,
+}
 
-pub fn init_project(project_root: PathBuf) -> Result<(), InitializationFailure> {
-    std::fs::create_dir_all(&project_root).map_err(RootDirectoryCreationError)?;
+/// Files of a new project, as paths relative to the project root and their
+/// content.
+fn project_files(template: InitTemplate) -> Vec<(&'static str, String)> {
+    let template_files = match template {
+        InitTemplate::Minimal => MINIMAL_FILES,
+        InitTemplate::Webservice => WEBSERVICE_FILES,
+    };
 
-    let mut lsp_types_path = project_root.clone();
-    lsp_types_path.push("types.lua");
+    [
+        (
+            "types.lua",
+            TYPES_LUA.replacen("{version}", env!("CARGO_PKG_VERSION"), 1),
+        ),
+        (".luarc.json", LUA_RC_JSON.to_string()),
+        (".gitignore", GITIGNORE.to_string()),
+    ]
+    .into_iter()
+    .chain(
+        template_files
+            .iter()
+            .map(|(path, content)| (*path, content.to_string())),
+    )
+    .collect()
+}
 
-    let mut lsp_types_file = std::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(lsp_types_path)
-        .map_err(LspTypesCreationError)?;
-    lsp_types_file
-        .write_all(
-            TYPES_LUA
-                .replacen("{version}", env!("CARGO_PKG_VERSION"), 1)
-                .as_bytes(),
-        )
-        .map_err(LspTypesCreationError)?;
-
-    let mut arc_lua_path = project_root.clone();
-    arc_lua_path.push("arc.lua");
-
-    let mut arc_lua_file = std::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(arc_lua_path)
-        .map_err(LspTypesCreationError)?;
-    arc_lua_file
-        .write_all(ARC_LUA.as_bytes())
-        .map_err(LspTypesCreationError)?;
-
-    let mut lua_rc_path = project_root.clone();
-    lua_rc_path.push(".luarc.json");
-
-    let mut lua_rc_file = std::fs::OpenOptions::new()
-        .create(true)
+fn write_new_file(path: &Path, content: &str) -> Result<(), FileCreationError> {
+    let to_error = |source| FileCreationError {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(to_error)?;
+    }
+
+    std::fs::OpenOptions::new()
         .write(true)
-        .truncate(true)
-        .open(lua_rc_path)
-        .map_err(LspTypesCreationError)?;
-    lua_rc_file
-        .write_all(LUA_RC_JSON.as_bytes())
-        .map_err(LspTypesCreationError)?;
+        .create_new(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(to_error)
+}
+
+/// Scaffolds a project from `template`. Nothing is written if any of its
+/// files already exists.
+pub fn init_project(
+    project_root: PathBuf,
+    template: InitTemplate,
+) -> Result<(), InitializationFailure> {
+    std::fs::create_dir_all(&project_root).map_err(RootDirectoryCreationError)?;
+
+    let files = project_files(template);
+
+    let existing_files: Vec<PathBuf> = files
+        .iter()
+        .map(|(path, _)| project_root.join(path))
+        .filter(|path| path.exists())
+        .collect();
+
+    if !existing_files.is_empty() {
+        return Err(ExistingFilesError(existing_files).into());
+    }
+
+    for (path, content) in files {
+        write_new_file(&project_root.join(path), &content)?;
+    }
 
     Ok(())
 }
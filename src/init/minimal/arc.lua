@@ -0,0 +1,11 @@
+targets.systems["node-1"] = {
+    address = "192.0.2.10",
+    user = "root",
+}
+
+targets.groups["nodes"] = {
+    members = { "node-1" },
+}
+
+-- The files in tasks/ are loaded after this file. Tasks defined in
+-- tasks/hello.lua are tagged with "tasks" and "hello".
@@ -0,0 +1,16 @@
+targets.systems["web-1"] = {
+    address = "192.0.2.10",
+    user = "root",
+}
+
+targets.systems["web-2"] = {
+    address = "192.0.2.11",
+    user = "root",
+}
+
+targets.groups["web"] = {
+    members = { "web-1", "web-2" },
+}
+
+-- The files in tasks/ are loaded after this file. Tasks defined in
+-- tasks/<name>.lua are tagged with "tasks" and "<name>".
@@ -0,0 +1,17 @@
+tasks["install_nginx"] = {
+    targets = { "web" },
+    handler = function(system)
+        system:run_command("apt-get update && apt-get install -y nginx", {
+            creates = "/usr/sbin/nginx",
+            check = true,
+        })
+    end,
+}
+
+tasks["start_nginx"] = {
+    targets = { "web" },
+    requires = { "install_nginx" },
+    handler = function(system)
+        system:run_command("systemctl enable --now nginx", { check = true })
+    end,
+}
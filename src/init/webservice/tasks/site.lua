@@ -0,0 +1,17 @@
+tasks["deploy_site"] = {
+    targets = { "web" },
+    requires = { "start_nginx" },
+    handler = function(system)
+        local page = template.render_file(
+            "templates/index.html",
+            { title = env.get("SITE_TITLE", "Hello from arc") },
+            { autoescape = true }
+        )
+        local target = system:file("/var/www/html/index.html")
+
+        if not target:exists() or tostring(target.content) ~= page then
+            target.content = page
+            log.info("Deployed index.html")
+        end
+    end,
+}
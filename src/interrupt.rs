@@ -0,0 +1,61 @@
+//! Ctrl-C handling. The first interrupt lets the running tasks finish and
+//! skips all tasks that did not start yet, a second one exits immediately.
+//! While a prompt waits for input, the first one exits immediately as well.
+//!
+//! The flag is process wide because systems of a parallel run are executed
+//! by separate engines on their own threads.
+
+use std::sync::{
+    Arc, LazyLock,
+    atomic::{AtomicBool, Ordering},
+};
+
+use signal_hook::consts::SIGINT;
+
+/// Exit status of a run stopped by Ctrl-C, following the shell convention of
+/// 128 plus the signal number.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: LazyLock<Arc<AtomicBool>> = LazyLock::new(|| Arc::new(AtomicBool::new(false)));
+static PROMPTING: LazyLock<Arc<AtomicBool>> = LazyLock::new(|| Arc::new(AtomicBool::new(false)));
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to install the Ctrl-C handler")]
+pub struct InterruptHandlerError(#[from] std::io::Error);
+
+pub fn install() -> Result<(), InterruptHandlerError> {
+    signal_hook::flag::register_conditional_shutdown(
+        SIGINT,
+        INTERRUPTED_EXIT_CODE,
+        PROMPTING.clone(),
+    )?;
+    // Registered before the flag so that it only sees earlier interrupts
+    signal_hook::flag::register_conditional_shutdown(
+        SIGINT,
+        INTERRUPTED_EXIT_CODE,
+        INTERRUPTED.clone(),
+    )?;
+    signal_hook::flag::register(SIGINT, INTERRUPTED.clone())?;
+
+    Ok(())
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Makes Ctrl-C exit immediately until the guard is dropped, as a blocking
+/// read from the terminal would not notice the interrupt.
+pub fn exit_while_prompting() -> PromptGuard {
+    PROMPTING.store(true, Ordering::Relaxed);
+
+    PromptGuard
+}
+
+pub struct PromptGuard;
+
+impl Drop for PromptGuard {
+    fn drop(&mut self) {
+        PROMPTING.store(false, Ordering::Relaxed);
+    }
+}
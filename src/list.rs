@@ -1,3 +1,6 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
 use serde::Serialize;
 use tabled::{
     Table, Tabled,
@@ -5,12 +8,17 @@ use tabled::{
 };
 
 use crate::{
-    cli::ListItemType,
-    engine::Engine,
+    cli::{ListFormat, ListItemType},
+    engine::{
+        Engine,
+        selection::{
+            InclusionReason, Selection, flatten_groups, inclusion_reason, sort_by_execution_order,
+        },
+    },
     error::MutexLockError,
     memory::{
         target_groups::TargetGroups,
-        target_systems::{TargetSystemKind, TargetSystems},
+        target_systems::{TargetSystem, TargetSystemKind, TargetSystems},
         tasks::{Task as MemoryTask, Tasks},
     },
 };
@@ -20,6 +28,8 @@ use crate::{
 pub enum ListError {
     Lock(#[from] MutexLockError),
     Serialization(#[from] serde_json::Error),
+    Csv(#[from] csv::Error),
+    Yaml(#[from] serde_yaml::Error),
 }
 
 fn format_list(items: &[String]) -> String {
@@ -44,6 +54,23 @@ struct Task {
     on_fail: String,
 }
 
+#[derive(Serialize, Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct OrderedTask {
+    #[tabled(rename = "#")]
+    position: usize,
+    name: String,
+    #[tabled(display = "format_list")]
+    tags: Vec<String>,
+    #[tabled(display = "format_list")]
+    requires: Vec<String>,
+    on_notify: bool,
+    /// Required tasks that only run after this one, so that the requirement
+    /// is not in place yet when it runs
+    #[tabled(display = "format_list", rename = "REQUIRED BUT LATER")]
+    required_but_later: Vec<String>,
+}
+
 #[derive(Serialize, Tabled)]
 #[tabled(rename_all = "UPPERCASE")]
 struct System {
@@ -64,6 +91,40 @@ struct Group {
     members: Vec<String>,
 }
 
+#[derive(Serialize)]
+struct Plan {
+    systems: Vec<PlannedSystem>,
+}
+
+#[derive(Serialize)]
+struct PlannedTask {
+    name: String,
+    tags: Vec<String>,
+    targets: Vec<String>,
+    requires: Vec<String>,
+    important: bool,
+    on_fail: String,
+    reason: InclusionReason,
+    /// Whether the task has a `when` guard, which decides at run time
+    conditional: bool,
+    /// Whether the task only runs when another task notifies it
+    on_notify: bool,
+}
+
+#[derive(Serialize)]
+struct PlannedSystem {
+    name: String,
+    tasks: Vec<PlannedTask>,
+    /// Tasks planned on other systems that do not target this one
+    not_targeted: Vec<String>,
+}
+
+fn sorted(items: &HashSet<String>) -> Vec<String> {
+    let mut items: Vec<String> = items.iter().cloned().collect();
+    items.sort();
+    items
+}
+
 fn convert_tasks(tasks: &Tasks) -> Vec<Task> {
     let mut result: Vec<Task> = tasks
         .values()
@@ -91,6 +152,29 @@ fn convert_tasks(tasks: &Tasks) -> Vec<Task> {
     result
 }
 
+/// Lists the tasks in the order they run on a system that all of them target.
+fn convert_tasks_in_order(tasks: &Tasks) -> Vec<OrderedTask> {
+    let mut ordered: Vec<&MemoryTask> = tasks.values().collect();
+    sort_by_execution_order(&mut ordered);
+
+    ordered
+        .iter()
+        .enumerate()
+        .map(|(index, task)| OrderedTask {
+            position: index + 1,
+            name: task.name.clone(),
+            tags: sorted(&task.tags),
+            requires: sorted(&task.requires),
+            on_notify: task.on_notify,
+            required_but_later: ordered[index + 1..]
+                .iter()
+                .filter(|later| later.tags.iter().any(|tag| task.requires.contains(tag)))
+                .map(|later| later.name.clone())
+                .collect(),
+        })
+        .collect()
+}
+
 fn convert_groups(groups: &TargetGroups) -> Vec<Group> {
     let mut result: Vec<Group> = groups
         .values()
@@ -163,57 +247,168 @@ fn print_table<T: Tabled>(items: Vec<T>) {
     println!("{}", table);
 }
 
-pub(crate) fn list_system_tasks(tasks: &[MemoryTask]) {
-    let display_tasks: Vec<Task> = tasks
-        .iter()
-        .map(|task| Task {
-            name: task.name.clone(),
-            tags: task.tags.iter().cloned().collect(),
-            targets: task.targets.iter().cloned().collect(),
-            requires: task.requires.iter().cloned().collect(),
-            important: task.important,
-            on_fail: task.on_fail.to_string(),
-        })
-        .collect();
+/// Prints the rows of the table as CSV, so list fields are joined the same way.
+fn print_csv<T: Tabled>(items: Vec<T>) -> Result<(), ListError> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+
+    writer.write_record(T::headers().iter().map(|header| header.as_ref()))?;
+    for item in &items {
+        writer.write_record(item.fields().iter().map(|field| field.as_ref()))?;
+    }
 
-    print_table(display_tasks);
+    writer.flush().map_err(csv::Error::from)?;
+
+    Ok(())
 }
 
-pub fn list(engine: &Engine, item_type: ListItemType, json: bool) -> Result<(), ListError> {
-    let state = engine.state();
-    let tasks = state.all_tasks()?;
-    let groups = state.all_groups()?;
-    let systems = state.all_systems()?;
+fn print_yaml<T: Serialize>(value: &T) -> Result<(), ListError> {
+    print!("{}", serde_yaml::to_string(value)?);
+    Ok(())
+}
 
-    match item_type {
-        ListItemType::Tasks => {
-            let tasks = convert_tasks(&tasks);
+fn print_items<T: Serialize + Tabled>(items: Vec<T>, format: ListFormat) -> Result<(), ListError> {
+    match format {
+        ListFormat::Table => print_table(items),
+        ListFormat::Json => print_json(&items)?,
+        ListFormat::Csv => print_csv(items)?,
+        ListFormat::Yaml => print_yaml(&items)?,
+    }
 
-            if json {
-                print_json(&tasks)?;
-            } else {
-                print_table(tasks);
-            }
+    Ok(())
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct PlannedTaskRow {
+    #[tabled(rename = "#")]
+    position: usize,
+    name: String,
+    reason: InclusionReason,
+    #[tabled(display = "format_list")]
+    tags: Vec<String>,
+    #[tabled(display = "format_list")]
+    targets: Vec<String>,
+    #[tabled(display = "format_list")]
+    requires: Vec<String>,
+    #[tabled(rename = "WHEN")]
+    condition: String,
+    important: bool,
+    on_fail: String,
+}
+
+/// Describes what decides at run time whether the task runs.
+fn format_condition(task: &MemoryTask) -> String {
+    match (task.when.is_some(), task.on_notify) {
+        (true, true) => "yes, notified".to_string(),
+        (true, false) => "yes".to_string(),
+        (false, true) => "notified".to_string(),
+        (false, false) => String::new(),
+    }
+}
+
+/// Names of the tasks planned on some system but not on `tasks`' system,
+/// because they target other systems or groups, in plan order.
+fn untargeted_tasks(
+    system_tasks: &IndexMap<TargetSystem, Vec<MemoryTask>>,
+    tasks: &[MemoryTask],
+) -> Vec<String> {
+    let planned: HashSet<&String> = tasks.iter().map(|task| &task.name).collect();
+    let mut untargeted = Vec::new();
+
+    for task in system_tasks.values().flatten() {
+        if !planned.contains(&task.name) && !untargeted.contains(&task.name) {
+            untargeted.push(task.name.clone());
         }
-        ListItemType::Groups => {
-            let groups = convert_groups(&groups);
+    }
 
-            if json {
-                print_json(&groups)?;
-            } else {
-                print_table(groups);
-            }
+    untargeted
+}
+
+/// Prints the numbered execution order of each system, why each task is
+/// planned and which selected tasks are left out because of their targets.
+pub(crate) fn list_plan(
+    system_tasks: &IndexMap<TargetSystem, Vec<MemoryTask>>,
+    selection: &Selection,
+) {
+    for (system, tasks) in system_tasks {
+        println!("\nSYSTEM : {}\n", system.name);
+
+        if tasks.is_empty() {
+            println!("No tasks planned");
+        } else {
+            print_table(
+                tasks
+                    .iter()
+                    .enumerate()
+                    .map(|(index, task)| PlannedTaskRow {
+                        position: index + 1,
+                        name: task.name.clone(),
+                        reason: inclusion_reason(task, selection),
+                        tags: sorted(&task.tags),
+                        targets: sorted(&task.targets),
+                        requires: sorted(&task.requires),
+                        condition: format_condition(task),
+                        important: task.important,
+                        on_fail: task.on_fail.to_string(),
+                    })
+                    .collect(),
+            );
         }
-        ListItemType::Systems => {
-            let systems = convert_systems(&systems, &groups);
 
-            if json {
-                print_json(&systems)?;
-            } else {
-                print_table(systems);
-            }
+        let untargeted = untargeted_tasks(system_tasks, tasks);
+
+        if !untargeted.is_empty() {
+            println!("\nNot targeted: {}", untargeted.join(", "));
         }
     }
+}
 
-    Ok(())
+pub(crate) fn list_plan_json(
+    system_tasks: &IndexMap<TargetSystem, Vec<MemoryTask>>,
+    selection: &Selection,
+) -> Result<(), ListError> {
+    let plan = Plan {
+        systems: system_tasks
+            .iter()
+            .map(|(system, tasks)| PlannedSystem {
+                name: system.name.clone(),
+                tasks: tasks
+                    .iter()
+                    .map(|task| PlannedTask {
+                        name: task.name.clone(),
+                        tags: sorted(&task.tags),
+                        targets: sorted(&task.targets),
+                        requires: sorted(&task.requires),
+                        important: task.important,
+                        on_fail: task.on_fail.to_string(),
+                        reason: inclusion_reason(task, selection),
+                        conditional: task.when.is_some(),
+                        on_notify: task.on_notify,
+                    })
+                    .collect(),
+                not_targeted: untargeted_tasks(system_tasks, tasks),
+            })
+            .collect(),
+    };
+
+    print_json(&plan)
+}
+
+pub fn list(
+    engine: &Engine,
+    item_type: ListItemType,
+    format: ListFormat,
+    order: bool,
+) -> Result<(), ListError> {
+    let state = engine.state();
+    let tasks = state.all_tasks()?;
+    let groups = flatten_groups(&state.all_groups()?);
+    let systems = state.all_systems()?;
+
+    match item_type {
+        ListItemType::Tasks if order => print_items(convert_tasks_in_order(&tasks), format),
+        ListItemType::Tasks => print_items(convert_tasks(&tasks), format),
+        ListItemType::Groups => print_items(convert_groups(&groups), format),
+        ListItemType::Systems => print_items(convert_systems(&systems, &groups), format),
+    }
 }
@@ -1,23 +1,352 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
 use colored::Colorize;
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use serde::Serialize;
+
+use crate::{
+    progress::{SystemLogger, SystemLoggerCreationError},
+    redact::redact,
+};
+
+/// What progress lines are prefixed with. Without it, lines are only
+/// prefixed with the system name when systems run in parallel.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum LinePrefix {
+    /// The name of the system
+    System,
+    /// The names of the system and the task
+    Task,
+}
+
+/// How log output is formatted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Colored output with progress spinners
+    #[default]
+    Pretty,
+    /// One JSON object per line, without progress spinners
+    Json,
+}
+
+/// A log line in the JSON log format.
+#[derive(Serialize)]
+pub struct LogRecord<'a> {
+    pub level: LogLevel,
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<&'a str>,
+    pub message: &'a str,
+}
+
+impl<'a> LogRecord<'a> {
+    pub fn new(level: LogLevel, message: &'a str) -> Self {
+        Self {
+            level,
+            timestamp: format!("{:.3}", jiff::Timestamp::now()),
+            system: None,
+            task: None,
+            message,
+        }
+    }
+
+    pub fn system(mut self, system: &'a str) -> Self {
+        self.system = Some(system);
+        self
+    }
+
+    pub fn task(mut self, task: &'a str) -> Self {
+        self.task = Some(task);
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to open log file {path:?}")]
+pub struct LogFileError {
+    path: PathBuf,
+    #[source]
+    source: std::io::Error,
+}
+
+/// Removes ANSI escape sequences such as colors.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut characters = text.chars();
+
+    while let Some(character) = characters.next() {
+        if character == '\x1b' {
+            if characters.next() == Some('[') {
+                characters
+                    .by_ref()
+                    .find(|character| ('@'..='~').contains(character));
+            }
+        } else {
+            stripped.push(character);
+        }
+    }
 
-use crate::progress::{SystemLogger, SystemLoggerCreationError};
+    stripped
+}
 
 #[derive(Clone)]
-pub struct Logger;
+pub struct Logger {
+    /// Progress output shared by systems running in parallel
+    shared_progress: Option<MultiProgress>,
+    /// Messages below this level are dropped
+    level: LogLevel,
+    /// Keeps stdout free for machine readable output such as `--json`
+    diagnostics_to_stderr: bool,
+    format: LogFormat,
+    /// Prints progress as lines instead of drawing spinners and bars, for
+    /// output that is not a terminal such as CI logs
+    plain_progress: bool,
+    line_prefix: Option<LinePrefix>,
+    /// Receives a copy of all printed lines without colors
+    log_file: Option<Arc<Mutex<BufWriter<File>>>>,
+}
 
 impl Logger {
     pub fn new() -> Self {
-        Self
+        Self {
+            shared_progress: None,
+            level: LogLevel::Info,
+            diagnostics_to_stderr: false,
+            format: LogFormat::Pretty,
+            plain_progress: false,
+            line_prefix: None,
+            log_file: None,
+        }
+    }
+
+    /// Sets the lowest level of messages that are shown. `Debug` enables
+    /// details about connections and execution that are hidden by default.
+    pub fn with_level(mut self, level: LogLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn is_enabled(&self, level: LogLevel) -> bool {
+        level >= self.level
+    }
+
+    /// Writes informational messages and Lua logs to stderr instead of stdout.
+    pub fn with_diagnostics_to_stderr(mut self, diagnostics_to_stderr: bool) -> Self {
+        self.diagnostics_to_stderr = diagnostics_to_stderr;
+        self
+    }
+
+    pub fn with_plain_progress(mut self, plain_progress: bool) -> Self {
+        self.plain_progress = plain_progress;
+        self
+    }
+
+    pub(crate) fn has_plain_progress(&self) -> bool {
+        self.plain_progress
+    }
+
+    pub fn with_line_prefix(mut self, line_prefix: Option<LinePrefix>) -> Self {
+        self.line_prefix = line_prefix;
+        self
+    }
+
+    pub(crate) fn line_prefix(&self) -> Option<LinePrefix> {
+        self.line_prefix
+    }
+
+    /// Prints a progress line that would otherwise remain above the spinners
+    /// once the progress it belongs to finished.
+    pub(crate) fn print_progress_line(&self, line: &str) {
+        if self.is_json() || !self.is_enabled(LogLevel::Info) {
+            return;
+        }
+
+        println!("{}", line);
+        self.write_to_file(line);
+    }
+
+    /// Switches to the JSON log format. Colors are disabled globally, so
+    /// that no escape codes end up in messages.
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        if format == LogFormat::Json {
+            colored::control::set_override(false);
+        }
+
+        self.format = format;
+        self
+    }
+
+    /// Copies all printed lines to the file at `path`, replacing its content.
+    pub fn with_log_file(mut self, path: &Path) -> Result<Self, LogFileError> {
+        let file = File::create(path).map_err(|source| LogFileError {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        self.log_file = Some(Arc::new(Mutex::new(BufWriter::new(file))));
+        Ok(self)
+    }
+
+    /// Appends `text` without colors to the log file, if there is one.
+    /// Failing to write to the log file does not interrupt the run.
+    pub(crate) fn write_to_file(&self, text: &str) {
+        if let Some(Ok(mut log_file)) = self.log_file.as_ref().map(|file| file.lock()) {
+            _ = writeln!(log_file, "{}", strip_ansi(&redact(text)));
+        }
+    }
+
+    /// Flushes the log file, so that it is complete even if arc crashes.
+    pub(crate) fn flush_file(&self) {
+        if let Some(Ok(mut log_file)) = self.log_file.as_ref().map(|file| file.lock()) {
+            _ = log_file.flush();
+        }
+    }
+
+    pub(crate) fn has_shared_progress(&self) -> bool {
+        self.shared_progress.is_some()
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == LogFormat::Json
+    }
+
+    /// Creates a logger whose systems share one progress display, so that
+    /// output of systems running in parallel does not interfere.
+    pub fn for_parallel_systems(&self) -> Self {
+        Self {
+            shared_progress: Some(self.progress_display()),
+            level: self.level,
+            diagnostics_to_stderr: self.diagnostics_to_stderr,
+            format: self.format,
+            plain_progress: self.plain_progress,
+            line_prefix: self.line_prefix,
+            log_file: self.log_file.clone(),
+        }
+    }
+
+    /// Returns the shared progress display or a new one. Progress is not
+    /// drawn in the JSON log format, as plain lines or if informational
+    /// messages are hidden.
+    pub(crate) fn progress_display(&self) -> MultiProgress {
+        match &self.shared_progress {
+            Some(multi_progress) => multi_progress.clone(),
+            None if self.is_json() || self.plain_progress || !self.is_enabled(LogLevel::Info) => {
+                MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+            }
+            None => MultiProgress::new(),
+        }
+    }
+
+    /// Prints a line in the JSON log format. Errors go to stderr.
+    pub fn record(&self, record: &LogRecord) {
+        if !self.is_enabled(record.level) {
+            return;
+        }
+
+        let line = serde_json::to_string(record).expect("log records are serializable");
+        let line = redact(&line);
+
+        match record.level {
+            LogLevel::Error => {
+                self.suspend_progress(|| eprintln!("{}", line));
+                self.write_to_file(&line);
+                self.flush_file();
+            }
+            _ => self.print_diagnostic(&line),
+        }
+    }
+
+    fn suspend_progress(&self, print: impl FnOnce()) {
+        match &self.shared_progress {
+            Some(multi_progress) => multi_progress.suspend(print),
+            None => print(),
+        }
+    }
+
+    fn print_diagnostic(&self, line: &str) {
+        let line = redact(line);
+
+        self.suspend_progress(|| match self.diagnostics_to_stderr {
+            true => eprintln!("{}", line),
+            false => println!("{}", line),
+        });
+        self.write_to_file(&line);
+    }
+
+    /// Prints preformatted output, such as a diff, where diagnostics go.
+    pub fn print(&self, text: &str) {
+        if !self.is_enabled(LogLevel::Info) {
+            return;
+        }
+
+        match self.format {
+            LogFormat::Pretty => self.print_diagnostic(text),
+            LogFormat::Json => self.record(&LogRecord::new(LogLevel::Info, text)),
+        }
     }
 
     pub fn info(&self, message: &str) {
-        println!("ARC | {}{} : {}", "INFO".blue(), "".clear(), message);
+        if !self.is_enabled(LogLevel::Info) {
+            return;
+        }
+
+        if self.is_json() {
+            return self.record(&LogRecord::new(LogLevel::Info, message));
+        }
+
+        self.print_diagnostic(&format!(
+            "ARC | {}{} : {}",
+            "INFO".blue(),
+            "".clear(),
+            message
+        ));
     }
 
     pub fn warn(&self, message: &str) {
-        println!("ARC | {}{} : {}", "WARN".yellow(), "".clear(), message);
+        if !self.is_enabled(LogLevel::Warn) {
+            return;
+        }
+
+        if self.is_json() {
+            return self.record(&LogRecord::new(LogLevel::Warn, message));
+        }
+
+        self.print_diagnostic(&format!(
+            "ARC | {}{} : {}",
+            "WARN".yellow(),
+            "".clear(),
+            message
+        ));
+    }
+
+    pub fn error(&self, message: &str) {
+        if self.is_json() {
+            return self.record(&LogRecord::new(LogLevel::Error, message));
+        }
+
+        let line = format!("ARC | {}{} : {}", "ERRO".red(), "".clear(), redact(message));
+
+        self.suspend_progress(|| eprintln!("{}", line));
+        self.write_to_file(&line);
+        self.flush_file();
     }
+
     pub fn lua_log(&self, level: LogLevel, message: &str) {
+        if !self.is_enabled(level) {
+            return;
+        }
+
+        if self.is_json() {
+            return self.record(&LogRecord::new(level, message));
+        }
+
         let level_colored = match level {
             LogLevel::Debug => "DEBG".green(),
             LogLevel::Info => "INFO".blue(),
@@ -25,17 +354,17 @@ impl Logger {
             LogLevel::Error => "ERRO".red(),
         };
 
-        println!(
+        self.print_diagnostic(&format!(
             " {}  {}{} {}",
             level_colored,
             format!("{:.3}", jiff::Timestamp::now()).bright_black(),
             ":".bright_black(),
             message.bright_black(),
-        );
+        ));
     }
 
     pub fn system(&self, name: &str) -> Result<SystemLogger, SystemLoggerCreationError> {
-        SystemLogger::new(name)
+        SystemLogger::new(name, self)
     }
 }
 
@@ -45,6 +374,9 @@ impl Default for Logger {
     }
 }
 
+/// Severity of a message, in increasing order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
 pub enum LogLevel {
     Debug,
     Info,
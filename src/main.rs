@@ -1,20 +1,25 @@
+use std::io::IsTerminal;
+
 use clap::Parser;
 use cli::Cli;
-use engine::Engine;
+use engine::{Engine, EngineOptions, RunOptions};
 
 use crate::{
-    engine::selection::{GroupSelection, SystemSelection, TagSelection},
-    logger::Logger,
+    engine::selection::{GroupSelection, Selection, SystemSelection, TagSelection, TaskSelection},
+    logger::{LogLevel, Logger},
 };
 
 mod cli;
 mod engine;
 mod error;
 mod init;
+mod interrupt;
 mod list;
 mod logger;
 mod memory;
 mod progress;
+mod redact;
+mod vault;
 
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to resolve working directory")]
@@ -27,24 +32,65 @@ struct HomeDirectoryError;
 fn main() -> Result<(), error::ErrorReport> {
     let cli_args = Cli::parse();
 
-    let logger = Logger::new();
+    let json_output = match &cli_args.command {
+        cli::Command::Run { json, .. } => *json,
+        cli::Command::List { json, format, .. } => {
+            *json || !matches!(format, cli::ListFormat::Table)
+        }
+        cli::Command::Init { .. }
+        | cli::Command::Secrets { .. }
+        | cli::Command::Validate { .. } => false,
+    };
+    let log_level = if cli_args.verbose {
+        LogLevel::Debug
+    } else if cli_args.quiet {
+        LogLevel::Error
+    } else {
+        cli_args.log_level
+    };
+    let mut logger = Logger::new()
+        .with_level(log_level)
+        .with_format(cli_args.log_format)
+        .with_diagnostics_to_stderr(json_output)
+        .with_plain_progress(cli_args.no_progress || !std::io::stdout().is_terminal())
+        .with_line_prefix(cli_args.prefix);
+    if let Some(log_file) = &cli_args.log_file {
+        logger = logger
+            .with_log_file(log_file)
+            .map_err(error::ErrorReport::boxed_from)?;
+    }
     let root_path = std::env::current_dir()
         .map_err(|error| error::ErrorReport::boxed_from(WorkingDirectoryError(error)))?;
     let home_path =
         std::env::home_dir().ok_or_else(|| error::ErrorReport::boxed_from(HomeDirectoryError))?;
 
     match cli_args.command {
-        cli::Command::Init { project_root } => {
-            init::init_project(project_root).map_err(error::ErrorReport::boxed_from)?
-        }
+        cli::Command::Init {
+            project_root,
+            template,
+        } => init::init_project(project_root, template).map_err(error::ErrorReport::boxed_from)?,
         cli::Command::Run {
             tag,
+            skip_tag,
+            task,
             group,
             system,
+            limit,
+            diff,
             list,
+            check,
+            json,
+            start_at,
+            retry_budget,
+            parallel,
+            report,
+            var,
             no_reqs,
+            yes,
             all_tags,
             all_systems,
+            strict,
+            file,
         } => {
             let tags = if all_tags {
                 TagSelection::All
@@ -52,6 +98,12 @@ fn main() -> Result<(), error::ErrorReport> {
                 TagSelection::Set(tag.into_iter().collect())
             };
 
+            let tasks = if task.is_empty() {
+                TaskSelection::None
+            } else {
+                TaskSelection::Set(task.into_iter().collect())
+            };
+
             let groups = if group.is_empty() {
                 GroupSelection::None
             } else {
@@ -66,52 +118,208 @@ fn main() -> Result<(), error::ErrorReport> {
                 SystemSelection::Set(system.into_iter().collect())
             };
 
+            let selection = Selection {
+                tags,
+                skip_tags: skip_tag.into_iter().collect(),
+                tasks,
+                groups,
+                systems,
+                limit: if limit.is_empty() {
+                    SystemSelection::All
+                } else {
+                    SystemSelection::Set(limit.into_iter().collect())
+                },
+                no_reqs,
+                start_at,
+                strict,
+            };
+
             if let Err(error) = dotenvy::dotenv_override() {
                 logger.warn(&format!("Failed to load .env: {}", error));
             };
 
-            let engine = Engine::new(logger, root_path, home_path)
-                .map_err(error::ErrorReport::boxed_from)?;
+            let engine = Engine::new(
+                logger.clone(),
+                root_path,
+                home_path,
+                EngineOptions {
+                    show_diff: diff,
+                    vars: var.into_iter().collect(),
+                    dry_run: list,
+                    entrypoint: file,
+                },
+            )
+            .map_err(error::ErrorReport::boxed_from)?;
 
-            if list {
+            if check {
+                check_plan(&engine, &logger, &selection)?;
+            } else if list {
                 engine
                     .execute_entrypoint()
                     .map_err(error::ErrorReport::boxed_from)?;
 
                 let system_tasks = engine
-                    .validate_and_filter_by_selection(&tags, &groups, &systems, no_reqs)
+                    .validate_and_filter_by_selection(&selection)
                     .map_err(error::ErrorReport::boxed_from)?;
 
-                for (system, tasks) in &system_tasks {
-                    if !tasks.is_empty() {
-                        println!("\nSYSTEM : {}\n", system.name);
-
-                        list::list_system_tasks(tasks);
-                    }
+                if json {
+                    list::list_plan_json(&system_tasks, &selection)
+                        .map_err(error::ErrorReport::boxed_from)?;
+                } else {
+                    list::list_plan(&system_tasks, &selection);
                 }
             } else {
-                match engine.execute(tags, groups, systems, no_reqs) {
-                    Ok(()) => {}
+                let options = RunOptions {
+                    retry_budget,
+                    parallel: parallel.map(|systems| systems as usize),
+                    report,
+                    assume_yes: yes,
+                };
+
+                interrupt::install().map_err(error::ErrorReport::boxed_from)?;
+
+                let result = engine.execute(selection, options);
+
+                if interrupt::is_interrupted() {
+                    std::process::exit(interrupt::INTERRUPTED_EXIT_CODE);
+                }
+
+                match result {
+                    // Failed tasks have already been reported, including
+                    // those that let the run continue
+                    Ok(summary) if summary.totals.failed > 0 => std::process::exit(1),
+                    Ok(_) => {}
                     Err(engine::EngineExecutionError::Aborted(_)) => std::process::exit(1),
+                    Err(engine::EngineExecutionError::ParallelRun(error)) if error.aborted => {
+                        std::process::exit(1)
+                    }
                     Err(error) => return Err(error::ErrorReport::boxed_from(error)),
                 }
             }
         }
-        cli::Command::List { item_type, json } => {
+        cli::Command::Validate {
+            tag,
+            skip_tag,
+            task,
+            group,
+            system,
+            strict,
+            file,
+        } => {
+            let tags = if tag.is_empty() && task.is_empty() {
+                TagSelection::All
+            } else {
+                TagSelection::Set(tag.into_iter().collect())
+            };
+
+            let tasks = if task.is_empty() {
+                TaskSelection::None
+            } else {
+                TaskSelection::Set(task.into_iter().collect())
+            };
+
+            let groups = if group.is_empty() {
+                GroupSelection::None
+            } else {
+                GroupSelection::Set(group.into_iter().collect())
+            };
+
+            let systems = match (system.is_empty(), &groups) {
+                (true, GroupSelection::None) => SystemSelection::All,
+                (true, GroupSelection::Set(_)) => SystemSelection::None,
+                (false, _) => SystemSelection::Set(system.into_iter().collect()),
+            };
+
+            let selection = Selection {
+                tags,
+                skip_tags: skip_tag.into_iter().collect(),
+                tasks,
+                groups,
+                systems,
+                limit: SystemSelection::All,
+                no_reqs: false,
+                start_at: None,
+                strict,
+            };
+
+            if let Err(error) = dotenvy::dotenv_override() {
+                logger.warn(&format!("Failed to load .env: {}", error));
+            };
+
+            let engine = Engine::new(
+                logger.clone(),
+                root_path,
+                home_path,
+                EngineOptions {
+                    entrypoint: file,
+                    ..EngineOptions::default()
+                },
+            )
+            .map_err(error::ErrorReport::boxed_from)?;
+
+            check_plan(&engine, &logger, &selection)?;
+        }
+        cli::Command::Secrets { command } => {
+            if let Err(error) = dotenvy::dotenv_override() {
+                logger.warn(&format!("Failed to load .env: {}", error));
+            };
+
+            let vault_path = root_path.join(vault::VAULT_FILE);
+
+            match command {
+                cli::SecretsCommand::Set { name } => vault::set_secret(&vault_path, name),
+                cli::SecretsCommand::Remove { name } => vault::remove_secret(&vault_path, &name),
+                cli::SecretsCommand::List => vault::list_secrets(&vault_path),
+            }
+            .map_err(error::ErrorReport::boxed_from)?;
+        }
+        cli::Command::List {
+            item_type,
+            json,
+            format,
+            order,
+        } => {
             if let Err(error) = dotenvy::dotenv_override() {
                 logger.warn(&format!("Failed to load .env: {}", error));
             };
 
-            let engine = Engine::new(logger, root_path, home_path)
+            let engine = Engine::new(logger, root_path, home_path, EngineOptions::default())
                 .map_err(error::ErrorReport::boxed_from)?;
 
             engine
                 .execute_entrypoint()
                 .map_err(error::ErrorReport::boxed_from)?;
 
-            list::list(&engine, item_type, json).map_err(error::ErrorReport::boxed_from)?;
+            let format = if json { cli::ListFormat::Json } else { format };
+
+            list::list(&engine, item_type, format, order)
+                .map_err(error::ErrorReport::boxed_from)?;
         }
     }
 
     Ok(())
 }
+
+/// Runs the entrypoint and validates the definitions and `selection` without
+/// connecting to any system.
+fn check_plan(
+    engine: &Engine,
+    logger: &Logger,
+    selection: &Selection,
+) -> Result<(), error::ErrorReport> {
+    engine
+        .execute_entrypoint()
+        .map_err(error::ErrorReport::boxed_from)?;
+
+    let system_tasks = engine
+        .validate_and_filter_by_selection(selection)
+        .map_err(error::ErrorReport::boxed_from)?;
+
+    logger.info(&format!(
+        "No problems found, {} tasks planned on {} systems",
+        system_tasks.values().map(Vec::len).sum::<usize>(),
+        system_tasks.len()
+    ));
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use std::sync::{Arc, Mutex};
 
+pub mod config;
 pub mod target_groups;
 pub mod target_systems;
 pub mod tasks;
@@ -0,0 +1,36 @@
+use super::target_systems::HostKeyCheck;
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub notify_webhook: Option<String>,
+    pub retry_budget: Option<u32>,
+    pub host_key_check: Option<HostKeyCheck>,
+    /// Directory with task files loaded after the entrypoint
+    pub tasks_directory: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigMemory {
+    memory: Config,
+}
+
+impl ConfigMemory {
+    pub fn get(&self) -> Config {
+        self.memory.clone()
+    }
+
+    pub fn update(&mut self, config: Config) {
+        if config.notify_webhook.is_some() {
+            self.memory.notify_webhook = config.notify_webhook;
+        }
+        if config.retry_budget.is_some() {
+            self.memory.retry_budget = config.retry_budget;
+        }
+        if config.host_key_check.is_some() {
+            self.memory.host_key_check = config.host_key_check;
+        }
+        if config.tasks_directory.is_some() {
+            self.memory.tasks_directory = config.tasks_directory;
+        }
+    }
+}
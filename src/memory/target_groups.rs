@@ -1,9 +1,14 @@
 use std::collections::HashMap;
 
+use super::target_systems::Vars;
+
 #[derive(Debug, Clone)]
 pub struct TargetGroup {
     pub name: String,
     pub members: Vec<String>,
+    pub vars: Vars,
+    /// Whether running against members of the group must be confirmed
+    pub confirm: bool,
 }
 
 pub type TargetGroups = HashMap<String, TargetGroup>;
@@ -1,15 +1,28 @@
 use std::{
     collections::HashMap,
+    fmt,
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
 };
 
+use serde::{Deserialize, Serialize};
+use strum::EnumString;
+
+/// Variables attached to systems and groups, see [`TargetSystem::vars`].
+pub type Vars = serde_json::Map<String, serde_json::Value>;
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct TargetSystem {
     pub name: String,
     pub kind: TargetSystemKind,
+    /// The variables defined for the system. Once the execution is planned,
+    /// they include the variables inherited from its groups.
+    pub vars: Vars,
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum TargetSystemKind {
     Remote(RemoteTargetSystem),
     Local,
@@ -20,9 +33,105 @@ pub struct RemoteTargetSystem {
     pub address: IpAddr,
     pub port: u16,
     pub user: String,
+    pub password: Option<SshPassword>,
+    pub private_key_path: Option<PathBuf>,
+    pub passphrase: Option<SshPassword>,
+    pub host_key_check: Option<HostKeyCheck>,
+    pub timeouts: SshTimeouts,
+    pub algorithms: SshAlgorithms,
+    /// Bastion the connection is tunneled through. It can have a jump host
+    /// itself.
+    pub jump_host: Option<Box<RemoteTargetSystem>>,
+}
+
+pub static DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize)]
+pub struct SshTimeouts {
+    /// Limit for establishing the TCP connection and the SSH handshake.
+    pub connect: Duration,
+    /// Limit for single blocking SFTP and exec operations.
+    pub operation: Option<Duration>,
+    /// Interval of SSH keepalive messages while waiting for command output.
+    pub keepalive_interval: Option<Duration>,
+}
+
+impl Default for SshTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: DEFAULT_CONNECT_TIMEOUT,
+            operation: None,
+            keepalive_interval: None,
+        }
+    }
+}
+
+/// How the host key of a remote system is verified against `known_hosts`.
+#[derive(Debug, Clone, Copy, Default, Hash, Eq, PartialEq, Serialize, EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum HostKeyCheck {
+    /// Refuse hosts that are missing from `known_hosts`.
+    Strict,
+    /// Add hosts that are missing from `known_hosts`.
+    #[default]
+    AcceptNew,
+    /// Skip host key verification.
+    Off,
+}
+
+/// An SSH password or key passphrase. Its `Debug` output is redacted so it never ends up in
+/// logs or error reports.
+#[derive(Clone, Hash, Eq, PartialEq)]
+pub struct SshPassword(String);
+
+impl SshPassword {
+    pub fn new(password: String) -> Self {
+        Self(password)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SshPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SshPassword(<redacted>)")
+    }
+}
+
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SshAlgorithms {
+    pub kex: Vec<String>,
+    pub host_key: Vec<String>,
+    pub ciphers: Vec<String>,
+    pub macs: Vec<String>,
+}
+
+impl SshAlgorithms {
+    pub fn is_empty(&self) -> bool {
+        self.kex.is_empty()
+            && self.host_key.is_empty()
+            && self.ciphers.is_empty()
+            && self.macs.is_empty()
+    }
 }
 
 impl RemoteTargetSystem {
+    /// Uses `host_key_check` for this system and its jump hosts where they
+    /// don't configure their own.
+    pub fn apply_default_host_key_check(&mut self, host_key_check: Option<HostKeyCheck>) {
+        if self.host_key_check.is_none() {
+            self.host_key_check = host_key_check;
+        }
+
+        if let Some(jump_host) = &mut self.jump_host {
+            jump_host.apply_default_host_key_check(host_key_check);
+        }
+    }
+
     pub fn socket_address(&self) -> SocketAddr {
         SocketAddr::new(self.address, self.port)
     }
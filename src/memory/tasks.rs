@@ -1,6 +1,8 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
 
 use indexmap::IndexMap;
+use mlua::IntoLua;
+use serde::Serialize;
 use strum::EnumString;
 
 #[derive(Debug, Clone, Copy, PartialEq, Default, EnumString, strum::Display)]
@@ -12,8 +14,96 @@ pub enum OnFailBehavior {
     Abort,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, EnumString, strum::Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString, strum::Display)]
 #[strum(serialize_all = "snake_case")]
+pub enum ParamType {
+    #[default]
+    String,
+    Number,
+    Integer,
+    Boolean,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    String(String),
+    Number(f64),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl ParamType {
+    /// Converts a value given on the command line to this type.
+    pub fn parse(&self, value: &str) -> Option<ParamValue> {
+        match self {
+            ParamType::String => Some(ParamValue::String(value.to_string())),
+            ParamType::Number => value.parse().ok().map(ParamValue::Number),
+            ParamType::Integer => value.parse().ok().map(ParamValue::Integer),
+            ParamType::Boolean => value.parse().ok().map(ParamValue::Boolean),
+        }
+    }
+
+    /// Whether a Lua value, such as a parameter default, is of this type.
+    pub fn accepts(&self, value: &mlua::Value) -> bool {
+        match (self, value) {
+            (ParamType::String, mlua::Value::String(_)) => true,
+            (ParamType::Number, mlua::Value::Number(_) | mlua::Value::Integer(_)) => true,
+            (ParamType::Integer, mlua::Value::Integer(_)) => true,
+            (ParamType::Integer, mlua::Value::Number(number)) => number.fract() == 0.0,
+            (ParamType::Boolean, mlua::Value::Boolean(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl IntoLua for ParamValue {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        match self {
+            ParamValue::String(value) => value.into_lua(lua),
+            ParamValue::Number(value) => value.into_lua(lua),
+            ParamValue::Integer(value) => value.into_lua(lua),
+            ParamValue::Boolean(value) => value.into_lua(lua),
+        }
+    }
+}
+
+/// How many systems run a task per batch of a rollout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Serial {
+    Count(usize),
+    /// Percentage of the systems the task is planned on, from 1 to 100
+    Percentage(usize),
+}
+
+impl Serial {
+    pub fn batch_size(&self, system_count: usize) -> usize {
+        match self {
+            Serial::Count(count) => *count,
+            Serial::Percentage(percentage) => (system_count * percentage).div_ceil(100),
+        }
+        .max(1)
+    }
+}
+
+impl std::fmt::Display for Serial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Serial::Count(count) => write!(f, "{}", count),
+            Serial::Percentage(percentage) => write!(f, "{}%", percentage),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskParam {
+    pub r#type: ParamType,
+    pub required: bool,
+    pub default: Option<mlua::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, strum::Display, Serialize)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum TaskState {
     Success,
     Failed,
@@ -30,9 +120,17 @@ pub struct Task {
     pub targets: HashSet<String>,
     pub requires: HashSet<String>,
     pub important: bool,
+    /// Only runs when notified by another task, after the other tasks
+    pub on_notify: bool,
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    pub serial: Option<Serial>,
+    pub params: IndexMap<String, TaskParam>,
     pub result: Option<mlua::Value>,
     pub state: Option<TaskState>,
     pub error: Option<String>,
+    /// Time spent in the handler, including retries
+    pub duration: Option<Duration>,
 }
 
 pub type Tasks = IndexMap<String, Task>;
@@ -64,6 +162,12 @@ pub enum TasksErrorSetError {
     TaskNotDefined(#[from] TaskNotDefinedError),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to set task's duration")]
+pub enum TasksDurationSetError {
+    TaskNotDefined(#[from] TaskNotDefinedError),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to retrieve tasks configuration")]
 pub enum TaskRetrievalError {
@@ -98,6 +202,7 @@ impl TasksMemory {
             task.result = None;
             task.state = None;
             task.error = None;
+            task.duration = None;
         });
     }
 
@@ -146,6 +251,21 @@ impl TasksMemory {
         Ok(())
     }
 
+    pub fn set_task_duration(
+        &mut self,
+        task_name: &str,
+        duration: Duration,
+    ) -> Result<(), TasksDurationSetError> {
+        match self.memory.get_mut(task_name) {
+            Some(task) => {
+                task.duration = Some(duration);
+            }
+            None => Err(TaskNotDefinedError(task_name.to_string()))?,
+        };
+
+        Ok(())
+    }
+
     pub fn get(&self, task_name: &str) -> Result<Task, TaskRetrievalError> {
         Ok(self
             .memory
@@ -1,11 +1,23 @@
 mod command;
+mod diff;
 mod system;
 mod task;
 mod transfer;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
 
-use crate::logger::{LogLevel, Logger};
+use crate::{
+    engine::delegator::executor::{CommandResult, RecordedCommand},
+    logger::{LogLevel, Logger},
+};
 
 pub use command::{CommandProgress, CommandProgressCreationError};
 pub use system::{SystemLogger, SystemLoggerCreationError, TaskLoggerCreationError};
@@ -19,35 +31,183 @@ static TASK_TICK_STRINGS: &[&str] = &["●   ", " ●  ", "  ● ", "   ●", "
 static TICK_DURATION_MS_TASK: u64 = 120;
 static TICK_DURATION_MS_SYSTEM: u64 = TICK_DURATION_MS_TASK * 2;
 
+/// Formats a duration as its non-zero components, e.g. `2m 3s` or `1d 4h`.
+/// Durations below one second are shown in milliseconds.
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        return format!("{}ms", duration.as_millis());
+    }
+
+    let total_seconds = duration.as_secs_f64().round() as u64;
+    let units = [
+        (total_seconds / 86400, "d"),
+        (total_seconds % 86400 / 3600, "h"),
+        (total_seconds % 3600 / 60, "m"),
+        (total_seconds % 60, "s"),
+    ];
+
+    units
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Prefixes every non-empty line of `msg` with `label`, so that the lines of
+/// one system can be told apart from those of others.
+fn prefix_lines(label: Option<&str>, msg: &str) -> String {
+    match label {
+        Some(label) => msg
+            .split('\n')
+            .map(|line| match line.is_empty() {
+                true => String::new(),
+                false => format!("{} {}", format!("{} |", label).bright_black(), line),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => msg.to_string(),
+    }
+}
+
+/// Workers of a parallel run evaluate the entrypoint again. They replay the
+/// host commands of the first evaluation instead of running them and skip
+/// its other side effects.
+enum EntrypointEvaluation {
+    Inactive,
+    Recording(Vec<RecordedCommand>),
+    Replaying(VecDeque<RecordedCommand>),
+}
+
 #[derive(Clone)]
 pub struct ProgressContext {
     active_task: Rc<RefCell<Option<TaskLogger>>>,
+    deadline: Rc<Cell<Option<Instant>>>,
+    entrypoint: Rc<RefCell<EntrypointEvaluation>>,
     logger: Logger,
+    show_diff: bool,
+    dry_run: bool,
 }
 
 impl ProgressContext {
-    pub fn new(logger: Logger) -> Self {
+    pub fn new(logger: Logger, show_diff: bool, dry_run: bool) -> Self {
         Self {
             active_task: Rc::new(RefCell::new(None)),
+            deadline: Rc::new(Cell::new(None)),
+            entrypoint: Rc::new(RefCell::new(EntrypointEvaluation::Inactive)),
             logger,
+            show_diff,
+            dry_run,
         }
     }
 
-    pub fn activate(&self, task_logger: TaskLogger) {
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    /// Whether changes to file contents are shown, which is always the case
+    /// in a dry run.
+    pub fn shows_diff(&self) -> bool {
+        self.show_diff || self.dry_run
+    }
+
+    /// Whether changes are only previewed instead of being made.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Makes `task_logger` the target of progress output. Commands started
+    /// while the task is active are interrupted once `deadline` has passed.
+    pub fn activate(&self, task_logger: TaskLogger, deadline: Option<Instant>) {
         *self.active_task.borrow_mut() = Some(task_logger);
+        self.deadline.set(deadline);
     }
 
     pub fn deactivate(&self) {
         *self.active_task.borrow_mut() = None;
+        self.deadline.set(None);
     }
 
-    pub fn command(&self, command: &str) -> Result<CommandProgress, CommandProgressCreationError> {
-        match &*self.active_task.borrow() {
-            Some(task_logger) => task_logger.command_progress(command),
-            None => Ok(CommandProgress::noop()),
+    /// Starts evaluating the entrypoint, recording its host commands or, if
+    /// `replay` is given, replaying them.
+    pub fn start_entrypoint(&self, replay: Option<Vec<RecordedCommand>>) {
+        *self.entrypoint.borrow_mut() = match replay {
+            Some(commands) => EntrypointEvaluation::Replaying(commands.into()),
+            None => EntrypointEvaluation::Recording(Vec::new()),
+        };
+    }
+
+    /// Ends the evaluation of the entrypoint and returns the recorded commands.
+    pub fn finish_entrypoint(&self) -> Vec<RecordedCommand> {
+        match self.entrypoint.replace(EntrypointEvaluation::Inactive) {
+            EntrypointEvaluation::Recording(commands) => commands,
+            EntrypointEvaluation::Inactive | EntrypointEvaluation::Replaying(_) => Vec::new(),
         }
     }
 
+    /// Whether the entrypoint is evaluated again, so side effects it already
+    /// had must not happen a second time.
+    pub fn is_replaying_entrypoint(&self) -> bool {
+        matches!(
+            &*self.entrypoint.borrow(),
+            EntrypointEvaluation::Replaying(_)
+        )
+    }
+
+    /// Whether a change to a system must be left out, because the first
+    /// evaluation of the entrypoint already made it or because it is a dry
+    /// run. A dry run logs the `change` instead, e.g. `remove "/tmp/file"`.
+    pub fn skips_change(&self, change: impl FnOnce() -> String) -> bool {
+        if self.is_replaying_entrypoint() {
+            return true;
+        }
+
+        if self.dry_run {
+            self.log(LogLevel::Info, &format!("Would {}", change()));
+        }
+
+        self.dry_run
+    }
+
+    /// Returns the recorded result of `command` if it is the next command of
+    /// a replayed entrypoint.
+    pub fn replayed_command(&self, command: &str) -> Option<CommandResult> {
+        match &mut *self.entrypoint.borrow_mut() {
+            EntrypointEvaluation::Replaying(commands)
+                if commands
+                    .front()
+                    .is_some_and(|recorded| recorded.command == command) =>
+            {
+                commands.pop_front().map(|recorded| recorded.result)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn record_command(&self, command: &str, result: &CommandResult) {
+        if let EntrypointEvaluation::Recording(commands) = &mut *self.entrypoint.borrow_mut() {
+            commands.push(RecordedCommand {
+                command: command.to_string(),
+                result: result.clone(),
+            });
+        }
+    }
+
+    pub fn command(&self, command: &str) -> Result<CommandProgress, CommandProgressCreationError> {
+        let progress = match &*self.active_task.borrow() {
+            Some(task_logger) => task_logger.command_progress(command)?,
+            None => CommandProgress::noop(),
+        };
+
+        Ok(progress.with_deadline(self.deadline.get()))
+    }
+
+    /// Progress of a command that is not shown, e.g. a repeated check, but
+    /// is still interrupted at the deadline of the task.
+    pub fn quiet_command(&self) -> CommandProgress {
+        CommandProgress::noop().with_deadline(self.deadline.get())
+    }
+
     pub fn transfer(
         &self,
         direction: TransferDirection,
@@ -59,9 +219,58 @@ impl ProgressContext {
         }
     }
 
+    pub fn diff(&self, path: &Path, previous: Option<&[u8]>, current: &[u8], sensitive: bool) {
+        if !self.shows_diff() {
+            return;
+        }
+
+        let Some(rendered) = diff::render(path, previous, current, sensitive) else {
+            return;
+        };
+
+        match &*self.active_task.borrow() {
+            Some(task_logger) => task_logger.print(&rendered),
+            None => self.logger.print(&rendered),
+        }
+    }
+
+    /// Logs a debug message that is only shown with `--log-level debug`.
+    pub fn log_verbose(&self, msg: &str) {
+        self.log(LogLevel::Debug, msg);
+    }
+
+    /// Runs `run` and logs `operation` with how long it took at debug level.
+    /// Nothing is timed or formatted unless debug messages are shown.
+    pub fn traced<T, E>(
+        &self,
+        operation: impl FnOnce() -> String,
+        run: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        if !self.logger.is_enabled(LogLevel::Debug) {
+            return run();
+        }
+
+        let started = Instant::now();
+        let result = run();
+        let outcome = match &result {
+            Ok(_) => "took",
+            Err(_) => "failed after",
+        };
+
+        self.log_verbose(&format!(
+            "{} {} {}",
+            operation(),
+            outcome,
+            format_duration(started.elapsed())
+        ));
+
+        result
+    }
+
     pub fn log(&self, level: LogLevel, msg: &str) {
         match &*self.active_task.borrow() {
             Some(task_logger) => task_logger.log(level, msg),
+            None if self.is_replaying_entrypoint() => {}
             None => self.logger.lua_log(level, msg),
         }
     }
@@ -1,7 +1,11 @@
+use std::time::Instant;
+
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
-use super::{TASK_TICK_STRINGS, TICK_DURATION_MS_TASK};
+use crate::{logger::Logger, redact::redact};
+
+use super::{TASK_TICK_STRINGS, TICK_DURATION_MS_TASK, prefix_lines};
 
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to create command progress")]
@@ -23,8 +27,11 @@ fn truncate_line(line: &str, max_width: usize) -> String {
 pub struct CommandProgress {
     bar: ProgressBar,
     header: String,
+    /// Writes the header to the log file once finished
+    logger: Logger,
     command: String,
     active: bool,
+    deadline: Option<Instant>,
 }
 
 impl CommandProgress {
@@ -32,15 +39,32 @@ impl CommandProgress {
         Self {
             bar: ProgressBar::hidden(),
             header: String::new(),
+            logger: Logger::new(),
             command: String::new(),
             active: false,
+            deadline: None,
         }
     }
 
     pub(crate) fn new(
         multi_progress: &MultiProgress,
         cmd: &str,
+        line_label: Option<&str>,
+        logger: Logger,
     ) -> Result<Self, CommandProgressCreationError> {
+        // Commands may contain credentials, e.g. in arguments
+        let cmd = &*redact(cmd);
+        let header = prefix_lines(
+            line_label,
+            &format!(" {}  {}", "CMND".cyan(), cmd.bright_black()),
+        );
+
+        if logger.has_plain_progress() {
+            logger.print_progress_line(&header);
+
+            return Ok(Self::noop());
+        }
+
         let bar = multi_progress.insert(0, ProgressBar::new_spinner());
 
         bar.set_style(
@@ -49,8 +73,6 @@ impl CommandProgress {
                 .template(" {prefix}  {msg}")?,
         );
 
-        let header = format!(" {}  {}", "CMND".cyan(), cmd.bright_black());
-
         bar.set_prefix(format!("{}", "CMND".cyan()));
         bar.set_message(format!("{}", cmd.bright_black()));
         bar.enable_steady_tick(std::time::Duration::from_millis(TICK_DURATION_MS_TASK));
@@ -58,24 +80,46 @@ impl CommandProgress {
         Ok(Self {
             bar,
             header,
+            logger,
             command: cmd.to_string(),
             active: true,
+            deadline: None,
         })
     }
 
+    pub(crate) fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Returns whether the deadline of the running task has passed.
+    pub fn deadline_reached(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Returns the time left until the deadline of the running task.
+    pub fn remaining(&self) -> Option<std::time::Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
     pub fn update_output(&self, output: &str) {
         if !self.active {
             return;
         }
 
-        let lines: Vec<&str> = output.lines().collect();
-        let start = lines.len().saturating_sub(MAX_OUTPUT_LINES);
-        let tail: String = lines[start..]
+        // Only the end of the output is scanned, so repeated updates with a
+        // growing buffer stay cheap
+        let mut lines: Vec<&str> = output.lines().rev().take(MAX_OUTPUT_LINES).collect();
+        lines.reverse();
+
+        let tail: String = lines
             .iter()
             .map(|line| {
                 format!(
                     "       {}",
-                    truncate_line(line, MAX_OUTPUT_LINE_WIDTH).bright_black()
+                    truncate_line(&redact(line), MAX_OUTPUT_LINE_WIDTH).bright_black()
                 )
             })
             .collect::<Vec<_>>()
@@ -94,6 +138,7 @@ impl CommandProgress {
         if self.active {
             self.bar.println(&self.header);
             self.bar.finish_and_clear();
+            self.logger.write_to_file(&self.header);
         }
     }
 }
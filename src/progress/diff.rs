@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use colored::Colorize;
+use similar::TextDiff;
+
+static DIFF_CONTEXT_LINES: usize = 3;
+
+/// Renders the change of a file's content as a colored unified diff.
+///
+/// Returns `None` if the content did not change. Sensitive content and
+/// binary content are summarized instead of being shown line by line.
+pub(super) fn render(
+    path: &Path,
+    previous: Option<&[u8]>,
+    current: &[u8],
+    sensitive: bool,
+) -> Option<String> {
+    if previous == Some(current) {
+        return None;
+    }
+
+    let header = format!(" {}  {}", "DIFF".magenta(), path.display());
+
+    if sensitive {
+        return Some(format!(
+            "{}\n{}",
+            header,
+            "     content changed (sensitive, hidden)".bright_black()
+        ));
+    }
+
+    let previous_text = match previous {
+        Some(previous) => as_text(previous),
+        None => Some(""),
+    };
+
+    let (Some(previous_text), Some(current_text)) = (previous_text, as_text(current)) else {
+        return Some(format!(
+            "{}\n{}",
+            header,
+            format!(
+                "     binary content changed ({} -> {} bytes)",
+                previous.map(<[u8]>::len).unwrap_or(0),
+                current.len()
+            )
+            .bright_black()
+        ));
+    };
+
+    let diff = TextDiff::from_lines(previous_text, current_text);
+    let unified = diff
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .to_string();
+
+    let lines = unified
+        .lines()
+        .map(|line| {
+            let colored = if line.starts_with("@@") {
+                line.cyan()
+            } else if line.starts_with('+') {
+                line.green()
+            } else if line.starts_with('-') {
+                line.red()
+            } else {
+                line.bright_black()
+            };
+
+            format!("     {}", colored)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(format!("{}\n{}", header, lines))
+}
+
+/// Content that is not UTF-8 or contains NUL bytes is treated as binary.
+fn as_text(content: &[u8]) -> Option<&str> {
+    std::str::from_utf8(content)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+}
@@ -6,11 +6,14 @@ use std::sync::{
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
-use crate::memory::tasks::TaskState;
+use crate::{
+    logger::{LinePrefix, LogLevel, LogRecord, Logger},
+    memory::tasks::TaskState,
+};
 
 use super::{
     SYSTEM_TICK_CHARS, TASK_TICK_STRINGS, TICK_DURATION_MS_SYSTEM, TICK_DURATION_MS_TASK,
-    task::TaskLogger,
+    prefix_lines, task::TaskLogger,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -22,6 +25,7 @@ pub(super) struct TaskSummary {
     success: Arc<AtomicUsize>,
     failed: Arc<AtomicUsize>,
     skipped: Arc<AtomicUsize>,
+    timed_out: Arc<AtomicUsize>,
 }
 
 impl TaskSummary {
@@ -33,6 +37,10 @@ impl TaskSummary {
         };
     }
 
+    pub(super) fn increment_timed_out(&self) {
+        self.timed_out.fetch_add(1, Ordering::Relaxed);
+    }
+
     fn success(&self) -> usize {
         self.success.load(Ordering::Relaxed)
     }
@@ -44,6 +52,10 @@ impl TaskSummary {
     fn skipped(&self) -> usize {
         self.skipped.load(Ordering::Relaxed)
     }
+
+    fn timed_out(&self) -> usize {
+        self.timed_out.load(Ordering::Relaxed)
+    }
 }
 
 pub struct SystemLogger {
@@ -51,11 +63,15 @@ pub struct SystemLogger {
     system_bar: ProgressBar,
     system_name: String,
     summary: TaskSummary,
+    /// Whether other systems print to the same progress display
+    shared: bool,
+    logger: Logger,
 }
 
 impl SystemLogger {
-    pub fn new(system_name: &str) -> Result<Self, SystemLoggerCreationError> {
-        let multi_progress = MultiProgress::new();
+    pub fn new(system_name: &str, logger: &Logger) -> Result<Self, SystemLoggerCreationError> {
+        let shared = logger.has_shared_progress();
+        let multi_progress = logger.progress_display();
         let bar = multi_progress.add(ProgressBar::new_spinner());
 
         bar.set_style(
@@ -72,17 +88,43 @@ impl SystemLogger {
             system_bar: bar,
             system_name: system_name.to_string(),
             summary: TaskSummary::default(),
+            shared,
+            logger: logger.clone(),
         };
 
-        system_logger.println(&format!("\nSYSTEM: {}\n", system_name));
+        match logger.is_json() {
+            true => system_logger.record("started"),
+            false => system_logger.println(&format!("\nSYSTEM: {}\n", system_name)),
+        }
 
         Ok(system_logger)
     }
 
+    fn record(&self, message: &str) {
+        self.logger
+            .record(&LogRecord::new(LogLevel::Info, message).system(&self.system_name));
+    }
+
     fn println(&self, msg: &str) {
+        if !self.logger.is_enabled(LogLevel::Info) {
+            return;
+        }
+
+        let msg = match self.labels_lines() {
+            true => prefix_lines(Some(&self.system_name), msg),
+            false => msg.to_string(),
+        };
+
         self.system_bar.suspend(|| {
             println!("{}", msg);
         });
+        self.logger.write_to_file(&msg);
+    }
+
+    /// Whether lines are prefixed, which is always the case when systems run
+    /// in parallel.
+    fn labels_lines(&self) -> bool {
+        self.shared || self.logger.line_prefix().is_some()
     }
 
     pub fn task(&self, task_name: &str) -> Result<TaskLogger, TaskLoggerCreationError> {
@@ -97,15 +139,37 @@ impl SystemLogger {
         bar.set_message(task_name.to_string());
         bar.enable_steady_tick(std::time::Duration::from_millis(TICK_DURATION_MS_TASK));
 
+        let line_label = match self.logger.line_prefix() {
+            Some(LinePrefix::Task) => Some(format!("{} {}", self.system_name, task_name)),
+            Some(LinePrefix::System) => Some(self.system_name.clone()),
+            None => self.shared.then(|| self.system_name.clone()),
+        };
+
         Ok(TaskLogger::new(
             self.multi_progress.clone(),
             bar,
             task_name.to_string(),
             self.summary.clone(),
+            self.system_name.clone(),
+            line_label,
+            self.logger.clone(),
         ))
     }
 
     pub fn finish(self) {
+        if self.logger.is_json() {
+            self.record(&format!(
+                "finished: {} ok, {} failed, {} skipped, {} timed out",
+                self.summary.success(),
+                self.summary.failed(),
+                self.summary.skipped(),
+                self.summary.timed_out()
+            ));
+            self.system_bar.finish_and_clear();
+            self.logger.flush_file();
+            return;
+        }
+
         let ok_part = format!("{} OK", self.summary.success()).green();
 
         let failed_part = if self.summary.failed() > 0 {
@@ -120,12 +184,22 @@ impl SystemLogger {
             format!("{} SKIPPED", self.summary.skipped()).normal()
         };
 
+        let timed_out_part = if self.summary.timed_out() > 0 {
+            format!(
+                " | {}",
+                format!("{} TIMED OUT", self.summary.timed_out()).red()
+            )
+        } else {
+            String::new()
+        };
+
         self.println(&format!(
-            "SYSTEM : {} | {} | {} | {}\n",
-            self.system_name, ok_part, failed_part, skipped_part
+            "SYSTEM : {} | {} | {} | {}{}\n",
+            self.system_name, ok_part, failed_part, skipped_part, timed_out_part
         ));
 
         self.system_bar.finish_and_clear();
+        self.logger.flush_file();
     }
 }
 
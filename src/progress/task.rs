@@ -1,10 +1,17 @@
+use std::time::Instant;
+
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar};
 
-use crate::{logger::LogLevel, memory::tasks::TaskState};
+use crate::{
+    logger::{LogLevel, LogRecord, Logger},
+    memory::tasks::TaskState,
+    redact::redact,
+};
 
 use super::{
     command::{CommandProgress, CommandProgressCreationError},
+    format_duration, prefix_lines,
     system::TaskSummary,
     transfer::{TransferDirection, TransferProgress, TransferProgressCreationError},
 };
@@ -15,6 +22,11 @@ pub struct TaskLogger {
     task_bar: ProgressBar,
     task_name: String,
     summary: TaskSummary,
+    system_name: String,
+    /// Prefix of every line, see [`LinePrefix`](crate::logger::LinePrefix)
+    line_label: Option<String>,
+    logger: Logger,
+    started: Instant,
 }
 
 impl TaskLogger {
@@ -23,32 +35,85 @@ impl TaskLogger {
         task_bar: ProgressBar,
         task_name: String,
         summary: TaskSummary,
+        system_name: String,
+        line_label: Option<String>,
+        logger: Logger,
     ) -> Self {
         Self {
             multi_progress,
             task_bar,
             task_name,
             summary,
+            system_name,
+            line_label,
+            logger,
+            started: Instant::now(),
+        }
+    }
+
+    fn println_at(&self, level: LogLevel, msg: &str) {
+        if self.logger.is_enabled(level) {
+            self.println(msg);
         }
     }
 
+    fn record(&self, level: LogLevel, message: &str) {
+        self.logger.record(
+            &LogRecord::new(level, message)
+                .system(&self.system_name)
+                .task(&self.task_name),
+        );
+    }
+
     fn println(&self, msg: &str) {
+        let msg = prefix_lines(self.line_label.as_deref(), &redact(msg));
+
         self.task_bar.suspend(|| {
             println!("{}", msg);
         });
+        self.logger.write_to_file(&msg);
+    }
+
+    pub(super) fn print(&self, msg: &str) {
+        match self.logger.is_json() {
+            true => self.record(LogLevel::Info, msg),
+            false => self.println_at(LogLevel::Info, msg),
+        }
     }
 
     pub fn start(&self) {
-        self.println(&format!("[{}] {}", "STRT".bright_blue(), self.task_name));
+        if self.logger.is_json() {
+            return self.record(LogLevel::Info, "started");
+        }
+
+        self.println_at(
+            LogLevel::Info,
+            &format!("[{}] {}", "STRT".bright_blue(), self.task_name),
+        );
     }
 
     pub fn skip(self) {
         self.summary.increment(TaskState::Skipped);
-        self.println(&format!("[{}] {}\n", "SKIP".yellow(), self.task_name));
+        match self.logger.is_json() {
+            true => self.record(LogLevel::Info, "skipped"),
+            false => self.println_at(
+                LogLevel::Info,
+                &format!("[{}] {}\n", "SKIP".yellow(), self.task_name),
+            ),
+        }
         self.task_bar.finish_and_clear();
+        self.logger.flush_file();
     }
 
     pub fn log(&self, level: LogLevel, message: &str) {
+        if self.logger.is_json() {
+            return self.record(level, message);
+        }
+
+        if !self.logger.is_enabled(level) {
+            return;
+        }
+
         let level_colored = match level {
             LogLevel::Debug => "DEBG".green(),
             LogLevel::Info => "INFO".blue(),
@@ -70,33 +135,74 @@ impl TaskLogger {
         direction: TransferDirection,
         total: u64,
     ) -> Result<TransferProgress, TransferProgressCreationError> {
-        TransferProgress::new(&self.multi_progress, direction, total)
+        TransferProgress::new(
+            &self.multi_progress,
+            direction,
+            total,
+            self.line_label.as_deref(),
+            self.logger.clone(),
+        )
     }
 
     pub(super) fn command_progress(
         &self,
         cmd: &str,
     ) -> Result<CommandProgress, CommandProgressCreationError> {
-        CommandProgress::new(&self.multi_progress, cmd)
+        CommandProgress::new(
+            &self.multi_progress,
+            cmd,
+            self.line_label.as_deref(),
+            self.logger.clone(),
+        )
+    }
+
+    pub fn time_out(&self) {
+        self.summary.increment_timed_out();
     }
 
     pub fn abort(self) {
         self.summary.increment(TaskState::Failed);
-        self.println(&format!("[{}] {}\n", "ABRT".red(), self.task_name));
+        match self.logger.is_json() {
+            true => self.record(LogLevel::Error, "aborted"),
+            false => self.println_at(
+                LogLevel::Error,
+                &format!("[{}] {}\n", "ABRT".red(), self.task_name),
+            ),
+        }
         self.task_bar.finish_and_clear();
+        self.logger.flush_file();
     }
 
     pub fn finish(self, state: TaskState) {
         self.summary.increment(state);
 
-        let status = match state {
-            TaskState::Success => format!(" {} ", "OK".green()),
-            TaskState::Failed => format!("{}", "FAIL".red()),
-            TaskState::Skipped => format!("{}", "SKIP".yellow()),
+        let level = match state {
+            TaskState::Failed => LogLevel::Error,
+            TaskState::Success | TaskState::Skipped => LogLevel::Info,
         };
 
-        self.println(&format!("[{}] {}\n", status, self.task_name));
+        if self.logger.is_json() {
+            self.record(level, &state.to_string());
+        } else {
+            let status = match state {
+                TaskState::Success => format!(" {} ", "OK".green()),
+                TaskState::Failed => format!("{}", "FAIL".red()),
+                TaskState::Skipped => format!("{}", "SKIP".yellow()),
+            };
+
+            // Logs without spinners do not show how long a task ran
+            let duration = match self.logger.has_plain_progress() {
+                true => format!(" ({})", format_duration(self.started.elapsed())),
+                false => String::new(),
+            };
+
+            self.println_at(
+                level,
+                &format!("[{}] {}{}\n", status, self.task_name, duration),
+            );
+        }
 
         self.task_bar.finish_and_clear();
+        self.logger.flush_file();
     }
 }
@@ -1,5 +1,111 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
 use colored::Colorize;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{
+    HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressState, ProgressStyle,
+    style::ProgressTracker,
+};
+
+use crate::logger::Logger;
+
+use super::{TICK_DURATION_MS_SYSTEM, prefix_lines};
+
+/// Period the transfer rate is averaged over, so that bursty writes do not
+/// make it jump.
+static RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Positions of a transfer during the last [`RATE_WINDOW`].
+#[derive(Clone, Default)]
+struct RateWindow {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateWindow {
+    fn record(&mut self, now: Instant, position: u64) {
+        self.samples.push_back((now, position));
+
+        // The oldest sample within the window is kept, so that the window
+        // stays covered once it is full
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(time, _)| now.duration_since(*time) >= RATE_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Average bytes per second within the window, `None` until two samples
+    /// are apart in time.
+    fn bytes_per_second(&self) -> Option<f64> {
+        let (first_time, first_position) = self.samples.front()?;
+        let (last_time, last_position) = self.samples.back()?;
+        let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+
+        (elapsed > 0.0).then(|| last_position.saturating_sub(*first_position) as f64 / elapsed)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum RateDisplay {
+    BytesPerSecond,
+    Eta,
+}
+
+/// Replaces indicatif's `bytes_per_sec` and `eta`, which are not averaged
+/// over a fixed window.
+#[derive(Clone)]
+struct TransferRate {
+    window: RateWindow,
+    display: RateDisplay,
+}
+
+impl TransferRate {
+    fn new(display: RateDisplay) -> Self {
+        Self {
+            window: RateWindow::default(),
+            display,
+        }
+    }
+}
+
+impl ProgressTracker for TransferRate {
+    fn clone_box(&self) -> Box<dyn ProgressTracker> {
+        Box::new(self.clone())
+    }
+
+    fn tick(&mut self, state: &ProgressState, now: Instant) {
+        self.window.record(now, state.pos());
+    }
+
+    fn reset(&mut self, _: &ProgressState, _: Instant) {
+        self.window = RateWindow::default();
+    }
+
+    fn write(&self, state: &ProgressState, w: &mut dyn Write) {
+        let rate = self.window.bytes_per_second();
+
+        _ = match (self.display, rate) {
+            (RateDisplay::BytesPerSecond, Some(rate)) => {
+                write!(w, "{}/s", HumanBytes(rate as u64))
+            }
+            (RateDisplay::Eta, Some(rate)) if rate > 0.0 => {
+                let remaining = state.len().unwrap_or(0).saturating_sub(state.pos());
+
+                write!(
+                    w,
+                    "{:#}",
+                    HumanDuration(Duration::from_secs_f64(remaining as f64 / rate))
+                )
+            }
+            (RateDisplay::BytesPerSecond | RateDisplay::Eta, _) => write!(w, "-"),
+        };
+    }
+}
 
 pub enum TransferDirection {
     Upload {
@@ -33,6 +139,8 @@ pub struct TransferProgressCreationError(#[from] indicatif::style::TemplateError
 pub struct TransferProgress {
     bar: ProgressBar,
     header: String,
+    /// Writes the header to the log file once finished
+    logger: Logger,
     active: bool,
 }
 
@@ -41,6 +149,7 @@ impl TransferProgress {
         Self {
             bar: ProgressBar::hidden(),
             header: String::new(),
+            logger: Logger::new(),
             active: false,
         }
     }
@@ -49,15 +158,9 @@ impl TransferProgress {
         multi_progress: &MultiProgress,
         direction: TransferDirection,
         total: u64,
+        line_label: Option<&str>,
+        logger: Logger,
     ) -> Result<Self, TransferProgressCreationError> {
-        let bar = multi_progress.insert(0, ProgressBar::new(total));
-
-        bar.set_style(
-            ProgressStyle::default_bar()
-                .template(" {prefix}  {msg}\n       [{bar:30.dim}] {bytes}/{total_bytes}")?
-                .progress_chars("█░ "),
-        );
-
         let label = direction.label().cyan();
 
         let (header, message) = match &direction {
@@ -121,12 +224,39 @@ impl TransferProgress {
             }
         };
 
+        let header = prefix_lines(line_label, &header);
+
+        if logger.has_plain_progress() {
+            logger.print_progress_line(&header);
+
+            return Ok(Self::noop());
+        }
+
+        let bar = multi_progress.insert(0, ProgressBar::new(total));
+
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    " {prefix}  {msg}\n       [{bar:30.dim}] {bytes}/{total_bytes} \
+                     {bytes_per_sec} ETA {eta}",
+                )?
+                .progress_chars("█░ ")
+                .with_key(
+                    "bytes_per_sec",
+                    TransferRate::new(RateDisplay::BytesPerSecond),
+                )
+                .with_key("eta", TransferRate::new(RateDisplay::Eta)),
+        );
+
         bar.set_prefix(format!("{}", label));
         bar.set_message(message);
+        // Keeps the rate falling while a stalled transfer does not advance
+        bar.enable_steady_tick(Duration::from_millis(TICK_DURATION_MS_SYSTEM));
 
         Ok(Self {
             bar,
             header,
+            logger,
             active: true,
         })
     }
@@ -141,6 +271,7 @@ impl TransferProgress {
         if self.active {
             self.bar.println(&self.header);
             self.bar.finish_and_clear();
+            self.logger.write_to_file(&self.header);
         }
     }
 }
@@ -175,3 +306,31 @@ impl<W: std::io::Write> std::io::Write for ProgressWriter<'_, W> {
         self.inner.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RateWindow;
+
+    #[test]
+    fn rate_is_averaged_over_bursty_writes_and_falls_when_stalled() {
+        let start = Instant::now();
+        let mut window = RateWindow::default();
+
+        // 1 MiB written in bursts at every second, nothing in between
+        for second in 0..=4u64 {
+            window.record(start + Duration::from_secs(second), second * 1024 * 1024);
+            window.record(
+                start + Duration::from_secs(second) + Duration::from_millis(900),
+                second * 1024 * 1024,
+            );
+        }
+
+        let rate = window.bytes_per_second().unwrap();
+        assert!((rate - 1024.0 * 1024.0).abs() < 0.2 * 1024.0 * 1024.0);
+
+        window.record(start + Duration::from_secs(12), 4 * 1024 * 1024);
+        assert_eq!(window.bytes_per_second(), Some(0.0));
+    }
+}
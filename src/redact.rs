@@ -0,0 +1,81 @@
+//! Process wide registry of secret values that are replaced with `****`
+//! before anything is displayed, logged or reported.
+//!
+//! The registry is global because output of all parallel workers and error
+//! reports built far away from the Lua state must be scrubbed alike.
+
+use std::{borrow::Cow, sync::RwLock};
+
+use regex::Regex;
+
+static REPLACEMENT: &str = "****";
+
+#[derive(Default)]
+struct Registry {
+    values: Vec<String>,
+    /// Alternation of all values, longest first, so that a value containing
+    /// another one is replaced as a whole
+    pattern: Option<Regex>,
+}
+
+static REGISTRY: RwLock<Registry> = RwLock::new(Registry {
+    values: Vec::new(),
+    pattern: None,
+});
+
+/// Adds `value` to the values that are redacted. Empty values are ignored.
+pub fn register(value: &str) {
+    if value.is_empty() {
+        return;
+    }
+
+    let Ok(mut registry) = REGISTRY.write() else {
+        return;
+    };
+
+    if registry.values.iter().any(|known| known == value) {
+        return;
+    }
+
+    registry.values.push(value.to_string());
+    registry
+        .values
+        .sort_by_key(|value| std::cmp::Reverse(value.len()));
+
+    let alternation = registry
+        .values
+        .iter()
+        .map(|value| regex::escape(value))
+        .collect::<Vec<_>>()
+        .join("|");
+    registry.pattern = Regex::new(&alternation).ok();
+}
+
+/// Replaces all registered values in `text`.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    match REGISTRY.read() {
+        Ok(registry) => match &registry.pattern {
+            Some(pattern) => Cow::Owned(pattern.replace_all(text, REPLACEMENT).into_owned()),
+            None => Cow::Borrowed(text),
+        },
+        Err(_) => Cow::Borrowed(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, register};
+
+    #[test]
+    fn registered_values_are_replaced() {
+        register("hunter2");
+        register("hunter2-extended");
+        register("");
+
+        assert_eq!(
+            redact("login hunter2 and hunter2-extended"),
+            "login **** and ****"
+        );
+        assert_eq!(redact("nothing to hide"), "nothing to hide");
+    }
+}
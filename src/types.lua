@@ -2,9 +2,46 @@
 ---Type definitions for arc v{version}
 
 ---@class CommandResult
----@field stdout string The command output
+---@field stdout string The command output, with invalid UTF-8 replaced by `run_command` and unchanged by `run_command_bytes`
 ---@field stderr string The command error output  
 ---@field exit_code integer The command exit code
+---@field elapsed number The command duration in seconds
+---@field skipped boolean Whether the command was skipped because of a `creates` or `unless` guard or a dry run
+
+
+---@class CommandOptions
+---@field cwd? string Directory to run the command and the `unless` check in
+---@field env? table<string, string> Environment variables of the command and the `unless` check
+---@field check? boolean Raise an error if the command exits with a non-zero code
+---@field creates? string Skip the command if this path exists
+---@field unless? string Skip the command if this command exits with 0
+
+
+---@class WaitOptions
+---@field port? integer Wait until this port accepts TCP connections
+---@field host? string Host whose `port` is checked, as seen from the system (default: "127.0.0.1")
+---@field path? string Wait until this path exists
+---@field command? string Wait until this command exits with 0
+---@field timeout? number Seconds to wait at most (default: 60)
+---@field interval? number Seconds between two checks (default: 1)
+
+
+---@class FileWriteResult
+---@field path string Path of the written file
+---@field bytes_written integer Number of bytes written
+---@field backup_path string|nil Path of the copy of the previous content, if `backup` was set and the file existed
+
+
+---@alias OpenMode "truncate" | "append" | "exclusive"
+
+---@class WriteOptions
+---@field open? OpenMode How the file is opened (default: "truncate"). "exclusive" fails if the file already exists
+---@field sensitive? boolean Hide the content from --diff output (default: false)
+---@field mode? integer Permissions to set on the file (new files default to 0644)
+---@field owner? string|integer User name or uid to own the file
+---@field group? string|integer Group name or gid to own the file
+---@field atomic? boolean Write to a temporary file and rename it over the target (only with open = "truncate", default: false)
+---@field backup? boolean Copy an existing file to `<name>.<timestamp>.bak` before writing (not with open = "exclusive", default: false)
 
 
 ---@class FileMetadata
@@ -12,10 +49,10 @@
 ---@field size integer|nil Size in bytes (nil if unavailable)
 ---@field permissions integer|nil Permission mode as number (nil if unavailable)
 ---@field type "file"|"directory"|"unknown" Type of the item
----@field uid integer|nil User ID of the owner (always nil on local systems)
----@field gid integer|nil Group ID of the owner (always nil on local systems)
----@field accessed integer|nil Last access time as Unix timestamp (nil if unavailable)
----@field modified integer|nil Last modification time as Unix timestamp (nil if unavailable)
+---@field uid integer|nil User ID of the owner (nil if unavailable)
+---@field gid integer|nil Group ID of the owner (nil if unavailable)
+---@field accessed integer|nil Last access time as Unix timestamp in seconds (nil if unavailable)
+---@field modified integer|nil Last modification time as Unix timestamp in seconds (nil if unavailable)
 
 
 ---Lazy file content handle.
@@ -28,18 +65,50 @@ local FileContent = {}
 ---@class File
 ---@field path string Path to the file (can be read and set, setting renames the file)
 ---@field file_name string The name of the file without the directory path (can be read and set; setting renames the file)
----@field content FileContent File content handle (set with string or FileContent)
+---@field content FileContent File content handle (set with string or FileContent, written in place; see File:write for atomic writes)
 ---@field permissions integer|nil File permissions (can be read and set as numeric mode; nil if file doesn't exist)
+---@field owner integer|nil uid of the owner (can be set with a user name or uid; nil if file doesn't exist)
+---@field group integer|nil gid of the group (can be set with a group name or gid; nil if file doesn't exist)
 local File = {}
 
 ---@param key "content"
 ---@param value string|FileContent
 function File:__newindex(key, value) end
 
+---Write content to the file
+---@param content string The content to write
+---@param options? WriteOptions Write options
+---@return FileWriteResult result Write result
+function File:write(content, options) end
+
+---Append content to the end of the file, creating it if it does not exist
+---@param content string The content to append
+---@return FileWriteResult result Write result with the number of bytes appended
+function File:append(content) end
+
 ---Get file metadata. Returns nil if the file does not exist.
 ---@return FileMetadata|nil metadata File metadata information, or nil if file doesn't exist
 function File:metadata() end
 
+---Compute the SHA-256 checksum of the file's content
+---@return string checksum Lowercase hex digest
+function File:sha256() end
+
+---Compute the MD5 checksum of the file's content
+---@return string checksum Lowercase hex digest
+function File:md5() end
+
+---Check if the file was modified more recently than another file, compared in whole seconds.
+---Raises an error if either file does not exist
+---@param other File The file to compare with, which may be on another system
+---@return boolean newer True if this file's modification time is later
+function File:newer_than(other) end
+
+---Check if the file has the same size as another file. Raises an error if either file does not exist
+---@param other File The file to compare with, which may be on another system
+---@return boolean same_size True if both files have the same size in bytes
+function File:same_size(other) end
+
 ---Remove the file
 function File:remove() end
 
@@ -47,19 +116,34 @@ function File:remove() end
 ---@return boolean exists True if file exists
 function File:exists() end
 
+---Check if the path is a regular file
+---@return boolean is_file True if the path exists and is a regular file
+function File:is_file() end
+
+---Check if the path is a directory
+---@return boolean is_directory True if the path exists and is a directory
+function File:is_directory() end
+
 ---Get the directory containing this file. Returns nil if at root path.
 ---@return Directory|nil directory Directory object, or nil if at root
 function File:directory() end
 
 
+---@class RecursivePermissions
+---@field dirs integer? Mode for the directory itself and all directories below it
+---@field files integer? Mode for all files below the directory
+
 ---@class Directory
 ---@field path string Path to the directory (can be read and set, setting renames the directory)
 ---@field file_name string The name of the directory without the parent path (can be read and set)
 ---@field permissions integer|nil Directory permissions (can be read and set as numeric mode; nil if directory doesn't exist)
+---@field owner integer|nil uid of the owner (can be set with a user name or uid; nil if directory doesn't exist)
+---@field group integer|nil gid of the group (can be set with a group name or gid; nil if directory doesn't exist)
 local Directory = {}
 
 ---Create the directory
-function Directory:create() end
+---@param options? CreateDirectoryOptions Create options
+function Directory:create(options) end
 
 ---Remove the directory
 function Directory:remove() end
@@ -68,6 +152,18 @@ function Directory:remove() end
 ---@return boolean exists True if directory exists
 function Directory:exists() end
 
+---Check if the path is a regular file
+---@return boolean is_file True if the path exists and is a regular file
+function Directory:is_file() end
+
+---Check if the path is a directory
+---@return boolean is_directory True if the path exists and is a directory
+function Directory:is_directory() end
+
+---Set the permissions of the directory and everything below it. Symbolic links are neither followed nor changed.
+---@param modes RecursivePermissions Modes for directories and files
+function Directory:set_permissions_recursive(modes) end
+
 ---Get directory metadata. Returns nil if the directory does not exist.
 ---@return FileMetadata|nil metadata Directory metadata information, or nil if directory doesn't exist
 function Directory:metadata() end
@@ -76,23 +172,116 @@ function Directory:metadata() end
 ---@return (File|Directory)[] entries Array of File and Directory objects representing the directory contents
 function Directory:entries() end
 
+---@class CreateDirectoryOptions
+---@field mode? integer Permissions of the directory if it is created, the umask does not apply
+
+---@class GrepOptions
+---@field max_file_size? integer Files larger than this many bytes are skipped (default: 1048576)
+---@field ignore? string[] File and directory names to skip
+
+---@class GrepLineMatch
+---@field line integer The line number, starting at 1
+---@field text string The matching line
+
+---@class GrepResult
+---@field path string Path of the matching file
+---@field matches GrepLineMatch[] The matching lines
+
+---Search the files below the directory for lines matching a regular expression.
+---Unreadable files and subdirectories are skipped with a warning
+---@param pattern string The regular expression to search for
+---@param options? GrepOptions Search options
+---@return GrepResult[] results Matching files sorted by path
+function Directory:grep(pattern, options) end
+
+---@class SearchOptions
+---@field max_depth? integer Number of directory levels searched, 1 only searches the directory itself (default: 32)
+
+---Find the files and directories below the directory whose relative path matches a glob pattern.
+---`*` and `?` match within a path component, `**` matches any number of components. Symbolic links are skipped
+---@param pattern string The glob pattern, e.g. "**/*.conf"
+---@param options? SearchOptions Search options
+---@return (File|Directory)[] entries Matching entries sorted by path
+function Directory:glob(pattern, options) end
+
+---Find the files and directories below the directory for which the predicate returns true. Symbolic links are skipped
+---@param predicate fun(metadata: FileMetadata): boolean Called with the metadata of each entry
+---@param options? SearchOptions Search options
+---@return (File|Directory)[] entries Matching entries sorted by path
+function Directory:find(predicate, options) end
+
+---@class DirectoryCopyResult
+---@field files_transferred integer Number of files copied
+---@field files_unchanged integer Number of files skipped because their content already matched
+---@field files_skipped integer Number of entries not copied because they are neither files nor directories, e.g. symbolic links
+---@field bytes_transferred integer Number of bytes copied
+
+---Copy a local directory tree into this directory, skipping unchanged files
+---@param local_path string The local directory to copy
+---@return DirectoryCopyResult result Copy result
+function Directory:upload(local_path) end
+
+---Copy this directory tree into a local directory, skipping unchanged files
+---@param local_path string The local directory to copy into
+---@return DirectoryCopyResult result Copy result
+function Directory:download(local_path) end
+
 ---Get the parent directory. Returns nil if at root path.
 ---@return Directory|nil parent Parent directory object, or nil if at root
 function Directory:parent() end
 
 
+---@class Shell
+local Shell = {}
+
+---Execute a command in the shell session
+---@param cmd string The command to execute
+---@return CommandResult result Command execution result
+function Shell:run(cmd) end
+
+---Close the shell session
+function Shell:close() end
+
+
 ---@class RemoteSystem
 ---@field name string The name of the system as defined in targets.systems
 ---@field type "remote" The type of system
 ---@field address string The IP address of the system
 ---@field port integer The SSH port of the system
 ---@field user string The SSH user used to connect to the system
+---@field vars table<string, any> Variables of the system, including those inherited from its groups
+---@field groups string[] Names of the groups the system is a member of, sorted
 local RemoteSystem = {}
 
 ---Execute a command on the remote system
 ---@param cmd string The command to execute
+---@param options? CommandOptions Guards that skip the command if its effect is already in place
 ---@return CommandResult result Command execution result
-function RemoteSystem:run_command(cmd) end
+function RemoteSystem:run_command(cmd, options) end
+
+---Execute a command on the remote system and keep its output byte for byte, e.g. binary output
+---@param cmd string The command to execute
+---@param options? CommandOptions Guards that skip the command if its effect is already in place
+---@return CommandResult result Command execution result with the raw bytes of `stdout` and `stderr`
+function RemoteSystem:run_command_bytes(cmd, options) end
+
+---Open a shell session on the remote system that keeps environment and working directory between commands
+---@return Shell shell Shell session object
+function RemoteSystem:shell() end
+
+---Reboot the remote system and wait until it accepts SSH connections again
+---@param timeout? number Seconds to wait for the system to come back (default: 600)
+---@return number? downtime Seconds the system was unreachable, `nil` in a dry run
+function RemoteSystem:reboot(timeout) end
+
+---Wait until exactly one of the conditions `port`, `path` or `command` holds on the remote system
+---@param options WaitOptions The condition and how long and how often it is checked
+---@return number? waited Seconds it waited, `nil` in a dry run
+function RemoteSystem:wait_for(options) end
+
+---Notify a task with `on_notify` to run on the remote system after all other tasks
+---@param task_name string Name of the notified task
+function RemoteSystem:notify(task_name) end
 
 ---Get a File object representing a file on the remote system
 ---@param path string Path to the file
@@ -111,12 +300,39 @@ function RemoteSystem:directory(path) end
 ---@field address nil The IP address of the system
 ---@field port nil The SSH port of the system
 ---@field user nil The SSH user used to connect to the system
+---@field vars table<string, any> Variables of the system, including those inherited from its groups
+---@field groups string[] Names of the groups the system is a member of, sorted
 local LocalSystem = {}
 
 ---Execute a command on the local system
 ---@param cmd string The command to execute
+---@param options? CommandOptions Guards that skip the command if its effect is already in place
 ---@return CommandResult result Command execution result
-function LocalSystem:run_command(cmd) end
+function LocalSystem:run_command(cmd, options) end
+
+---Execute a command on the local system and keep its output byte for byte, e.g. binary output
+---@param cmd string The command to execute
+---@param options? CommandOptions Guards that skip the command if its effect is already in place
+---@return CommandResult result Command execution result with the raw bytes of `stdout` and `stderr`
+function LocalSystem:run_command_bytes(cmd, options) end
+
+---Open a shell session on the local system that keeps environment and working directory between commands
+---@return Shell shell Shell session object
+function LocalSystem:shell() end
+
+---Not supported: local systems cannot be rebooted and raise an error
+---@param timeout? number
+---@return number? downtime
+function LocalSystem:reboot(timeout) end
+
+---Wait until exactly one of the conditions `port`, `path` or `command` holds on the local system
+---@param options WaitOptions The condition and how long and how often it is checked
+---@return number? waited Seconds it waited, `nil` in a dry run
+function LocalSystem:wait_for(options) end
+
+---Notify a task with `on_notify` to run on the local system after all other tasks
+---@param task_name string Name of the notified task
+function LocalSystem:notify(task_name) end
 
 ---Get a File object representing a file on the local system
 ---@param path string Path to the file
@@ -132,34 +348,68 @@ function LocalSystem:directory(path) end
 ---Behavior when a task fails
 ---@alias FailureBehavior "continue" | "skip_system" | "abort"
 
+---Task parameter type
+---@alias TaskParamType "string" | "number" | "integer" | "boolean"
+
+---@class TaskParam
+---@field type? TaskParamType Type the --var value is converted to (default: "string")
+---@field required? boolean Whether the parameter must be given unless it has a default
+---@field default? any Value used when the parameter is not given. Must match `type`
+
 ---Task execution state
 ---@alias TaskState "success" | "failed" | "skipped"
 
 ---@class TaskDefinition
----@field handler fun(system: RemoteSystem|LocalSystem): any The function that implements the task
----@field when? fun(): boolean Guard predicate to determine if task should run (check previous task states/results)
+---@field handler fun(system: RemoteSystem|LocalSystem, params: table<string, any>): any The function that implements the task
+---@field when? fun(system: RemoteSystem|LocalSystem): boolean Guard predicate to determine if task should run on the system (check previous task states/results)
 ---@field on_fail? FailureBehavior Behavior when this task fails (default: "continue")
 ---@field tags? string[] Array of tags associated with the task, used for filtering
----@field targets? string[] Array of group or system names this task should run on
+---@field targets? string[] Names of the systems and groups this task runs on, among the systems selected on the command line; all of them if omitted
 ---@field requires? string[] Array of tags that this task requires
 ---@field important? boolean If true, task always runs regardless of tag filters, --no-reqs, and skip_system
----@field result? any The result of the task execution (available after execution)
+---@field on_notify? boolean If true, task only runs when notified with `system:notify()`, after all other tasks of the system
+---@field timeout? number Maximum handler execution time in seconds; Lua code and running commands are interrupted and the task fails when exceeded
+---@field retries? integer Number of times a failed handler is run again (default: 0)
+---@field serial? integer|string Roll the task out in batches of this many systems or this percentage of systems, e.g. "25%"
+---@field params? table<string, TaskParam> Parameters filled from --var values and passed to the handler
+---@field result? any The result of the task execution on the current system (nil until the task has run)
 ---@field state? TaskState State of the task after execution
 ---@field error? string Error message if the task failed
 
+---@class SshAlgorithms
+---@field kex? string[] Key exchange algorithms in order of preference
+---@field host_key? string[] Host key algorithms in order of preference
+---@field ciphers? string[] Ciphers in order of preference
+---@field macs? string[] MAC algorithms in order of preference
+
+---@alias HostKeyCheck "strict" | "accept_new" | "off"
+
 ---@class RemoteSystemDefinition
 ---@field type? "remote" System type (optional, defaults to "remote")
 ---@field address string IP address or hostname of the system
 ---@field user string SSH username for the system
 ---@field port? integer SSH port (defaults to 22)
+---@field password? string SSH password, tried after the private key and before the SSH agent
+---@field private_key_path? string Path to a private key file, tried first
+---@field passphrase? string Passphrase of the private key file
+---@field connect_timeout? number Seconds to wait for the connection and SSH handshake (defaults to 30)
+---@field operation_timeout? number Seconds a single blocking SSH operation may take (defaults to no limit)
+---@field keepalive_interval? number Seconds between SSH keepalive messages while waiting for command output
+---@field host_key_check? HostKeyCheck How the host key is verified against known_hosts (defaults to arc.config or "accept_new")
+---@field algorithms? SshAlgorithms SSH algorithm preferences (defaults to the SSH library's preferences)
+---@field jump_host? RemoteSystemDefinition Bastion to tunnel the connection through, may have its own jump_host
+---@field vars? table<string, any> Variables of the system, overriding those of its groups
 
 ---@class LocalSystemDefinition
 ---@field type "local" System type - must be "local" to target the local system
+---@field vars? table<string, any> Variables of the system, overriding those of its groups
 
 ---@alias SystemDefinition RemoteSystemDefinition|LocalSystemDefinition
 
 ---@class GroupDefinition
----@field members string[] List of system names that belong to this group
+---@field members string[] Names of the systems and groups that belong to this group
+---@field vars? table<string, any> Variables inherited by the member systems
+---@field confirm? boolean Ask for confirmation before running against the member systems
 
 ---@class TargetsConfig
 ---@field systems table<string, SystemDefinition> Map of system names to system definitions
@@ -176,27 +426,65 @@ targets = {
     groups = {}
 }
 
+---Inventory module
+---@class InventoryModule
+local Inventory = {}
+
+---Define the systems and groups of a YAML or TOML inventory file
+---@param path string Path of a .yaml, .yml or .toml file, relative to the project directory
+function Inventory.load(path) end
+
+---Inventory module
+---@type InventoryModule
+inventory = {}
+
 ---Environment variables module
 ---@class EnvModule
 local Env = {}
 
 ---Get the value of an environment variable
 ---@param var_name string Name of the environment variable
----@return string|nil value Value of the environment variable or nil if not set
-function Env.get(var_name) end
+---@param default? string Value to return if the variable is not set
+---@return string|nil value Value of the environment variable, or `default` if not set
+function Env.get(var_name, default) end
+
+---Get the value of an environment variable. Errors if the variable is not set
+---@param var_name string Name of the environment variable
+---@return string value Value of the environment variable
+function Env.require(var_name) end
 
 ---Environment variables module
 ---@type EnvModule
 env = {}
 
+---Secrets module. Values come from ARC_SECRET_<NAME> environment variables or the encrypted secrets.vault
+---@class SecretsModule
+local Secrets = {}
+
+---Get the value of a secret. Errors if the secret is not defined or the vault cannot be decrypted
+---@param name string Name of the secret
+---@return string value Value of the secret
+function Secrets.get(name) end
+
+---Secrets module
+---@type SecretsModule
+secrets = {}
+
 --- Global host class for accessing the host system
 ---@class Host
 local Host = {}
 
 ---Execute a command on the local system
 ---@param cmd string The command to execute
+---@param options? CommandOptions Guards that skip the command if its effect is already in place
 ---@return CommandResult result Command execution result
-function Host:run_command(cmd) end
+function Host:run_command(cmd, options) end
+
+---Execute a command on the local system and keep its output byte for byte, e.g. binary output
+---@param cmd string The command to execute
+---@param options? CommandOptions Guards that skip the command if its effect is already in place
+---@return CommandResult result Command execution result with the raw bytes of `stdout` and `stderr`
+function Host:run_command_bytes(cmd, options) end
 
 ---Get a File object representing a file on the local system
 ---@param path string Path to the file
@@ -227,11 +515,20 @@ function JsonFormat.encode(value) end
 ---@return string json Pretty-printed JSON string
 function JsonFormat.encode_pretty(value) end
 
----Decode a JSON string to a Lua value
+---Decode a JSON string to a Lua value, `null` becomes `JsonFormat.null`
 ---@param input string JSON string to decode
 ---@return any value Decoded Lua value
 function JsonFormat.decode(input) end
 
+---Mark a table as an array, so that it is encoded as `[]` even when it is empty
+---@param table table? The table to mark, a new empty table if not given
+---@return table array The marked table
+function JsonFormat.array(table) end
+
+---The value of JSON `null`, which is kept in tables unlike `nil`
+---@type lightuserdata
+JsonFormat.null = nil
+
 
 ---TOML format
 ---@class TomlFormat
@@ -302,26 +599,107 @@ local Format = {}
 format = {}
 
 
+---Human-readable formatting module
+---@class FmtModule
+local Fmt = {}
+
+---Format a byte count using binary units (e.g. "1.50 GiB")
+---@param n number Number of bytes
+---@return string formatted Formatted byte count
+function Fmt.bytes(n) end
+
+---Format a duration (e.g. "2m 3s")
+---@param secs number Duration in seconds
+---@return string formatted Formatted duration
+function Fmt.duration(secs) end
+
+---Human-readable formatting module
+---@type FmtModule
+fmt = {}
+
+
+---Shell quoting module
+---@class ShellModule
+local Shell = {}
+
+---Quote a value as a single shell argument
+---@param value string Value to quote
+---@return string quoted The value in single quotes
+function Shell.quote(value) end
+
+---Quote each argument and join them with spaces
+---@param arguments string[] List of arguments
+---@return string joined Command line the shell splits into exactly these arguments
+function Shell.join(arguments) end
+
+---Shell quoting module
+---@type ShellModule
+shell = {}
+
+
+---Options for rendering a template
+---@class RenderOptions
+---@field autoescape boolean? HTML-escape all values inserted into the template (default: false)
+
 ---Template rendering module
 ---@class TemplateModule
 local Template = {}
 
 ---Render a template with given context
 ---@param template_content string|FileContent Template content
----@param context table Variables to use for template rendering
+---@param context table Variables to use for template rendering, `system` is set to the system of the running task unless given
+---@param options RenderOptions? Render options
+---@return string rendered Rendered template as string
+function Template.render(template_content, context, options) end
+
+---Render a template file from the machine running arc
+---@param path string Path of the template file
+---@param context table Variables to use for template rendering, `system` is set to the system of the running task unless given
+---@param options RenderOptions? Render options
 ---@return string rendered Rendered template as string
-function Template.render(template_content, context) end
+function Template.render_file(path, context, options) end
+
+---Render a template file and write the result to a file on a system
+---@param path string Path of the template file on the machine running arc
+---@param target_path string Path of the file to write on `system`
+---@param context table Variables to use for template rendering, `system` is set to the system of the running task unless given
+---@param system System System to write the rendered file to
+---@param options RenderOptions? Render options
+---@return FileWriteResult result Write result
+function Template.render_file_to(path, target_path, context, system, options) end
+
+---Options for rendering a directory of templates
+---@class RenderTreeOptions : RenderOptions
+---@field skip string[]? Patterns of files copied without rendering, matched against the relative path if they contain "/", otherwise against the file name
+
+---@class TemplateTreeResult
+---@field files_rendered integer Number of files rendered
+---@field files_copied integer Number of files copied unchanged because they matched a skip pattern
+
+---Render every file below a local directory into the same relative path below a directory on a system, keeping permissions
+---@param path string Path of the template directory on the machine running arc
+---@param target_path string Path of the directory to write to on `system`
+---@param context table Variables to use for rendering every template, `system` is set to the system of the running task unless given
+---@param system System System to write the rendered files to
+---@param options RenderTreeOptions? Render options
+---@return TemplateTreeResult result Number of rendered and copied files
+function Template.render_tree(path, target_path, context, system, options) end
+
+---Register a filter that can be used in all templates
+---@param name string Name of the filter
+---@param filter fun(value: any, args: table): any Function returning the filtered value
+function Template.add_filter(name, filter) end
 
 ---Template rendering module
 ---@type TemplateModule
 template = {}
 
 
----Logging utilities
+---Logging utilities. Messages are written to stdout, or to stderr when --json is given
 ---@class LogModule
 local Log = {}
 
----Log a debug message
+---Log a debug message, only shown with `--log-level debug` or `-v`
 ---@param message any Debug message to log
 function Log.debug(message) end
 
@@ -342,12 +720,28 @@ function Log.error(message) end
 log = {}
 
 
+---@class ArcConfig
+---@field notify_webhook? string URL that receives the JSON run summary via POST when the run finishes
+---@field retry_budget? integer Maximum number of task retries across the whole run
+---@field host_key_check? HostKeyCheck Default host key verification policy for remote systems
+---@field tasks_directory? string Directory whose .lua files are loaded after arc.lua (default: "tasks")
+
 ---Global arc configuration object
 ---@class ArcObject
 ---@field project_root_path string The absolute path of the directory containing arc.lua
 ---@field home_path string The absolute path of the user's home directory
+---@field vars table<string, string> Variables given on the command line via --var
 local Arc = {}
 
+---Configure run behavior. Options that are not given keep their current value.
+---@param config ArcConfig Configuration options
+function Arc.config(config) end
+
+---Hide a value from all output of arc, which shows `****` instead
+---@param value string The sensitive value
+---@return string value The value unchanged
+function Arc.redact(value) end
+
 ---Global arc instance
 ---@type ArcObject
 arc = {}
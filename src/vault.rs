@@ -0,0 +1,213 @@
+//! Encrypted storage for secrets used by the `secrets` Lua module.
+//!
+//! A vault file consists of the header line `arc-vault:1` followed by a line
+//! with the base64 encoded salt (16 bytes), nonce (12 bytes), ciphertext and
+//! GCM tag (16 bytes). The ciphertext is a JSON object mapping secret names
+//! to values, encrypted with AES-256-GCM. The key is derived from the
+//! `ARC_VAULT_KEY` passphrase with PBKDF2-HMAC-SHA256.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use openssl::{hash::MessageDigest, symm::Cipher};
+
+pub static VAULT_FILE: &str = "secrets.vault";
+pub static VAULT_KEY_VARIABLE: &str = "ARC_VAULT_KEY";
+
+static HEADER: &str = "arc-vault:1";
+static SALT_LENGTH: usize = 16;
+static NONCE_LENGTH: usize = 12;
+static TAG_LENGTH: usize = 16;
+static KEY_LENGTH: usize = 32;
+static KEY_DERIVATION_ITERATIONS: usize = 600_000;
+
+pub type Secrets = BTreeMap<String, String>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VaultError {
+    #[error("Failed to read vault {0:?}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("Failed to write vault {0:?}")]
+    Write(PathBuf, #[source] std::io::Error),
+    #[error("{VAULT_KEY_VARIABLE} is not set")]
+    MissingKey,
+    #[error("Vault {0:?} is not a valid arc vault")]
+    InvalidFormat(PathBuf),
+    #[error("Failed to decrypt vault {0:?}, the key is wrong or the file was modified")]
+    Decryption(PathBuf),
+    #[error("Failed to encrypt vault")]
+    Encryption(#[source] openssl::error::ErrorStack),
+    #[error("Vault {0:?} does not contain valid secrets")]
+    Content(PathBuf, #[source] serde_json::Error),
+}
+
+fn vault_key() -> Result<String, VaultError> {
+    std::env::var(VAULT_KEY_VARIABLE).map_err(|_| VaultError::MissingKey)
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<[u8; KEY_LENGTH], openssl::error::ErrorStack> {
+    let mut key = [0u8; KEY_LENGTH];
+    openssl::pkcs5::pbkdf2_hmac(
+        passphrase.as_bytes(),
+        salt,
+        KEY_DERIVATION_ITERATIONS,
+        MessageDigest::sha256(),
+        &mut key,
+    )?;
+
+    Ok(key)
+}
+
+/// Reads and decrypts the vault at `path`. A missing vault contains no
+/// secrets.
+pub fn load(path: &Path) -> Result<Secrets, VaultError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Secrets::new());
+        }
+        Err(error) => return Err(VaultError::Read(path.to_path_buf(), error)),
+    };
+
+    decrypt(&content, &vault_key()?, path)
+}
+
+fn decrypt(content: &str, passphrase: &str, path: &Path) -> Result<Secrets, VaultError> {
+    let invalid_format = || VaultError::InvalidFormat(path.to_path_buf());
+
+    let mut lines = content.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(invalid_format());
+    }
+    let data = lines
+        .next()
+        .and_then(|line| STANDARD.decode(line.trim()).ok())
+        .filter(|data| data.len() >= SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH)
+        .ok_or_else(invalid_format)?;
+
+    let (salt, rest) = data.split_at(SALT_LENGTH);
+    let (nonce, rest) = rest.split_at(NONCE_LENGTH);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LENGTH);
+
+    let decryption_error = |_| VaultError::Decryption(path.to_path_buf());
+    let key = derive_key(passphrase, salt).map_err(decryption_error)?;
+    let plaintext = openssl::symm::decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(nonce),
+        HEADER.as_bytes(),
+        ciphertext,
+        tag,
+    )
+    .map_err(decryption_error)?;
+
+    serde_json::from_slice(&plaintext)
+        .map_err(|error| VaultError::Content(path.to_path_buf(), error))
+}
+
+/// Encrypts `secrets` with a fresh salt and nonce and writes them to `path`.
+pub fn save(path: &Path, secrets: &Secrets) -> Result<(), VaultError> {
+    let content = encrypt(secrets, &vault_key()?).map_err(VaultError::Encryption)?;
+
+    std::fs::write(path, content).map_err(|error| VaultError::Write(path.to_path_buf(), error))
+}
+
+fn encrypt(secrets: &Secrets, passphrase: &str) -> Result<String, openssl::error::ErrorStack> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    openssl::rand::rand_bytes(&mut salt)?;
+    openssl::rand::rand_bytes(&mut nonce)?;
+
+    let key = derive_key(passphrase, &salt)?;
+    let plaintext = serde_json::to_vec(secrets).expect("secrets are serializable");
+    let mut tag = [0u8; TAG_LENGTH];
+    let ciphertext = openssl::symm::encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&nonce),
+        HEADER.as_bytes(),
+        &plaintext,
+        &mut tag,
+    )?;
+
+    let data = [&salt[..], &nonce, &ciphertext, &tag].concat();
+
+    Ok(format!("{}\n{}\n", HEADER, STANDARD.encode(data)))
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to read the secret value from stdin")]
+pub struct SecretInputError(#[source] std::io::Error);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to update secrets")]
+pub enum SecretsCommandError {
+    Vault(#[from] VaultError),
+    Input(#[from] SecretInputError),
+}
+
+/// Stores the value read from stdin as `name`, without its trailing newline.
+pub fn set_secret(path: &Path, name: String) -> Result<(), SecretsCommandError> {
+    let mut secrets = load(path)?;
+    let mut value = std::io::read_to_string(std::io::stdin()).map_err(SecretInputError)?;
+
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+
+    secrets.insert(name, value);
+
+    Ok(save(path, &secrets)?)
+}
+
+pub fn remove_secret(path: &Path, name: &str) -> Result<(), SecretsCommandError> {
+    let mut secrets = load(path)?;
+
+    if secrets.remove(name).is_some() {
+        save(path, &secrets)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the names of the stored secrets, never their values.
+pub fn list_secrets(path: &Path) -> Result<(), SecretsCommandError> {
+    for name in load(path)?.keys() {
+        println!("{}", name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Secrets, VaultError, decrypt, encrypt};
+
+    #[test]
+    fn vault_content_round_trips_only_with_the_right_key() {
+        let secrets = Secrets::from([("token".to_string(), "s3cr3t".to_string())]);
+        let content = encrypt(&secrets, "passphrase").unwrap();
+        let path = Path::new("secrets.vault");
+
+        assert_eq!(decrypt(&content, "passphrase", path).unwrap(), secrets);
+        assert!(matches!(
+            decrypt(&content, "wrong", path),
+            Err(VaultError::Decryption(_))
+        ));
+        assert!(matches!(
+            decrypt("not a vault", "passphrase", path),
+            Err(VaultError::InvalidFormat(_))
+        ));
+    }
+}